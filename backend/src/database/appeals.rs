@@ -0,0 +1,263 @@
+use crate::database::audit::audit_timestamp;
+use crate::database::operations::set_student_field;
+use crate::utils::types::AppError;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+// Fields of a weekly row a participant can contest
+pub const APPEALABLE_FIELDS: &[&str] = &[
+    "attendance",
+    "fa",
+    "fb",
+    "fc",
+    "fd",
+    "bonus_attempt",
+    "bonus_answer_quality",
+    "bonus_follow_up",
+    "exercise_submitted",
+    "exercise_test_passing",
+    "exercise_good_documentation",
+    "exercise_good_structure",
+];
+
+#[derive(Debug, Error)]
+pub enum AppealError {
+    #[error("{0} can't be appealed")]
+    UnknownCriterion(String),
+    #[error("No score recorded for week {0}")]
+    UnknownWeek(i32),
+    #[error("There is already a pending appeal for this week and criterion")]
+    AlreadyPending,
+    #[error("Appeal not found")]
+    NotFound,
+    #[error("Appeal was already resolved")]
+    AlreadyResolved,
+    #[error("Invalid value {0:?} for {1}")]
+    InvalidValue(String, String),
+    #[error("Accepting an appeal needs the new value")]
+    MissingValue,
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+impl From<rusqlite::Error> for AppealError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppealError::App(AppError::Database(e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Appeal {
+    pub id: i64,
+    pub student: String,
+    pub email: String,
+    pub week: i32,
+    pub criterion: String,
+    pub comment: String,
+    // "pending", "accepted" or "rejected"
+    pub status: String,
+    pub created_at: String,
+    pub resolved_at: Option<String>,
+    pub resolved_by: Option<String>,
+    pub decision_comment: Option<String>,
+    // Score before and after an accepted appeal
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+// What a TA decides on an appeal. Accepting needs `new_value`, which is applied to the score.
+#[derive(Debug, Deserialize)]
+pub struct AppealDecision {
+    pub accept: bool,
+    pub new_value: Option<String>,
+    pub comment: Option<String>,
+}
+
+fn ensure_appeals_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS appeals (
+            id               INTEGER PRIMARY KEY AUTOINCREMENT,
+            student          TEXT NOT NULL,
+            email            TEXT NOT NULL,
+            week             INTEGER NOT NULL,
+            criterion        TEXT NOT NULL,
+            comment          TEXT NOT NULL,
+            status           TEXT NOT NULL DEFAULT 'pending',
+            created_at       TEXT NOT NULL,
+            resolved_at      TEXT,
+            resolved_by      TEXT,
+            decision_comment TEXT,
+            old_value        TEXT,
+            new_value        TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_appeals_status ON appeals (status);",
+    )
+}
+
+fn appeal_from_row(row: &Row) -> rusqlite::Result<Appeal> {
+    Ok(Appeal {
+        id: row.get("id")?,
+        student: row.get("student")?,
+        email: row.get("email")?,
+        week: row.get("week")?,
+        criterion: row.get("criterion")?,
+        comment: row.get("comment")?,
+        status: row.get("status")?,
+        created_at: row.get("created_at")?,
+        resolved_at: row.get("resolved_at")?,
+        resolved_by: row.get("resolved_by")?,
+        decision_comment: row.get("decision_comment")?,
+        old_value: row.get("old_value")?,
+        new_value: row.get("new_value")?,
+    })
+}
+
+// Files an appeal for the student whose weekly row carries `email`
+pub fn file_appeal(
+    db_path: &Path,
+    email: &str,
+    week: i32,
+    criterion: &str,
+    comment: &str,
+) -> Result<Appeal, AppealError> {
+    if !APPEALABLE_FIELDS.contains(&criterion) {
+        return Err(AppealError::UnknownCriterion(criterion.to_string()));
+    }
+    let conn = Connection::open(db_path)?;
+    ensure_appeals_table(&conn)?;
+
+    let student: String = conn
+        .query_row(
+            "SELECT name FROM students WHERE lower(trim(mail)) = lower(trim(?1)) AND week = ?2",
+            params![email, week],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(AppealError::UnknownWeek(week))?;
+
+    let pending: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM appeals
+         WHERE student = ?1 AND week = ?2 AND criterion = ?3 AND status = 'pending'",
+        params![student, week, criterion],
+        |row| row.get(0),
+    )?;
+    if pending {
+        return Err(AppealError::AlreadyPending);
+    }
+
+    conn.execute(
+        "INSERT INTO appeals (student, email, week, criterion, comment, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![student, email, week, criterion, comment, audit_timestamp()],
+    )?;
+    Ok(conn.query_row(
+        "SELECT * FROM appeals WHERE id = ?1",
+        params![conn.last_insert_rowid()],
+        appeal_from_row,
+    )?)
+}
+
+// Appeals of a cohort, oldest first. `status` narrows to one status, `email` to one participant.
+pub fn list_appeals(
+    db_path: &Path,
+    status: Option<&str>,
+    email: Option<&str>,
+) -> Result<Vec<Appeal>, AppealError> {
+    let conn = Connection::open(db_path)?;
+    ensure_appeals_table(&conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT * FROM appeals
+         WHERE (?1 IS NULL OR status = ?1)
+           AND (?2 IS NULL OR lower(trim(email)) = lower(trim(?2)))
+         ORDER BY id",
+    )?;
+    let appeals = stmt
+        .query_map(params![status, email], appeal_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(appeals)
+}
+
+// Resolves a pending appeal. Accepting updates the score to `new_value` through the audited
+// write path in the same transaction, so the appeal and the grade change land together.
+pub fn resolve_appeal(
+    db_path: &Path,
+    id: i64,
+    decision: &AppealDecision,
+    actor: &str,
+) -> Result<Appeal, AppealError> {
+    let mut conn = Connection::open(db_path)?;
+    ensure_appeals_table(&conn)?;
+    let tx = conn.transaction()?;
+
+    let appeal = tx
+        .query_row(
+            "SELECT * FROM appeals WHERE id = ?1",
+            params![id],
+            appeal_from_row,
+        )
+        .optional()?
+        .ok_or(AppealError::NotFound)?;
+    if appeal.status != "pending" {
+        return Err(AppealError::AlreadyResolved);
+    }
+
+    let (mut old_value, mut new_value) = (None, None);
+    if decision.accept {
+        let value = decision
+            .new_value
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .ok_or(AppealError::MissingValue)?;
+        // Attendance and exercise criteria are yes/no; the rest are scores
+        let yes_no = appeal.criterion.starts_with("exercise_") || appeal.criterion == "attendance";
+        let valid = if yes_no {
+            value == "yes" || value == "no"
+        } else {
+            value.parse::<u64>().is_ok()
+        };
+        if !valid {
+            return Err(AppealError::InvalidValue(
+                value.to_string(),
+                appeal.criterion.clone(),
+            ));
+        }
+        (old_value, new_value) = set_student_field(
+            &tx,
+            &appeal.student,
+            appeal.week,
+            &appeal.criterion,
+            Some(value),
+            actor,
+        )?
+        .ok_or(AppealError::UnknownWeek(appeal.week))?;
+    }
+
+    tx.execute(
+        "UPDATE appeals SET status = ?2, resolved_at = ?3, resolved_by = ?4,
+             decision_comment = ?5, old_value = ?6, new_value = ?7
+         WHERE id = ?1",
+        params![
+            id,
+            if decision.accept {
+                "accepted"
+            } else {
+                "rejected"
+            },
+            audit_timestamp(),
+            actor,
+            decision.comment,
+            old_value,
+            new_value
+        ],
+    )?;
+    let appeal = tx.query_row(
+        "SELECT * FROM appeals WHERE id = ?1",
+        params![id],
+        appeal_from_row,
+    )?;
+    tx.commit()?;
+    Ok(appeal)
+}
@@ -0,0 +1,267 @@
+use crate::database::operations::read_from_db;
+use crate::utils::types::{AppError, AuditEntry, RowData, Table};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use log::warn;
+use rusqlite::{Connection, ToSql, params};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// Filters accepted by `GET /audit/{cohort_name}`. All of them are optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub student: Option<String>,
+    pub week: Option<i32>,
+    pub field: Option<String>,
+    pub actor: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+}
+
+pub fn ensure_audit_table(conn: &Connection) -> Result<(), AppError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            changed_at  TEXT NOT NULL,
+            actor       TEXT NOT NULL,
+            cohort      TEXT NOT NULL,
+            student     TEXT NOT NULL,
+            week        INTEGER NOT NULL,
+            action      TEXT NOT NULL,
+            field       TEXT,
+            old_value   TEXT,
+            new_value   TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_log_student ON audit_log (student, week);
+        CREATE INDEX IF NOT EXISTS idx_audit_log_changed_at ON audit_log (changed_at);",
+    )?;
+    Ok(())
+}
+
+// Timestamps are stored as fixed-width RFC 3339 so they sort and compare as text
+pub fn audit_timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Accepts RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC) or a bare date and returns the stored format
+pub fn normalize_timestamp(input: &str) -> Option<String> {
+    let parsed = DateTime::parse_from_rfc3339(input)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S"))
+                .ok()
+                .map(|dt| dt.and_utc())
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|dt| dt.and_utc())
+        })?;
+    Some(parsed.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+// Cohort label stored with each entry, e.g. `pb_cohort.db`
+pub fn cohort_label(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+// Record the difference between the stored row (if any) and the row being written.
+// Returns the number of entries added.
+pub fn record_row_change(
+    conn: &Connection,
+    cohort: &str,
+    actor: &str,
+    old: Option<&RowData>,
+    new: &RowData,
+) -> Result<usize, AppError> {
+    let changed_at = audit_timestamp();
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO audit_log (changed_at, actor, cohort, student, week, action, field, old_value, new_value)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+
+    let Some(old) = old else {
+        stmt.execute(params![
+            changed_at,
+            actor,
+            cohort,
+            new.name,
+            new.week,
+            "insert",
+            None::<String>,
+            None::<String>,
+            serde_json::to_string(new).unwrap_or_default(),
+        ])?;
+        return Ok(1);
+    };
+
+    let mut recorded = 0;
+    for ((field, old_value), (_, new_value)) in
+        old.field_values().into_iter().zip(new.field_values())
+    {
+        if old_value != new_value {
+            stmt.execute(params![
+                changed_at, actor, cohort, new.name, new.week, "update", field, old_value,
+                new_value,
+            ])?;
+            recorded += 1;
+        }
+    }
+    Ok(recorded)
+}
+
+pub fn record_row_delete(
+    conn: &Connection,
+    cohort: &str,
+    actor: &str,
+    old: &RowData,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO audit_log (changed_at, actor, cohort, student, week, action, field, old_value, new_value)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'delete', NULL, ?6, NULL)",
+        params![
+            audit_timestamp(),
+            actor,
+            cohort,
+            old.name,
+            old.week,
+            serde_json::to_string(old).unwrap_or_default(),
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn read_audit_log(path: &Path, filter: &AuditFilter) -> Result<Vec<AuditEntry>, AppError> {
+    let conn = Connection::open(path)?;
+    ensure_audit_table(&conn)?;
+
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+    if let Some(student) = &filter.student {
+        clauses.push("student = ?");
+        values.push(Box::new(student.clone()));
+    }
+    if let Some(week) = filter.week {
+        clauses.push("week = ?");
+        values.push(Box::new(week));
+    }
+    if let Some(field) = &filter.field {
+        clauses.push("field = ?");
+        values.push(Box::new(field.clone()));
+    }
+    if let Some(actor) = &filter.actor {
+        clauses.push("actor = ?");
+        values.push(Box::new(actor.clone()));
+    }
+    // Bounds are compared in the stored format; ones that aren't timestamps are ignored
+    let bound = |value: &Option<String>| {
+        let value = value.as_deref()?;
+        let normalized = normalize_timestamp(value.trim());
+        if normalized.is_none() {
+            warn!("Ignoring invalid audit log bound {:?}", value);
+        }
+        normalized
+    };
+    if let Some(since) = bound(&filter.since) {
+        clauses.push("changed_at >= ?");
+        values.push(Box::new(since));
+    }
+    if let Some(until) = bound(&filter.until) {
+        clauses.push("changed_at <= ?");
+        values.push(Box::new(until));
+    }
+
+    let mut sql = "SELECT id, changed_at, actor, cohort, student, week, action, field, old_value, new_value FROM audit_log".to_string();
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(" ORDER BY id DESC");
+    if let Some(limit) = filter.limit {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+    let entries = stmt
+        .query_map(params.as_slice(), |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                changed_at: row.get(1)?,
+                actor: row.get(2)?,
+                cohort: row.get(3)?,
+                student: row.get(4)?,
+                week: row.get(5)?,
+                action: row.get(6)?,
+                field: row.get(7)?,
+                old_value: row.get(8)?,
+                new_value: row.get(9)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+// Rebuilds the `students` table as it was at `at` (normalized timestamp) by undoing every
+// logged change made after it, newest first, on top of the current rows.
+pub fn reconstruct_students_at(path: &PathBuf, at: &str) -> Result<Table, AppError> {
+    let mut table = read_from_db(path)?;
+    let conn = Connection::open(path)?;
+    ensure_audit_table(&conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT student, week, action, field, old_value FROM audit_log WHERE changed_at > ?1 ORDER BY id DESC",
+    )?;
+    let entries = stmt
+        .query_map(params![at], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (student, week, action, field, old_value) in entries {
+        let position = table
+            .rows
+            .iter()
+            .position(|r| r.name == student && r.week == week);
+        match (action.as_str(), position) {
+            ("insert", Some(pos)) => {
+                table.rows.remove(pos);
+            }
+            ("delete", None) => {
+                match serde_json::from_str::<RowData>(old_value.as_deref().unwrap_or_default()) {
+                    Ok(row) => table.rows.push(row),
+                    Err(e) => warn!(
+                        "Unreadable deleted row for {} week {}: {}",
+                        student, week, e
+                    ),
+                }
+            }
+            ("update", Some(pos)) => {
+                let field = field.unwrap_or_default();
+                if !table.rows[pos].set_field(&field, old_value.as_deref()) {
+                    warn!("Skipping unknown audited field {:?}", field);
+                }
+            }
+            _ => warn!(
+                "Audit entry {} for {} week {} doesn't match current data, skipping",
+                action, student, week
+            ),
+        }
+    }
+
+    table
+        .rows
+        .sort_by(|a, b| a.week.cmp(&b.week).then_with(|| a.name.cmp(&b.name)));
+    Ok(table)
+}
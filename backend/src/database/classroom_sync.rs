@@ -0,0 +1,151 @@
+use crate::database::operations::set_student_field;
+use crate::utils::classroom::Assignment;
+use crate::utils::types::AppError;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// How the last Classroom sync of a cohort went
+#[derive(Debug, Default, Serialize)]
+pub struct SyncStatus {
+    pub last_sync_at: Option<String>,
+    // Last sync that had no errors
+    pub last_success_at: Option<String>,
+    pub last_error: Option<String>,
+    // Fields the last sync changed
+    pub updated_fields: i64,
+}
+
+// A field the sync changed
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncChange {
+    pub name: String,
+    pub week: i32,
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+fn ensure_sync_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS classroom_sync (
+            id              INTEGER PRIMARY KEY CHECK (id = 1),
+            last_sync_at    TEXT NOT NULL,
+            last_success_at TEXT,
+            last_error      TEXT,
+            updated_fields  INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn read_sync_status(db_path: &Path) -> Result<SyncStatus, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_sync_table(&conn)?;
+    let status = conn
+        .query_row(
+            "SELECT last_sync_at, last_success_at, last_error, updated_fields FROM classroom_sync",
+            [],
+            |row| {
+                Ok(SyncStatus {
+                    last_sync_at: row.get(0)?,
+                    last_success_at: row.get(1)?,
+                    last_error: row.get(2)?,
+                    updated_fields: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(status.unwrap_or_default())
+}
+
+// Records a finished sync. `error` is None when every week synced.
+pub fn record_sync(
+    db_path: &Path,
+    synced_at: &str,
+    updated_fields: usize,
+    error: Option<&str>,
+) -> Result<(), AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_sync_table(&conn)?;
+    conn.execute(
+        "INSERT INTO classroom_sync (id, last_sync_at, last_success_at, last_error, updated_fields)
+         VALUES (1, ?1, CASE WHEN ?3 IS NULL THEN ?1 END, ?3, ?2)
+         ON CONFLICT (id) DO UPDATE SET
+             last_sync_at = excluded.last_sync_at,
+             last_success_at = COALESCE(excluded.last_success_at, last_success_at),
+             last_error = excluded.last_error,
+             updated_fields = excluded.updated_fields",
+        params![synced_at, updated_fields as i64, error],
+    )?;
+    Ok(())
+}
+
+// Registered participants' emails by lowercase GitHub handle
+fn emails_by_github(conn: &Connection) -> rusqlite::Result<HashMap<String, String>> {
+    let has_participants: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'participants'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_participants {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT lower(github), email FROM participants WHERE github IS NOT NULL AND github != ''",
+    )?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+// Sets `exercise_submitted` and `exercise_test_passing` of `week` from Classroom grades through
+// the audited write path, in one transaction. Students without a submission are left alone.
+pub fn apply_classroom_grades(
+    db_path: &Path,
+    week: i32,
+    assignments: &[Assignment],
+    actor: &str,
+) -> Result<Vec<SyncChange>, AppError> {
+    let mut conn = Connection::open(db_path)?;
+    let emails = emails_by_github(&conn)?;
+    let tx = conn.transaction()?;
+    let mut changes = Vec::new();
+
+    for assignment in assignments.iter().filter(|a| a.is_submitted()) {
+        let Some(email) = emails.get(&assignment.github_username.to_lowercase()) else {
+            continue;
+        };
+        let Some(name): Option<String> = tx
+            .query_row(
+                "SELECT name FROM students WHERE lower(trim(mail)) = lower(trim(?1)) AND week = ?2",
+                params![email, week],
+                |row| row.get(0),
+            )
+            .optional()?
+        else {
+            continue;
+        };
+        let passing = if assignment.is_passing() { "yes" } else { "no" };
+        for (field, value) in [
+            ("exercise_submitted", "yes"),
+            ("exercise_test_passing", passing),
+        ] {
+            if let Some((old, new)) =
+                set_student_field(&tx, &name, week, field, Some(value), actor)?
+                && old != new
+            {
+                changes.push(SyncChange {
+                    name: name.clone(),
+                    week,
+                    field: field.to_string(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(changes)
+}
@@ -0,0 +1,364 @@
+use crate::database::audit::audit_timestamp;
+use crate::utils::types::AppError;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnrollmentStatus {
+    Applied,
+    Accepted,
+    Enrolled,
+    Dropped,
+    Completed,
+    Deferred,
+    // An application TAs turned down
+    Rejected,
+}
+
+impl EnrollmentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnrollmentStatus::Applied => "applied",
+            EnrollmentStatus::Accepted => "accepted",
+            EnrollmentStatus::Enrolled => "enrolled",
+            EnrollmentStatus::Dropped => "dropped",
+            EnrollmentStatus::Completed => "completed",
+            EnrollmentStatus::Deferred => "deferred",
+            EnrollmentStatus::Rejected => "rejected",
+        }
+    }
+
+    // Whether a student in this status takes part in the weekly sessions
+    pub fn is_active(self) -> bool {
+        !matches!(
+            self,
+            EnrollmentStatus::Dropped | EnrollmentStatus::Deferred | EnrollmentStatus::Rejected
+        )
+    }
+
+    fn can_become(self, next: EnrollmentStatus) -> bool {
+        use EnrollmentStatus::*;
+        match self {
+            Applied => matches!(next, Accepted | Deferred | Dropped | Rejected),
+            Accepted => matches!(next, Enrolled | Deferred | Dropped),
+            Enrolled => matches!(next, Dropped | Completed | Deferred),
+            // A deferred applicant comes back in a later cohort at any earlier stage
+            Deferred => matches!(next, Applied | Accepted | Enrolled | Dropped | Rejected),
+            // Re-admitting someone who dropped out
+            Dropped => matches!(next, Enrolled),
+            Completed | Rejected => false,
+        }
+    }
+}
+
+impl fmt::Display for EnrollmentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EnrollmentStatus {
+    type Err = EnrollmentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_lowercase()))
+            .map_err(|_| EnrollmentError::UnknownStatus(s.to_string()))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EnrollmentError {
+    #[error("Unknown enrollment status {0:?}")]
+    UnknownStatus(String),
+    #[error("Can't go from {0} to {1}")]
+    InvalidTransition(EnrollmentStatus, EnrollmentStatus),
+    #[error("No participant with email {0}")]
+    UnknownParticipant(String),
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+impl From<rusqlite::Error> for EnrollmentError {
+    fn from(e: rusqlite::Error) -> Self {
+        EnrollmentError::App(AppError::Database(e))
+    }
+}
+
+// One status change of a participant
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrollmentChange {
+    pub email: String,
+    pub status: EnrollmentStatus,
+    pub reason: Option<String>,
+    // Latest cohort week when the change was made
+    pub week: i32,
+    pub changed_at: String,
+    pub changed_by: String,
+}
+
+// Where a participant currently stands. `since` is None for statuses that were never set
+// explicitly (roster students count as enrolled, other registrations as applied).
+#[derive(Debug, Clone, Serialize)]
+pub struct Enrollment {
+    pub email: String,
+    pub name: Option<String>,
+    pub status: EnrollmentStatus,
+    pub since: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnrollmentStats {
+    pub by_status: BTreeMap<String, usize>,
+    // Everyone who got onto the weekly sessions: enrolled, completed or dropped
+    pub ever_enrolled: usize,
+    pub retention_rate: Option<f64>,
+    pub dropout_rate: Option<f64>,
+    // Week of the cohort in which students dropped out
+    pub dropouts_by_week: BTreeMap<i32, usize>,
+}
+
+fn ensure_enrollment_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS enrollment_history (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            email      TEXT NOT NULL COLLATE NOCASE,
+            status     TEXT NOT NULL,
+            reason     TEXT,
+            week       INTEGER NOT NULL,
+            changed_at TEXT NOT NULL,
+            changed_by TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_enrollment_history_email ON enrollment_history (email);",
+    )
+}
+
+fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |row| row.get(0),
+    )
+}
+
+fn read_history(conn: &Connection, email: Option<&str>) -> rusqlite::Result<Vec<EnrollmentChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT email, status, reason, week, changed_at, changed_by FROM enrollment_history
+         WHERE ?1 IS NULL OR email = ?1 ORDER BY id",
+    )?;
+    stmt.query_map(params![email], |row| {
+        let status: String = row.get(1)?;
+        Ok(EnrollmentChange {
+            email: row.get(0)?,
+            status: status.parse().unwrap_or(EnrollmentStatus::Applied),
+            reason: row.get(2)?,
+            week: row.get(3)?,
+            changed_at: row.get(4)?,
+            changed_by: row.get(5)?,
+        })
+    })?
+    .collect()
+}
+
+pub fn enrollment_history(
+    db_path: &Path,
+    email: &str,
+) -> Result<Vec<EnrollmentChange>, EnrollmentError> {
+    let conn = Connection::open(db_path)?;
+    ensure_enrollment_table(&conn)?;
+    Ok(read_history(&conn, Some(email.trim()))?)
+}
+
+// Current status of everyone registered for or on the roster of the cohort, keyed by
+// lowercased email
+pub fn current_enrollments(
+    db_path: &Path,
+) -> Result<BTreeMap<String, Enrollment>, EnrollmentError> {
+    read_enrollments(&Connection::open(db_path)?)
+}
+
+fn read_enrollments(conn: &Connection) -> Result<BTreeMap<String, Enrollment>, EnrollmentError> {
+    ensure_enrollment_table(conn)?;
+    let mut enrollments: BTreeMap<String, Enrollment> = BTreeMap::new();
+    let mut add = |email: String, name: Option<String>, status: EnrollmentStatus| {
+        let key = email.trim().to_lowercase();
+        if !key.is_empty() && !enrollments.contains_key(&key) {
+            enrollments.insert(
+                key,
+                Enrollment {
+                    email: email.trim().to_string(),
+                    name,
+                    status,
+                    since: None,
+                    reason: None,
+                },
+            );
+        }
+    };
+
+    if table_exists(conn, "students")? {
+        let mut stmt = conn.prepare("SELECT DISTINCT mail, name FROM students WHERE week = 0")?;
+        for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (email, name): (Option<String>, String) = row?;
+            add(
+                email.unwrap_or_default(),
+                Some(name),
+                EnrollmentStatus::Enrolled,
+            );
+        }
+    }
+    if table_exists(conn, "participants")? {
+        let mut stmt = conn.prepare("SELECT email, name, enrolled FROM participants")?;
+        for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))? {
+            let (email, name, enrolled): (Option<String>, Option<String>, Option<bool>) = row?;
+            let status = if enrolled.unwrap_or(false) {
+                EnrollmentStatus::Enrolled
+            } else {
+                EnrollmentStatus::Applied
+            };
+            add(email.unwrap_or_default(), name, status);
+        }
+    }
+
+    for change in read_history(conn, None)? {
+        let key = change.email.to_lowercase();
+        let enrollment = enrollments.entry(key).or_insert_with(|| Enrollment {
+            email: change.email.clone(),
+            name: None,
+            status: change.status,
+            since: None,
+            reason: None,
+        });
+        enrollment.status = change.status;
+        enrollment.since = Some(change.changed_at);
+        enrollment.reason = change.reason;
+    }
+    Ok(enrollments)
+}
+
+// Lowercased emails of dropped and deferred participants, who are left out of weekly grouping
+pub fn inactive_emails(db_path: &Path) -> Result<HashSet<String>, EnrollmentError> {
+    Ok(current_enrollments(db_path)?
+        .into_iter()
+        .filter(|(_, e)| !e.status.is_active())
+        .map(|(email, _)| email)
+        .collect())
+}
+
+// Moves a participant to `status`, keeping the legacy `participants.enrolled` flag in step
+pub fn set_enrollment_status(
+    db_path: &Path,
+    email: &str,
+    status: EnrollmentStatus,
+    reason: Option<&str>,
+    actor: &str,
+) -> Result<EnrollmentChange, EnrollmentError> {
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    let change = change_enrollment_status(&tx, email, status, reason, actor)?;
+    tx.commit()?;
+    Ok(change)
+}
+
+// `set_enrollment_status` on the caller's connection, so it can be part of a larger transaction
+pub fn change_enrollment_status(
+    conn: &Connection,
+    email: &str,
+    status: EnrollmentStatus,
+    reason: Option<&str>,
+    actor: &str,
+) -> Result<EnrollmentChange, EnrollmentError> {
+    let email = email.trim();
+    // Only participants and roster students have a status to change
+    let current = read_enrollments(conn)?
+        .remove(&email.to_lowercase())
+        .map(|e| e.status)
+        .ok_or_else(|| EnrollmentError::UnknownParticipant(email.to_string()))?;
+    if current != status && !current.can_become(status) {
+        return Err(EnrollmentError::InvalidTransition(current, status));
+    }
+
+    let week: i32 = if table_exists(conn, "students")? {
+        conn.query_row("SELECT COALESCE(MAX(week), 0) FROM students", [], |row| {
+            row.get(0)
+        })?
+    } else {
+        0
+    };
+    let change = EnrollmentChange {
+        email: email.to_string(),
+        status,
+        reason: reason.map(str::to_string).filter(|r| !r.trim().is_empty()),
+        week,
+        changed_at: audit_timestamp(),
+        changed_by: actor.to_string(),
+    };
+    conn.execute(
+        "INSERT INTO enrollment_history (email, status, reason, week, changed_at, changed_by)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            change.email,
+            status.as_str(),
+            change.reason,
+            change.week,
+            change.changed_at,
+            change.changed_by
+        ],
+    )?;
+    if table_exists(conn, "participants")? {
+        let enrolled = matches!(
+            status,
+            EnrollmentStatus::Enrolled | EnrollmentStatus::Completed
+        );
+        conn.execute(
+            "UPDATE participants SET enrolled = ?1 WHERE lower(email) = lower(?2)",
+            params![enrolled, email],
+        )?;
+    }
+    Ok(change)
+}
+
+pub fn enrollment_stats(db_path: &Path) -> Result<EnrollmentStats, EnrollmentError> {
+    let enrollments = current_enrollments(db_path)?;
+    let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+    for enrollment in enrollments.values() {
+        *by_status.entry(enrollment.status.to_string()).or_default() += 1;
+    }
+    let count = |status: EnrollmentStatus| by_status.get(status.as_str()).copied().unwrap_or(0);
+    let retained = count(EnrollmentStatus::Enrolled) + count(EnrollmentStatus::Completed);
+    let dropped = count(EnrollmentStatus::Dropped);
+    let ever_enrolled = retained + dropped;
+    let rate = |n: usize| (ever_enrolled > 0).then(|| n as f64 / ever_enrolled as f64);
+
+    // The latest drop of everyone who is still out
+    let conn = Connection::open(db_path)?;
+    let mut last_drop: BTreeMap<String, i32> = BTreeMap::new();
+    for change in read_history(&conn, None)? {
+        if change.status == EnrollmentStatus::Dropped {
+            last_drop.insert(change.email.to_lowercase(), change.week);
+        }
+    }
+    let mut dropouts_by_week: BTreeMap<i32, usize> = BTreeMap::new();
+    for (email, week) in last_drop {
+        if enrollments
+            .get(&email)
+            .is_some_and(|e| e.status == EnrollmentStatus::Dropped)
+        {
+            *dropouts_by_week.entry(week).or_default() += 1;
+        }
+    }
+
+    Ok(EnrollmentStats {
+        retention_rate: rate(retained),
+        dropout_rate: rate(dropped),
+        by_status,
+        ever_enrolled,
+        dropouts_by_week,
+    })
+}
@@ -17,6 +17,20 @@
 //! cargo run --bin migrate PB
 //! cargo run --bin migrate LBTCL
 //! cargo run --bin migrate MB
+//!
+//! # Backup management for a registered cohort (see `cohorts.json`):
+//! cargo run --bin migrate backup list PB
+//! cargo run --bin migrate backup create PB
+//! cargo run --bin migrate backup verify PB pb_cohort_2025-06-02_030000000.db
+//! cargo run --bin migrate backup restore PB pb_cohort_2025-06-02_030000000.db
+//!
+//! # Turn a shipped archive (.gz/.zst, optionally .enc) back into a database file.
+//! # Encrypted archives need BACKUP_PASSPHRASE.
+//! cargo run --bin migrate backup unpack pb_cohort_2025-06-02_030000000.db.gz.enc restored.db
+//!
+//! # Import feedback form responses from the cohort's configured source, or from a CSV file
+//! cargo run --bin migrate feedback import LBTCL
+//! cargo run --bin migrate feedback import LBTCL responses.csv
 //! ```
 //!
 //! This binary will:
@@ -27,6 +41,15 @@
 //! - Populate the `students` table using data from `participants` and `ta` tables,
 //!   assigning random groups and TAs, and setting default scores and statuses.
 
+use backend::utils::archive::{Compression, unpack_archive};
+use backend::utils::backup::{
+    BackupConfig, backup_database, find_backup, list_backups, restore_backup, table_row_counts,
+    verify_backup,
+};
+use backend::utils::cohort::find_cohort;
+use backend::utils::feedback_import::{
+    FeedbackMapping, fetch_feedback_csv, import_feedback, import_feedback_csv,
+};
 use csv::Reader;
 use rusqlite::{Connection, params};
 use std::env;
@@ -41,7 +64,7 @@ struct ParticipantInfo {
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 enum Cohort {
     BPD,
     PB,
@@ -91,12 +114,144 @@ impl Cohort {
     }
 }
 
-#[allow(dead_code)]
+fn backup_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if let (Some("unpack"), Some(archive), Some(output)) =
+        (args.first().map(String::as_str), args.get(1), args.get(2))
+    {
+        let passphrase = env::var("BACKUP_PASSPHRASE").ok();
+        let data = unpack_archive(
+            &std::fs::read(archive)?,
+            Compression::from_file_name(archive),
+            passphrase.as_deref(),
+        )?;
+        std::fs::write(output, data)?;
+        verify_backup(std::path::Path::new(output))?;
+        println!("Unpacked {} to {}", archive, output);
+        return Ok(());
+    }
+
+    let (Some(action), Some(cohort_name)) = (args.first(), args.get(1)) else {
+        eprintln!("Usage: migrate backup <list|create|verify|restore> <COHORT_NAME> [BACKUP_FILE]");
+        eprintln!("       migrate backup unpack <ARCHIVE> <OUTPUT_DB>");
+        std::process::exit(1);
+    };
+    let Some(cohort) = find_cohort(cohort_name) else {
+        eprintln!("Error: cohort {} is not in the registry", cohort_name);
+        std::process::exit(1);
+    };
+    let config = BackupConfig::from_env();
+    let db_path = cohort.db_path();
+
+    let backup_file = |args: &[String]| {
+        let Some(file_name) = args.get(2) else {
+            eprintln!(
+                "Usage: migrate backup {} <COHORT_NAME> <BACKUP_FILE>",
+                action
+            );
+            std::process::exit(1);
+        };
+        match find_backup(&db_path, file_name, &config) {
+            Some(backup) => backup,
+            None => {
+                eprintln!("Error: no backup named {} for {}", file_name, cohort.name);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    match action.as_str() {
+        "list" => {
+            for backup in list_backups(&db_path, &config) {
+                let size = std::fs::metadata(&backup.path)?.len();
+                let rows = table_row_counts(&backup.path)?;
+                println!(
+                    "{}\t{}\t{} bytes\t{:?}",
+                    backup
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                    backup.created,
+                    size,
+                    rows
+                );
+            }
+        }
+        "create" => {
+            let path = backup_database(&db_path, &config)?;
+            println!("Created backup {}", path.display());
+        }
+        "verify" => {
+            let backup = backup_file(args);
+            verify_backup(&backup.path)?;
+            println!("{} passed the integrity check", backup.path.display());
+        }
+        "restore" => {
+            let backup = backup_file(args);
+            let safety = restore_backup(&db_path, &backup.path, &config)?;
+            println!("Restored {} from {}", cohort.db, backup.path.display());
+            if let Some(safety) = safety {
+                println!("Previous database saved as {}", safety.display());
+            }
+        }
+        _ => {
+            eprintln!("Unknown backup action: {}", action);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn feedback_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (Some("import"), Some(cohort_name)) = (args.first().map(String::as_str), args.get(1))
+    else {
+        eprintln!("Usage: migrate feedback import <COHORT_NAME> [CSV_FILE]");
+        std::process::exit(1);
+    };
+    let Some(cohort) = find_cohort(cohort_name) else {
+        eprintln!("Error: unknown cohort {}", cohort_name);
+        std::process::exit(1);
+    };
+
+    let report = match args.get(2) {
+        Some(file) => import_feedback_csv(
+            &cohort.db_path(),
+            &fetch_feedback_csv(file)?,
+            &FeedbackMapping::for_cohort(&cohort)?,
+        )?,
+        None => import_feedback(&cohort)?,
+    };
+    println!(
+        "Imported {} feedback responses into {}",
+        report.imported, cohort.db
+    );
+    for column in &report.unmapped_columns {
+        println!("  unmapped column (kept as extra answer): {}", column);
+    }
+    for field in &report.missing_fields {
+        println!("  no column for field: {}", field);
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("backup") => return backup_command(&args[2..]),
+        Some("feedback") => return feedback_command(&args[2..]),
+        _ => {}
+    }
     if args.len() != 2 {
         eprintln!("Usage: {} <COHORT_NAME>", args[0]);
+        eprintln!(
+            "       {} backup <list|create|verify|restore> <COHORT_NAME> [BACKUP_FILE]",
+            args[0]
+        );
+        eprintln!(
+            "       {} feedback import <COHORT_NAME> [CSV_FILE]",
+            args[0]
+        );
         eprintln!("Valid cohorts: BPD, PB, LBTCL, MB");
         eprintln!("Example: cargo run --bin migrate BPD");
         std::process::exit(1);
@@ -115,7 +270,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("CSV file: {}", cohort.csv_file());
 
     // Open or create the SQLite database with cohort-specific name
-    let conn = Connection::open(&cohort.db_name())?;
+    let conn = Connection::open(cohort.db_name())?;
 
     // Drop existing tables to ensure a fresh load
     conn.execute_batch(
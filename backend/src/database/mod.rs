@@ -1,2 +1,11 @@
-pub mod migrate;
+pub mod appeals;
+pub mod audit;
+pub mod classroom_sync;
+pub mod enrollment;
+pub mod notes;
+pub mod notifications;
 pub mod operations;
+pub mod outbox;
+pub mod reviews;
+pub mod verification;
+pub mod waitlist;
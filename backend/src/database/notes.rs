@@ -0,0 +1,186 @@
+use crate::database::audit::audit_timestamp;
+use crate::utils::types::AppError;
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+// A TA's note about a student. Notes are only ever shown to TAs.
+#[derive(Debug, Clone, Serialize)]
+pub struct StudentNote {
+    pub id: i64,
+    pub student: String,
+    pub author: String,
+    pub created_at: String,
+    pub text: String,
+}
+
+pub fn ensure_notes_tables(conn: &Connection) -> Result<(), AppError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS student_notes (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            student    TEXT NOT NULL,
+            author     TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            text       TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_student_notes_student ON student_notes (student);
+        CREATE TABLE IF NOT EXISTS student_tags (
+            student  TEXT NOT NULL,
+            tag      TEXT NOT NULL COLLATE NOCASE,
+            added_by TEXT NOT NULL,
+            added_at TEXT NOT NULL,
+            PRIMARY KEY (student, tag)
+        );",
+    )?;
+    Ok(())
+}
+
+pub fn add_note(
+    db_path: &Path,
+    student: &str,
+    author: &str,
+    text: &str,
+) -> Result<StudentNote, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_notes_tables(&conn)?;
+    let created_at = audit_timestamp();
+    conn.execute(
+        "INSERT INTO student_notes (student, author, created_at, text) VALUES (?1, ?2, ?3, ?4)",
+        params![student, author, created_at, text],
+    )?;
+    Ok(StudentNote {
+        id: conn.last_insert_rowid(),
+        student: student.to_string(),
+        author: author.to_string(),
+        created_at,
+        text: text.to_string(),
+    })
+}
+
+// Notes about `student`, newest first
+pub fn read_notes(db_path: &Path, student: &str) -> Result<Vec<StudentNote>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_notes_tables(&conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, student, author, created_at, text FROM student_notes
+         WHERE student = ?1 ORDER BY id DESC",
+    )?;
+    let notes = stmt
+        .query_map(params![student], |row| {
+            Ok(StudentNote {
+                id: row.get(0)?,
+                student: row.get(1)?,
+                author: row.get(2)?,
+                created_at: row.get(3)?,
+                text: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(notes)
+}
+
+// Replaces the tags of `student`. Blank tags are dropped; tags compare case-insensitively.
+pub fn set_tags(
+    db_path: &Path,
+    student: &str,
+    tags: &[String],
+    actor: &str,
+) -> Result<Vec<String>, AppError> {
+    let mut conn = Connection::open(db_path)?;
+    ensure_notes_tables(&conn)?;
+    let tx = conn.transaction()?;
+    let tags: Vec<&str> = tags
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let keep = tags.iter().map(|t| t.to_lowercase()).collect::<Vec<_>>();
+    let existing: Vec<String> = tx
+        .prepare("SELECT tag FROM student_tags WHERE student = ?1")?
+        .query_map(params![student], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for tag in existing {
+        if !keep.contains(&tag.to_lowercase()) {
+            tx.execute(
+                "DELETE FROM student_tags WHERE student = ?1 AND tag = ?2",
+                params![student, tag],
+            )?;
+        }
+    }
+    let now = audit_timestamp();
+    for tag in tags {
+        tx.execute(
+            "INSERT OR IGNORE INTO student_tags (student, tag, added_by, added_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![student, tag, actor, now],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(read_tags(db_path)?.remove(student).unwrap_or_default())
+}
+
+// Tags of every student that has any, alphabetically
+pub fn read_tags(db_path: &Path) -> Result<BTreeMap<String, Vec<String>>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_notes_tables(&conn)?;
+    let mut stmt = conn.prepare("SELECT student, tag FROM student_tags ORDER BY student, tag")?;
+    let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (student, tag): (String, String) = row?;
+        tags.entry(student).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+// Students matching every tag in `tags` and, if given, whose name, tags or notes contain `query`
+// (case-insensitive)
+pub fn search_students(
+    db_path: &Path,
+    tags: &[String],
+    query: Option<&str>,
+) -> Result<BTreeSet<String>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_notes_tables(&conn)?;
+    let all_tags = read_tags(db_path)?;
+
+    let mut students: BTreeSet<String> = conn
+        .prepare("SELECT DISTINCT name FROM students")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let wanted: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if !wanted.is_empty() {
+        students.retain(|student| {
+            let has: Vec<String> = all_tags
+                .get(student)
+                .map(|tags| tags.iter().map(|t| t.to_lowercase()).collect())
+                .unwrap_or_default();
+            wanted.iter().all(|t| has.contains(t))
+        });
+    }
+
+    if let Some(query) = query.map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", query);
+        let in_notes: BTreeSet<String> = conn
+            .prepare("SELECT DISTINCT student FROM student_notes WHERE text LIKE ?1")?
+            .query_map(params![pattern], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let query = query.to_lowercase();
+        students.retain(|student| {
+            student.to_lowercase().contains(&query)
+                || in_notes.contains(student)
+                || all_tags
+                    .get(student)
+                    .is_some_and(|tags| tags.iter().any(|t| t.to_lowercase().contains(&query)))
+        });
+    }
+
+    Ok(students)
+}
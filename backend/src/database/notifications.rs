@@ -0,0 +1,221 @@
+use crate::database::audit::audit_timestamp;
+use crate::database::enrollment::{EnrollmentError, inactive_emails};
+use crate::database::operations::read_from_db;
+use crate::utils::notifications::{
+    Channel, GROUP_ASSIGNMENT, NotificationConfig, REMINDER_ANNOUNCEMENT, WEEK_PUBLISHED,
+    WEEKLY_REMINDER, queue_notification,
+};
+use crate::utils::types::{AppError, RowData};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Default, Serialize)]
+pub struct NotificationReport {
+    pub week: i32,
+    // Students who were sent a notification
+    pub notified: Vec<String>,
+    // Students skipped because they already got this notification
+    pub unchanged: usize,
+}
+
+// What each student was told, so publishing a week again only notifies students whose group
+// or TA changed and reminders go out once per week
+fn ensure_notification_log(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notification_log (
+            template TEXT NOT NULL,
+            week     INTEGER NOT NULL,
+            email    TEXT NOT NULL COLLATE NOCASE,
+            detail   TEXT NOT NULL,
+            sent_at  TEXT NOT NULL,
+            PRIMARY KEY (template, week, email)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn logged_detail(
+    conn: &Connection,
+    template: &str,
+    week: i32,
+    email: &str,
+) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT detail FROM notification_log WHERE template = ?1 AND week = ?2 AND email = ?3",
+        params![template, week, email],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn log_notification(
+    conn: &Connection,
+    template: &str,
+    week: i32,
+    email: &str,
+    detail: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO notification_log (template, week, email, detail, sent_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![template, week, email, detail, audit_timestamp()],
+    )?;
+    Ok(())
+}
+
+// Grouped rows of `week` for students who are still active and have an email
+fn week_rows(db_path: &Path, week: i32) -> Result<Vec<RowData>, AppError> {
+    let inactive = inactive_emails(db_path).map_err(|e| match e {
+        EnrollmentError::App(e) => e,
+        e => AppError::Io(std::io::Error::other(e)),
+    })?;
+    Ok(read_from_db(&db_path.to_path_buf())?
+        .rows
+        .into_iter()
+        .filter(|row| row.week == week && !row.group_id.is_empty())
+        .filter(|row| !row.mail.trim().is_empty())
+        .filter(|row| !inactive.contains(&row.mail.trim().to_lowercase()))
+        .collect())
+}
+
+fn student_vars(cohort: &str, row: &RowData) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("name", row.name.clone()),
+        ("cohort", cohort.to_string()),
+        ("week", row.week.to_string()),
+        ("group", row.group_id.clone()),
+        (
+            "ta",
+            row.ta.clone().unwrap_or_else(|| "your TA".to_string()),
+        ),
+    ])
+}
+
+// Tells every student of `week` their group and TA, and posts the group list to the cohort
+// channel. Students who were already told about their current group are skipped.
+pub fn publish_week(
+    db_path: &Path,
+    cohort: &str,
+    week: i32,
+    config: &NotificationConfig,
+) -> Result<NotificationReport, AppError> {
+    let rows = week_rows(db_path, week)?;
+    let mut conn = Connection::open(db_path)?;
+    ensure_notification_log(&conn)?;
+    let tx = conn.transaction()?;
+    let mut report = NotificationReport {
+        week,
+        ..Default::default()
+    };
+
+    for row in &rows {
+        let email = row.mail.trim();
+        let detail = format!("{}|{}", row.group_id, row.ta.as_deref().unwrap_or(""));
+        if logged_detail(&tx, GROUP_ASSIGNMENT, week, email)?.as_deref() == Some(detail.as_str()) {
+            report.unchanged += 1;
+            continue;
+        }
+        if config.uses(Channel::Email) {
+            let vars = student_vars(cohort, row);
+            queue_notification(&tx, config, Channel::Email, GROUP_ASSIGNMENT, email, &vars)?;
+        }
+        log_notification(&tx, GROUP_ASSIGNMENT, week, email, &detail)?;
+        report.notified.push(email.to_string());
+    }
+
+    if !report.notified.is_empty() && config.uses(Channel::Discord) {
+        let mut groups: BTreeMap<&str, (String, Vec<&str>)> = BTreeMap::new();
+        for row in &rows {
+            groups
+                .entry(row.group_id.as_str())
+                .or_insert_with(|| (row.ta.clone().unwrap_or_default(), Vec::new()))
+                .1
+                .push(row.name.as_str());
+        }
+        let groups = groups
+            .iter()
+            .map(|(group, (ta, names))| format!("{} ({}): {}", group, ta, names.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let vars = BTreeMap::from([
+            ("cohort", cohort.to_string()),
+            ("week", week.to_string()),
+            ("groups", groups),
+        ]);
+        queue_notification(&tx, config, Channel::Discord, WEEK_PUBLISHED, "", &vars)?;
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+// Reminds the students of the latest published week of their group, TA and whether their
+// exercise is in. Each student is reminded once per week.
+pub fn queue_weekly_reminders(
+    db_path: &Path,
+    cohort: &str,
+    config: &NotificationConfig,
+) -> Result<NotificationReport, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_notification_log(&conn)?;
+    let latest: Option<i32> = conn.query_row(
+        "SELECT MAX(week) FROM notification_log WHERE template = ?1",
+        params![GROUP_ASSIGNMENT],
+        |row| row.get(0),
+    )?;
+    drop(conn);
+    let Some(week) = latest else {
+        return Ok(NotificationReport::default());
+    };
+
+    let rows = week_rows(db_path, week)?;
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    let mut report = NotificationReport {
+        week,
+        ..Default::default()
+    };
+    for row in &rows {
+        let email = row.mail.trim();
+        if logged_detail(&tx, WEEKLY_REMINDER, week, email)?.is_some() {
+            report.unchanged += 1;
+            continue;
+        }
+        if config.uses(Channel::Email) {
+            let mut vars = student_vars(cohort, row);
+            let exercise = if row.exercise_submitted.as_deref() == Some("yes") {
+                "Your exercise is in, thanks!"
+            } else {
+                "You haven't submitted this week's exercise yet."
+            };
+            vars.insert("exercise", exercise.to_string());
+            queue_notification(&tx, config, Channel::Email, WEEKLY_REMINDER, email, &vars)?;
+        }
+        log_notification(&tx, WEEKLY_REMINDER, week, email, "")?;
+        report.notified.push(email.to_string());
+    }
+
+    if !report.notified.is_empty() && config.uses(Channel::Discord) {
+        let missing = rows
+            .iter()
+            .filter(|row| row.exercise_submitted.as_deref() != Some("yes"))
+            .count();
+        let vars = BTreeMap::from([
+            ("cohort", cohort.to_string()),
+            ("week", week.to_string()),
+            ("missing", missing.to_string()),
+        ]);
+        queue_notification(
+            &tx,
+            config,
+            Channel::Discord,
+            REMINDER_ANNOUNCEMENT,
+            "",
+            &vars,
+        )?;
+    }
+    tx.commit()?;
+    Ok(report)
+}
@@ -1,81 +1,240 @@
+use crate::database::audit::{
+    cohort_label, ensure_audit_table, record_row_change, record_row_delete,
+};
+use crate::database::verification::{stage_registration_update, start_verification};
+use crate::database::waitlist::{ensure_waitlist_column, next_waitlist_position};
+use crate::utils::email_verification::VerificationConfig;
 use crate::utils::types::{AppError, CohortParticipant, FeedbackResponse, RowData, Table};
 use chrono::Utc;
 use log::info;
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, OptionalExtension, Result, Row, params};
+use serde::Serialize;
 use serde_json;
 use std::path::PathBuf;
 
+const STUDENT_COLUMNS: &str = "name, group_id, ta, attendance, CAST(fa as INTEGER) as fa, CAST(fb as INTEGER) as fb, CAST(fc as INTEGER) as fc, CAST(fd as INTEGER) as fd, CAST(bonus_attempt as INTEGER) as bonus_attempt, CAST(bonus_answer_quality as INTEGER) as bonus_answer_quality, CAST(bonus_follow_up as INTEGER) as bonus_follow_up, exercise_submitted, exercise_test_passing, exercise_good_documentation, exercise_good_structure, CAST(total as INTEGER) as total, mail, week";
+
+fn row_from_sql(row: &Row) -> Result<RowData> {
+    Ok(RowData {
+        name: row.get(0)?,
+        group_id: row.get(1)?,
+        ta: row.get(2)?,
+        attendance: row.get(3)?,
+        fa: row.get(4)?,
+        fb: row.get(5)?,
+        fc: row.get(6)?,
+        fd: row.get(7)?,
+        bonus_attempt: row.get(8)?,
+        bonus_answer_quality: row.get(9)?,
+        bonus_follow_up: row.get(10)?,
+        exercise_submitted: row.get(11)?,
+        exercise_test_passing: row.get(12)?,
+        exercise_good_documentation: row.get(13)?,
+        exercise_good_structure: row.get(14)?,
+        total: row.get(15)?,
+        mail: row.get(16)?,
+        week: row.get(17)?,
+    })
+}
+
 pub fn read_from_db(path: &PathBuf) -> Result<Table, AppError> {
     info!("Reading from DB at path: {:?}", path);
     let conn = Connection::open(path)?;
 
-    let mut stmt = conn.prepare("SELECT name, group_id, ta, attendance, CAST(fa as INTEGER) as fa, CAST(fb as INTEGER) as fb, CAST(fc as INTEGER) as fc, CAST(fd as INTEGER) as fd, CAST(bonus_attempt as INTEGER) as bonus_attempt, CAST(bonus_answer_quality as INTEGER) as bonus_answer_quality, CAST(bonus_follow_up as INTEGER) as bonus_follow_up, exercise_submitted, exercise_test_passing, exercise_good_documentation, exercise_good_structure, CAST(total as INTEGER) as total, mail, week FROM students")?;
-
-    let rows = stmt.query_map([], |row| {
-        Ok(RowData {
-            name: row.get(0)?,
-            group_id: row.get(1)?,
-            ta: row.get(2)?,
-            attendance: row.get(3)?,
-            fa: row.get(4)?,
-            fb: row.get(5)?,
-            fc: row.get(6)?,
-            fd: row.get(7)?,
-            bonus_attempt: row.get(8)?,
-            bonus_answer_quality: row.get(9)?,
-            bonus_follow_up: row.get(10)?,
-            exercise_submitted: row.get(11)?,
-            exercise_test_passing: row.get(12)?,
-            exercise_good_documentation: row.get(13)?,
-            exercise_good_structure: row.get(14)?,
-            total: row.get(15)?,
-            mail: row.get(16)?,
-            week: row.get(17)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM students", STUDENT_COLUMNS))?;
+
+    let rows = stmt.query_map([], row_from_sql)?;
 
     let rows_vec: Vec<RowData> = rows.collect::<Result<Vec<_>, _>>()?;
     info!(
         "Successfully read {} rows from the database.",
         rows_vec.len()
     );
-    Ok(Table { rows: rows_vec })
+    Ok(Table {
+        rows: rows_vec,
+        db_path: Some(path.clone()),
+    })
 }
 
-pub fn write_to_db(path: &PathBuf, table: &Table) -> Result<(), AppError> {
+// Upserts every row of `table` and records each changed field in `audit_log` under `actor`.
+// Rows that are identical to what's stored are left untouched.
+pub fn write_to_db(path: &PathBuf, table: &Table, actor: &str) -> Result<(), AppError> {
+    write_rows(path, &table.rows, actor)
+}
+
+// Like `write_to_db`, for just `rows`; the rest of the cohort is left alone
+pub fn write_rows(path: &PathBuf, rows: &[RowData], actor: &str) -> Result<(), AppError> {
     info!("Writing to DB at path: {:?}", path);
     let mut conn = Connection::open(path)?;
+    ensure_audit_table(&conn)?;
+    let cohort = cohort_label(path);
     let tx = conn.transaction()?;
 
-    // tx.execute("DELETE FROM students", [])?;
+    let changes = upsert_rows(&tx, &cohort, rows, actor)?;
 
-    for row in &table.rows {
-        // First, try to update existing record
-        let updated_rows = tx.execute(
-            "UPDATE students SET group_id = ?2, ta = ?3, attendance = ?4, fa = ?5, fb = ?6, fc = ?7, fd = ?8, bonus_attempt = ?9, bonus_answer_quality = ?10, bonus_follow_up = ?11, exercise_submitted = ?12, exercise_test_passing = ?13, exercise_good_documentation = ?14, exercise_good_structure = ?15, total = ?16, mail = ?17 WHERE name = ?1 AND week = ?18",
-            params![
-                row.name,
-                row.group_id,
-                row.ta,
-                row.attendance,
-                row.fa,
-                row.fb,
-                row.fc,
-                row.fd,
-                row.bonus_attempt,
-                row.bonus_answer_quality,
-                row.bonus_follow_up,
-                row.exercise_submitted,
-                row.exercise_test_passing,
-                row.exercise_good_documentation,
-                row.exercise_good_structure,
-                row.total,
-                row.mail,
-                row.week
-            ],
-        )?;
+    info!(
+        "Successfully wrote {} rows to the database ({} audited changes by {}).",
+        rows.len(),
+        changes,
+        actor
+    );
+    tx.commit()?;
+    Ok(())
+}
 
-        if updated_rows == 0 {
+// Old and new value of a field, as rendered in the audit log
+pub type FieldChange = (Option<String>, Option<String>);
+
+// Sets one scored field of a student's week and recomputes the total, recording the change
+// under `actor`. Runs on the caller's connection so it can be part of a larger transaction.
+// Returns the old and new value, or None if the student has no row for that week.
+pub fn set_student_field(
+    conn: &Connection,
+    name: &str,
+    week: i32,
+    field: &str,
+    value: Option<&str>,
+    actor: &str,
+) -> Result<Option<FieldChange>, AppError> {
+    ensure_audit_table(conn)?;
+    let Some(mut row) = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM students WHERE name = ?1 AND week = ?2",
+                STUDENT_COLUMNS
+            ),
+            params![name, week],
+            row_from_sql,
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let value_of = |row: &RowData| {
+        row.field_values()
+            .into_iter()
+            .find(|(f, _)| *f == field)
+            .and_then(|(_, v)| v)
+    };
+    let old = value_of(&row);
+    row.set_field(field, value);
+    row.total = Some(row.compute_total());
+    let new = value_of(&row);
+
+    let cohort = conn
+        .path()
+        .map(|p| cohort_label(std::path::Path::new(p)))
+        .unwrap_or_default();
+    upsert_rows(conn, &cohort, &[row], actor)?;
+    Ok(Some((old, new)))
+}
+
+// Puts a student on the cohort roster by giving them an empty week 0 row, recorded under `actor`.
+// Runs on the caller's connection. Returns false if they already have one.
+pub fn add_roster_row(
+    conn: &Connection,
+    name: &str,
+    mail: &str,
+    actor: &str,
+) -> Result<bool, AppError> {
+    ensure_audit_table(conn)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS students (
+            name TEXT NOT NULL, group_id TEXT, ta TEXT, attendance TEXT,
+            fa REAL, fb REAL, fc REAL, fd REAL,
+            bonus_attempt REAL, bonus_answer_quality REAL, bonus_follow_up REAL,
+            exercise_submitted TEXT, exercise_test_passing TEXT,
+            exercise_good_documentation TEXT, exercise_good_structure TEXT,
+            total REAL, mail TEXT, GitHub TEXT, week INTEGER
+        )",
+        [],
+    )?;
+    let on_roster: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM students
+         WHERE week = 0 AND (name = ?1 OR lower(trim(mail)) = lower(trim(?2)))",
+        params![name, mail],
+        |row| row.get(0),
+    )?;
+    if on_roster {
+        return Ok(false);
+    }
+
+    let no = || Some("no".to_string());
+    let row = RowData {
+        name: name.to_string(),
+        group_id: String::new(),
+        ta: None,
+        attendance: no(),
+        fa: Some(0),
+        fb: Some(0),
+        fc: Some(0),
+        fd: Some(0),
+        bonus_attempt: Some(0),
+        bonus_answer_quality: Some(0),
+        bonus_follow_up: Some(0),
+        exercise_submitted: no(),
+        exercise_test_passing: no(),
+        exercise_good_documentation: no(),
+        exercise_good_structure: no(),
+        total: Some(0),
+        mail: mail.to_string(),
+        week: 0,
+    };
+    let cohort = conn
+        .path()
+        .map(|p| cohort_label(std::path::Path::new(p)))
+        .unwrap_or_default();
+    upsert_rows(conn, &cohort, &[row], actor)?;
+    Ok(true)
+}
+
+// Upsert loop shared by `write_to_db` and `restore_students`. Returns the number of audit entries.
+fn upsert_rows(
+    tx: &Connection,
+    cohort: &str,
+    rows: &[RowData],
+    actor: &str,
+) -> Result<usize, AppError> {
+    let mut changes = 0;
+    for row in rows {
+        let existing = tx
+            .prepare_cached(&format!(
+                "SELECT {} FROM students WHERE name = ?1 AND week = ?2",
+                STUDENT_COLUMNS
+            ))?
+            .query_row(params![row.name, row.week], row_from_sql)
+            .optional()?;
+
+        if existing.as_ref() == Some(row) {
+            continue;
+        }
+
+        if existing.is_some() {
+            tx.execute(
+                "UPDATE students SET group_id = ?2, ta = ?3, attendance = ?4, fa = ?5, fb = ?6, fc = ?7, fd = ?8, bonus_attempt = ?9, bonus_answer_quality = ?10, bonus_follow_up = ?11, exercise_submitted = ?12, exercise_test_passing = ?13, exercise_good_documentation = ?14, exercise_good_structure = ?15, total = ?16, mail = ?17 WHERE name = ?1 AND week = ?18",
+                params![
+                    row.name,
+                    row.group_id,
+                    row.ta,
+                    row.attendance,
+                    row.fa,
+                    row.fb,
+                    row.fc,
+                    row.fd,
+                    row.bonus_attempt,
+                    row.bonus_answer_quality,
+                    row.bonus_follow_up,
+                    row.exercise_submitted,
+                    row.exercise_test_passing,
+                    row.exercise_good_documentation,
+                    row.exercise_good_structure,
+                    row.total,
+                    row.mail,
+                    row.week
+                ],
+            )?;
+        } else {
             tx.execute(
                 "INSERT INTO students (name, group_id, ta, attendance, fa, fb, fc, fd, bonus_attempt, bonus_answer_quality, bonus_follow_up, exercise_submitted, exercise_test_passing, exercise_good_documentation, exercise_good_structure, total, mail, week) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
                 params![
@@ -100,20 +259,119 @@ pub fn write_to_db(path: &PathBuf, table: &Table) -> Result<(), AppError> {
                 ],
             )?;
         }
+
+        changes += record_row_change(tx, cohort, actor, existing.as_ref(), row)?;
     }
+    Ok(changes)
+}
 
+// Deletes a student's rows (all weeks, or just `week`) and records each removed row in `audit_log`.
+// Returns how many rows were deleted.
+pub fn delete_from_db(
+    path: &PathBuf,
+    name: &str,
+    week: Option<i32>,
+    actor: &str,
+) -> Result<usize, AppError> {
     info!(
-        "Successfully wrote {} rows to the database.",
-        table.rows.len()
+        "Deleting rows for {} (week {:?}) at path: {:?}",
+        name, week, path
     );
+    let mut conn = Connection::open(path)?;
+    ensure_audit_table(&conn)?;
+    let cohort = cohort_label(path);
+    let tx = conn.transaction()?;
+
+    let deleted = delete_rows(&tx, &cohort, name, week, actor)?;
+
     tx.commit()?;
-    Ok(())
+    Ok(deleted)
+}
+
+fn delete_rows(
+    tx: &Connection,
+    cohort: &str,
+    name: &str,
+    week: Option<i32>,
+    actor: &str,
+) -> Result<usize, AppError> {
+    let existing: Vec<RowData> = tx
+        .prepare(&format!(
+            "SELECT {} FROM students WHERE name = ?1 AND (?2 IS NULL OR week = ?2)",
+            STUDENT_COLUMNS
+        ))?
+        .query_map(params![name, week], row_from_sql)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for row in &existing {
+        tx.execute(
+            "DELETE FROM students WHERE name = ?1 AND week = ?2",
+            params![row.name, row.week],
+        )?;
+        record_row_delete(tx, cohort, actor, row)?;
+    }
+    Ok(existing.len())
+}
+
+// Makes the `students` table match `rows` exactly in one transaction: rows missing from `rows`
+// are deleted and the rest upserted, all audited under `actor` so a restore can itself be undone.
+// Returns (rows deleted, fields/rows changed).
+pub fn restore_students(
+    path: &PathBuf,
+    rows: &[RowData],
+    actor: &str,
+) -> Result<(usize, usize), AppError> {
+    info!("Restoring students table at path: {:?}", path);
+    let mut conn = Connection::open(path)?;
+    ensure_audit_table(&conn)?;
+    let cohort = cohort_label(path);
+    let tx = conn.transaction()?;
+
+    let current: Vec<(String, i32)> = tx
+        .prepare("SELECT name, week FROM students")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut deleted = 0;
+    for (name, week) in current {
+        if !rows.iter().any(|r| r.name == name && r.week == week) {
+            deleted += delete_rows(&tx, &cohort, &name, Some(week), actor)?;
+        }
+    }
+    let changed = upsert_rows(&tx, &cohort, rows, actor)?;
+
+    tx.commit()?;
+    info!(
+        "Restore complete: {} rows deleted, {} changes applied.",
+        deleted, changed
+    );
+    Ok((deleted, changed))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationOutcome {
+    Created,
+    // The new answers are applied once the code sent to the registered email is entered
+    UpdatePending,
+    Waitlisted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Registration {
+    pub outcome: RegistrationOutcome,
+    pub waitlist_position: Option<i64>,
+}
+
+// Registers a participant. New registrations beyond `seats` go on the waitlist and every new
+// registration gets a verification email; the bot invite is queued once the email is verified.
+// Registering an email again stages the new answers and sends a code to that email; nothing
+// changes until it is entered. `enrolled`, `created_at` and the waitlist position are kept.
 pub fn register_cohort_participant(
     path: &PathBuf,
     participant: CohortParticipant,
-) -> Result<(), AppError> {
+    seats: Option<usize>,
+) -> Result<Registration, AppError> {
     info!("Writing to DB at path: {:?}", path);
     let mut conn = Connection::open(path)?;
     let tx = conn.transaction()?;
@@ -144,34 +402,111 @@ pub fn register_cohort_participant(
     let now = Utc::now().naive_utc();
     let now_str = now.to_string();
 
-    tx.execute(
-        "INSERT INTO participants (
-            name, enrolled, role, email, describe_yourself, background, 
-            github, skills, year, books, why, time, location, cohort_name, 
-            created_at, updated_at
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+    ensure_waitlist_column(&tx)?;
+
+    let existing: Option<(String, Option<i64>)> = tx
+        .query_row(
+            "SELECT email, waitlist_position FROM participants WHERE lower(email) = lower(?1)",
+            params![participant.email],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let skills = serde_json::to_string(&participant.skills).unwrap_or_default();
+    let books = serde_json::to_string(&participant.books).unwrap_or_default();
+
+    let registration = if let Some((email, waitlist_position)) = existing {
+        stage_registration_update(
+            &tx,
+            &email,
+            &participant,
+            VerificationConfig::from_env().code_valid_for,
+        )?;
+        Registration {
+            outcome: RegistrationOutcome::UpdatePending,
+            waitlist_position,
+        }
+    } else {
+        let waitlist_position = next_waitlist_position(&tx, seats)?;
+        tx.execute(
+            "INSERT INTO participants (
+                name, enrolled, role, email, describe_yourself, background, 
+                github, skills, year, books, why, time, location, cohort_name, 
+                created_at, updated_at, waitlist_position
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+            params![
+                participant.name,
+                participant.enrolled,
+                participant.role,
+                participant.email,
+                participant.describe_yourself,
+                participant.background,
+                participant.github,
+                skills,
+                participant.year,
+                books,
+                participant.why,
+                participant.time,
+                participant.location,
+                participant.cohort_name,
+                now_str,
+                now_str,
+                waitlist_position
+            ],
+        )?;
+        // The bot invite follows once the email is verified (and the participant has a seat)
+        start_verification(
+            &tx,
+            &participant.name,
+            &participant.email,
+            &participant.cohort_name,
+            VerificationConfig::from_env().code_valid_for,
+        )?;
+        Registration {
+            outcome: if waitlist_position.is_some() {
+                RegistrationOutcome::Waitlisted
+            } else {
+                RegistrationOutcome::Created
+            },
+            waitlist_position,
+        }
+    };
+
+    info!("Successfully wrote rows to the database.");
+    tx.commit()?;
+    Ok(registration)
+}
+
+// Overwrites the answers of the participant registered as `email`
+pub(crate) fn update_participant(
+    conn: &Connection,
+    email: &str,
+    participant: &CohortParticipant,
+) -> Result<()> {
+    let skills = serde_json::to_string(&participant.skills).unwrap_or_default();
+    let books = serde_json::to_string(&participant.books).unwrap_or_default();
+    conn.execute(
+        "UPDATE participants SET
+            name = ?2, role = ?3, describe_yourself = ?4, background = ?5, github = ?6,
+            skills = ?7, year = ?8, books = ?9, why = ?10, time = ?11, location = ?12,
+            cohort_name = ?13, updated_at = ?14
+        WHERE lower(email) = lower(?1)",
         params![
+            email,
             participant.name,
-            participant.enrolled,
             participant.role,
-            participant.email,
             participant.describe_yourself,
             participant.background,
             participant.github,
-            serde_json::to_string(&participant.skills).unwrap_or_default(),
+            skills,
             participant.year,
-            serde_json::to_string(&participant.books).unwrap_or_default(),
+            books,
             participant.why,
             participant.time,
             participant.location,
             participant.cohort_name,
-            now_str,
-            now_str
+            Utc::now().naive_utc().to_string()
         ],
     )?;
-
-    info!("Successfully wrote rows to the database.");
-    tx.commit()?;
     Ok(())
 }
 
@@ -179,7 +514,7 @@ pub fn read_all_responses(db_path: &PathBuf, _cohort_name: &str) -> Result<Vec<F
     let conn = Connection::open(db_path)?;
 
     let mut stmt = conn.prepare("SELECT * FROM responses")?;
-    let response_iter = stmt.query_map(params![], |row| FeedbackResponse::from_row(row))?;
+    let response_iter = stmt.query_map(params![], FeedbackResponse::from_row)?;
 
     let mut responses = Vec::new();
     for response in response_iter {
@@ -189,6 +524,17 @@ pub fn read_all_responses(db_path: &PathBuf, _cohort_name: &str) -> Result<Vec<F
     Ok(responses)
 }
 
+// Students on the cohort roster (everyone with a week 0 row)
+pub fn count_enrolled_students(db_path: &PathBuf) -> Result<usize> {
+    let conn = Connection::open(db_path)?;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(DISTINCT name) FROM students WHERE week = 0",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
 pub fn match_discord_username(
     path: &PathBuf,
     name: &str,
@@ -198,7 +544,7 @@ pub fn match_discord_username(
     let mut stmt = conn.prepare("SELECT * FROM participants WHERE name = ?1 OR email = ?2")?;
 
     let participant = stmt
-        .query_row(params![name, email], |row| CohortParticipant::from_row(row))
+        .query_row(params![name, email], CohortParticipant::from_row)
         .map_err(|e| match e {
             rusqlite::Error::QueryReturnedNoRows => {
                 AppError::Database(rusqlite::Error::QueryReturnedNoRows)
@@ -0,0 +1,175 @@
+use crate::database::audit::audit_timestamp;
+use crate::utils::bot_invites::BOT_INVITE;
+use crate::utils::types::AppError;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{Connection, Row, params};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+// A message to an external service, written in the same transaction as the change that caused
+// it and delivered later by a background worker
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxMessage {
+    pub id: i64,
+    // What the message is, e.g. "bot_invite"
+    pub kind: String,
+    // Participant the message is about
+    pub email: String,
+    // JSON body to send
+    pub payload: String,
+    // "pending", "delivered" or "failed" (gave up after too many attempts)
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: String,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+pub fn ensure_outbox_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind            TEXT NOT NULL,
+            email           TEXT NOT NULL,
+            payload         TEXT NOT NULL,
+            status          TEXT NOT NULL DEFAULT 'pending',
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL,
+            last_error      TEXT,
+            created_at      TEXT NOT NULL,
+            delivered_at    TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_outbox_status ON outbox (status, next_attempt_at);",
+    )
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<OutboxMessage> {
+    Ok(OutboxMessage {
+        id: row.get("id")?,
+        kind: row.get("kind")?,
+        email: row.get("email")?,
+        payload: row.get("payload")?,
+        status: row.get("status")?,
+        attempts: row.get("attempts")?,
+        next_attempt_at: row.get("next_attempt_at")?,
+        last_error: row.get("last_error")?,
+        created_at: row.get("created_at")?,
+        delivered_at: row.get("delivered_at")?,
+    })
+}
+
+// Queues a message for immediate delivery. Takes a connection so callers can pass their
+// transaction and have the message commit (or roll back) with their own writes.
+pub fn enqueue_message(
+    conn: &Connection,
+    kind: &str,
+    email: &str,
+    payload: &serde_json::Value,
+) -> rusqlite::Result<i64> {
+    ensure_outbox_table(conn)?;
+    let now = audit_timestamp();
+    conn.execute(
+        "INSERT INTO outbox (kind, email, payload, next_attempt_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)",
+        params![kind, email, payload.to_string(), now],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// Pending messages of `kind` whose next attempt is due, oldest first
+pub fn due_messages(db_path: &Path, kind: &str) -> Result<Vec<OutboxMessage>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_outbox_table(&conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT * FROM outbox
+         WHERE kind = ?1 AND status = 'pending' AND next_attempt_at <= ?2
+         ORDER BY id",
+    )?;
+    let messages = stmt
+        .query_map(params![kind, audit_timestamp()], message_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(messages)
+}
+
+pub fn mark_delivered(db_path: &Path, id: i64) -> Result<(), AppError> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "UPDATE outbox SET status = 'delivered', attempts = attempts + 1, last_error = NULL,
+             delivered_at = ?2
+         WHERE id = ?1",
+        params![id, audit_timestamp()],
+    )?;
+    Ok(())
+}
+
+// Records a failed attempt. The message is retried after `retry_in`, or given up on when that
+// is None.
+pub fn mark_attempt_failed(
+    db_path: &Path,
+    id: i64,
+    error: &str,
+    retry_in: Option<Duration>,
+) -> Result<(), AppError> {
+    let conn = Connection::open(db_path)?;
+    let (status, next_attempt_at) = match retry_in {
+        Some(delay) => {
+            let at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+            ("pending", at.to_rfc3339_opts(SecondsFormat::Millis, true))
+        }
+        None => ("failed", audit_timestamp()),
+    };
+    conn.execute(
+        "UPDATE outbox SET status = ?2, attempts = attempts + 1, last_error = ?3,
+             next_attempt_at = ?4
+         WHERE id = ?1",
+        params![id, status, error, next_attempt_at],
+    )?;
+    Ok(())
+}
+
+// Puts a message of `kind` that was given up on back in the queue with a fresh attempt count.
+// None if there's no message of that kind with `id`.
+pub fn retry_message(
+    db_path: &Path,
+    kind: &str,
+    id: i64,
+) -> Result<Option<OutboxMessage>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_outbox_table(&conn)?;
+    conn.execute(
+        "UPDATE outbox SET status = 'pending', attempts = 0, next_attempt_at = ?3
+         WHERE id = ?1 AND kind = ?2 AND status = 'failed'",
+        params![id, kind, audit_timestamp()],
+    )?;
+    let mut stmt = conn.prepare("SELECT * FROM outbox WHERE id = ?1 AND kind = ?2")?;
+    let mut messages = stmt.query_map(params![id, kind], message_from_row)?;
+    Ok(messages.next().transpose()?)
+}
+
+// Messages of `kind`, newest first
+pub fn list_messages(db_path: &Path, kind: &str) -> Result<Vec<OutboxMessage>, AppError> {
+    let conn = Connection::open(db_path)?;
+    ensure_outbox_table(&conn)?;
+    let mut stmt = conn.prepare("SELECT * FROM outbox WHERE kind = ?1 ORDER BY id DESC")?;
+    let messages = stmt
+        .query_map(params![kind], message_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(messages)
+}
+
+// Queues the Discord bot invite for a registered participant
+pub fn enqueue_bot_invite(conn: &Connection, email: &str) -> rusqlite::Result<i64> {
+    let (name, email, role): (String, String, String) = conn.query_row(
+        "SELECT name, email, role FROM participants WHERE lower(email) = lower(?1)",
+        params![email],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    enqueue_message(
+        conn,
+        BOT_INVITE,
+        &email,
+        &serde_json::json!({ "name": name, "email": email, "role": role }),
+    )
+}
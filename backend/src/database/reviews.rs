@@ -0,0 +1,400 @@
+use crate::database::audit::audit_timestamp;
+use crate::database::enrollment::{EnrollmentError, EnrollmentStatus, change_enrollment_status};
+use crate::database::operations::add_roster_row;
+use crate::database::outbox::enqueue_bot_invite;
+use crate::database::verification::is_verified;
+use crate::database::waitlist::leave_waitlist;
+use crate::utils::types::AppError;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+// Something TAs score applications on, from 0 to `max`. Set per cohort under `review_criteria`
+// in `cohorts.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReviewCriterion {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub max: u32,
+}
+
+pub fn default_review_criteria() -> Vec<ReviewCriterion> {
+    let criterion = |name: &str, description: &str| ReviewCriterion {
+        name: name.to_string(),
+        description: description.to_string(),
+        max: 5,
+    };
+    vec![
+        criterion("motivation", "Why they want to join"),
+        criterion("background", "Background and skills for the cohort"),
+        criterion(
+            "preparation",
+            "Books read and what they describe of themselves",
+        ),
+        criterion("commitment", "Time they can give each week"),
+    ]
+}
+
+#[derive(Debug, Error)]
+pub enum ReviewError {
+    #[error("No application from {0}")]
+    UnknownApplicant(String),
+    #[error("Unknown criterion {0:?}")]
+    UnknownCriterion(String),
+    #[error("Score for {0} must be between 0 and {1}")]
+    ScoreOutOfRange(String, u32),
+    #[error("Missing score for {0}")]
+    MissingScore(String),
+    #[error(transparent)]
+    Enrollment(EnrollmentError),
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+impl From<rusqlite::Error> for ReviewError {
+    fn from(e: rusqlite::Error) -> Self {
+        ReviewError::App(AppError::Database(e))
+    }
+}
+
+impl From<EnrollmentError> for ReviewError {
+    fn from(e: EnrollmentError) -> Self {
+        match e {
+            EnrollmentError::App(e) => ReviewError::App(e),
+            e => ReviewError::Enrollment(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Review {
+    pub email: String,
+    pub reviewer: String,
+    pub scores: BTreeMap<String, u32>,
+    pub comment: Option<String>,
+    pub reviewed_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Accept,
+    Reject,
+}
+
+// A registration as TAs see it in the review queue
+#[derive(Debug, Serialize)]
+pub struct Application {
+    pub name: String,
+    pub email: String,
+    pub github: Option<String>,
+    pub describe_yourself: Option<String>,
+    pub background: Option<String>,
+    pub skills: Option<String>,
+    pub books: Option<String>,
+    pub why: Option<String>,
+    pub time: Option<String>,
+    pub location: Option<String>,
+    pub registered_at: String,
+    pub reviews: Vec<Review>,
+    // Sum of the criteria scores, averaged over reviewers
+    pub average_score: Option<f64>,
+    // "accepted" or "rejected" once decided
+    pub decision: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DecisionReport {
+    pub accepted: Vec<String>,
+    pub rejected: Vec<String>,
+    // Unknown applicants, applications that were already decided or can no longer be decided
+    // this way, and applicants whose name another roster student already has
+    pub skipped: Vec<String>,
+}
+
+pub(crate) fn ensure_review_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS application_reviews (
+            email       TEXT NOT NULL COLLATE NOCASE,
+            reviewer    TEXT NOT NULL,
+            scores      TEXT NOT NULL,
+            comment     TEXT,
+            reviewed_at TEXT NOT NULL,
+            PRIMARY KEY (email, reviewer)
+        );
+        CREATE TABLE IF NOT EXISTS application_decisions (
+            email      TEXT PRIMARY KEY COLLATE NOCASE,
+            decision   TEXT NOT NULL,
+            comment    TEXT,
+            decided_by TEXT NOT NULL,
+            decided_at TEXT NOT NULL
+        );",
+    )
+}
+
+fn applicant(conn: &Connection, email: &str) -> rusqlite::Result<Option<(String, String)>> {
+    conn.query_row(
+        "SELECT name, email FROM participants WHERE lower(email) = lower(trim(?1))",
+        params![email],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+// Records `reviewer`'s scores for an application, replacing their earlier review. Every
+// criterion must be scored.
+pub fn submit_review(
+    db_path: &Path,
+    email: &str,
+    reviewer: &str,
+    scores: &BTreeMap<String, u32>,
+    comment: Option<&str>,
+    criteria: &[ReviewCriterion],
+) -> Result<Review, ReviewError> {
+    if let Some(name) = scores
+        .keys()
+        .find(|name| !criteria.iter().any(|c| &c.name == *name))
+    {
+        return Err(ReviewError::UnknownCriterion(name.clone()));
+    }
+    for criterion in criteria {
+        match scores.get(&criterion.name) {
+            None => return Err(ReviewError::MissingScore(criterion.name.clone())),
+            Some(&score) if score > criterion.max => {
+                return Err(ReviewError::ScoreOutOfRange(
+                    criterion.name.clone(),
+                    criterion.max,
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let conn = Connection::open(db_path)?;
+    ensure_review_tables(&conn)?;
+    let (_, email) =
+        applicant(&conn, email)?.ok_or_else(|| ReviewError::UnknownApplicant(email.to_string()))?;
+    let review = Review {
+        email,
+        reviewer: reviewer.to_string(),
+        scores: scores.clone(),
+        comment: comment
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::to_string),
+        reviewed_at: audit_timestamp(),
+    };
+    conn.execute(
+        "INSERT OR REPLACE INTO application_reviews (email, reviewer, scores, comment, reviewed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            review.email,
+            review.reviewer,
+            serde_json::to_string(&review.scores).unwrap_or_default(),
+            review.comment,
+            review.reviewed_at
+        ],
+    )?;
+    Ok(review)
+}
+
+// Applications with their reviews, undecided ones first and then by average score (highest
+// first). Decided applications are only included with `include_decided`.
+pub fn review_queue(
+    db_path: &Path,
+    include_decided: bool,
+) -> Result<Vec<Application>, ReviewError> {
+    let conn = Connection::open(db_path)?;
+    let has_participants: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'participants'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_participants {
+        return Ok(Vec::new());
+    }
+    ensure_review_tables(&conn)?;
+
+    let mut reviews: BTreeMap<String, Vec<Review>> = BTreeMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT email, reviewer, scores, comment, reviewed_at FROM application_reviews
+         ORDER BY reviewed_at",
+    )?;
+    for review in stmt.query_map([], |row| {
+        let scores: String = row.get(2)?;
+        Ok(Review {
+            email: row.get(0)?,
+            reviewer: row.get(1)?,
+            scores: serde_json::from_str(&scores).unwrap_or_default(),
+            comment: row.get(3)?,
+            reviewed_at: row.get(4)?,
+        })
+    })? {
+        let review = review?;
+        reviews
+            .entry(review.email.to_lowercase())
+            .or_default()
+            .push(review);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT p.name, p.email, p.github, p.describe_yourself, p.background, p.skills, p.books,
+                p.why, p.time, p.location, p.created_at, d.decision
+         FROM participants p LEFT JOIN application_decisions d ON lower(d.email) = lower(p.email)",
+    )?;
+    let mut applications = stmt
+        .query_map([], |row| {
+            Ok(Application {
+                name: row.get(0)?,
+                email: row.get(1)?,
+                github: row.get(2)?,
+                describe_yourself: row.get(3)?,
+                background: row.get(4)?,
+                skills: row.get(5)?,
+                books: row.get(6)?,
+                why: row.get(7)?,
+                time: row.get(8)?,
+                location: row.get(9)?,
+                registered_at: row.get(10)?,
+                decision: row.get(11)?,
+                reviews: Vec::new(),
+                average_score: None,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    applications.retain(|a| include_decided || a.decision.is_none());
+    for application in &mut applications {
+        application.reviews = reviews
+            .remove(&application.email.to_lowercase())
+            .unwrap_or_default();
+        if !application.reviews.is_empty() {
+            let total: u32 = application
+                .reviews
+                .iter()
+                .map(|r| r.scores.values().sum::<u32>())
+                .sum();
+            application.average_score = Some(total as f64 / application.reviews.len() as f64);
+        }
+    }
+    applications.sort_by(|a, b| {
+        a.decision
+            .is_some()
+            .cmp(&b.decision.is_some())
+            .then(
+                b.average_score
+                    .unwrap_or(-1.0)
+                    .total_cmp(&a.average_score.unwrap_or(-1.0)),
+            )
+            .then_with(|| a.registered_at.cmp(&b.registered_at))
+    });
+    Ok(applications)
+}
+
+// Accepts or rejects applications in one transaction. Accepted applicants move to `accepted`,
+// get a week 0 row on the roster and leave the waitlist with a bot invite like a promotion.
+// Rejected applicants move to `rejected` and give up their seat or waitlist position.
+pub fn decide_applications(
+    db_path: &Path,
+    emails: &[String],
+    decision: Decision,
+    comment: Option<&str>,
+    actor: &str,
+) -> Result<DecisionReport, ReviewError> {
+    let mut conn = Connection::open(db_path)?;
+    ensure_review_tables(&conn)?;
+    let tx = conn.transaction()?;
+    let mut report = DecisionReport::default();
+    let now = audit_timestamp();
+
+    for email in emails {
+        let Some((name, email)) = applicant(&tx, email)? else {
+            report.skipped.push(email.clone());
+            continue;
+        };
+        let decided: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM application_decisions WHERE email = ?1",
+            params![email],
+            |row| row.get(0),
+        )?;
+        if decided {
+            report.skipped.push(email);
+            continue;
+        }
+
+        let label = match decision {
+            Decision::Accept => {
+                if name_taken(&tx, &name, &email)? {
+                    report.skipped.push(email);
+                    continue;
+                }
+                // Participants who are already further along keep their status
+                match change_enrollment_status(
+                    &tx,
+                    &email,
+                    EnrollmentStatus::Accepted,
+                    comment,
+                    actor,
+                ) {
+                    Ok(_) | Err(EnrollmentError::InvalidTransition(..)) => {}
+                    Err(e) => return Err(e.into()),
+                }
+                add_roster_row(&tx, &name, &email, actor)?;
+                // Unverified applicants get their invite when they verify
+                if leave_waitlist(&tx, &email)? && is_verified(&tx, &email)? {
+                    enqueue_bot_invite(&tx, &email)?;
+                }
+                report.accepted.push(email.clone());
+                "accepted"
+            }
+            Decision::Reject => {
+                // Applicants who already got further than an application can't be rejected
+                match change_enrollment_status(
+                    &tx,
+                    &email,
+                    EnrollmentStatus::Rejected,
+                    comment,
+                    actor,
+                ) {
+                    Ok(_) => {}
+                    Err(EnrollmentError::InvalidTransition(..)) => {
+                        report.skipped.push(email);
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                leave_waitlist(&tx, &email)?;
+                report.rejected.push(email.clone());
+                "rejected"
+            }
+        };
+        tx.execute(
+            "INSERT INTO application_decisions (email, decision, comment, decided_by, decided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![email, label, comment, actor, now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+// Whether another roster student has `name`; rows are told apart by name
+fn name_taken(conn: &Connection, name: &str, email: &str) -> rusqlite::Result<bool> {
+    let has_students: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'students'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_students {
+        return Ok(false);
+    }
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM students
+         WHERE week = 0 AND name = ?1 AND lower(trim(COALESCE(mail, ''))) != lower(trim(?2))",
+        params![name, email],
+        |row| row.get(0),
+    )
+}
@@ -0,0 +1,351 @@
+use crate::database::audit::audit_timestamp;
+use crate::database::operations::update_participant;
+use crate::database::outbox::{enqueue_bot_invite, enqueue_message};
+use crate::database::waitlist::holds_seat;
+use crate::utils::email_verification::VERIFICATION_EMAIL;
+use crate::utils::types::{AppError, CohortParticipant};
+use chrono::{SecondsFormat, Utc};
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+// Wrong codes allowed before a new one has to be requested
+const MAX_CODE_ATTEMPTS: i64 = 5;
+
+#[derive(Debug, Error)]
+pub enum VerificationError {
+    #[error("No verification pending for this email")]
+    NotFound,
+    #[error("Verification code has expired, request a new one")]
+    Expired,
+    #[error("Wrong verification code")]
+    WrongCode,
+    #[error("Too many wrong codes, request a new one")]
+    TooManyAttempts,
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+impl From<rusqlite::Error> for VerificationError {
+    fn from(e: rusqlite::Error) -> Self {
+        VerificationError::App(AppError::Database(e))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Verification {
+    pub email: String,
+    pub verified_at: String,
+    // Whether this verification queued the bot invite; waitlisted participants get theirs
+    // when promoted
+    pub invite_queued: bool,
+}
+
+fn ensure_verification_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS email_verifications (
+            email           TEXT PRIMARY KEY COLLATE NOCASE,
+            code            TEXT NOT NULL,
+            created_at      TEXT NOT NULL,
+            expires_at      TEXT NOT NULL,
+            failed_attempts INTEGER NOT NULL DEFAULT 0,
+            verified_at     TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// New answers for an existing registration, waiting for the code sent to the registered email so
+// that knowing someone's email isn't enough to overwrite their registration
+fn ensure_pending_updates_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_registration_updates (
+            email           TEXT PRIMARY KEY COLLATE NOCASE,
+            participant     TEXT NOT NULL,
+            code            TEXT NOT NULL,
+            created_at      TEXT NOT NULL,
+            expires_at      TEXT NOT NULL,
+            failed_attempts INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn generate_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+// Gives `email` a fresh code valid for `valid_for` and queues the email carrying it. Runs on the
+// caller's connection so it commits with the registration.
+pub fn start_verification(
+    conn: &Connection,
+    name: &str,
+    email: &str,
+    cohort: &str,
+    valid_for: Duration,
+) -> rusqlite::Result<()> {
+    ensure_verification_table(conn)?;
+    let code = generate_code();
+    let expires_at = (Utc::now() + chrono::Duration::from_std(valid_for).unwrap_or_default())
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+    // A resent code keeps the failed attempts, so resending doesn't lift the limit
+    conn.execute(
+        "INSERT INTO email_verifications (email, code, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(email) DO UPDATE SET code = excluded.code,
+             created_at = excluded.created_at, expires_at = excluded.expires_at,
+             verified_at = NULL",
+        params![email, code, audit_timestamp(), expires_at],
+    )?;
+    enqueue_message(
+        conn,
+        VERIFICATION_EMAIL,
+        email,
+        &serde_json::json!({ "name": name, "email": email, "cohort": cohort, "code": code }),
+    )?;
+    Ok(())
+}
+
+// Stages `participant` as the new answers of the registration of `email` and sends a code to
+// that email. A newer update replaces a staged one.
+pub fn stage_registration_update(
+    conn: &Connection,
+    email: &str,
+    participant: &CohortParticipant,
+    valid_for: Duration,
+) -> rusqlite::Result<()> {
+    ensure_pending_updates_table(conn)?;
+    let code = generate_code();
+    let expires_at = (Utc::now() + chrono::Duration::from_std(valid_for).unwrap_or_default())
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+    let payload = serde_json::to_string(participant)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    // Restaging keeps the failed attempts, like a resent verification code
+    conn.execute(
+        "INSERT INTO pending_registration_updates
+             (email, participant, code, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(email) DO UPDATE SET participant = excluded.participant,
+             code = excluded.code, created_at = excluded.created_at,
+             expires_at = excluded.expires_at",
+        params![email, payload, code, audit_timestamp(), expires_at],
+    )?;
+    enqueue_message(
+        conn,
+        VERIFICATION_EMAIL,
+        email,
+        &serde_json::json!({
+            "name": participant.name,
+            "email": email,
+            "cohort": participant.cohort_name,
+            "code": code
+        }),
+    )?;
+    Ok(())
+}
+
+// Participants registered before verification existed have no entry and count as verified
+pub fn is_verified(conn: &Connection, email: &str) -> rusqlite::Result<bool> {
+    ensure_verification_table(conn)?;
+    let verified: Option<bool> = conn
+        .query_row(
+            "SELECT verified_at IS NOT NULL FROM email_verifications WHERE email = ?1",
+            params![email],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(verified.unwrap_or(true))
+}
+
+// Checks `code` against a staged registration update of `email`, applying it on a match.
+// Returns whether it was applied, or why not; None when nothing is staged.
+fn confirm_registration_update(
+    conn: &Connection,
+    email: &str,
+    code: &str,
+    now: &str,
+) -> Result<Option<Result<(), VerificationError>>, VerificationError> {
+    ensure_pending_updates_table(conn)?;
+    let pending: Option<(String, String, String, i64)> = conn
+        .query_row(
+            "SELECT participant, code, expires_at, failed_attempts
+             FROM pending_registration_updates WHERE email = ?1",
+            params![email],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+    let Some((participant, stored_code, expires_at, failed_attempts)) = pending else {
+        return Ok(None);
+    };
+    if failed_attempts >= MAX_CODE_ATTEMPTS {
+        return Ok(Some(Err(VerificationError::TooManyAttempts)));
+    }
+    if now >= expires_at.as_str() {
+        return Ok(Some(Err(VerificationError::Expired)));
+    }
+    if code.trim() != stored_code {
+        conn.execute(
+            "UPDATE pending_registration_updates SET failed_attempts = failed_attempts + 1
+             WHERE email = ?1",
+            params![email],
+        )?;
+        return Ok(Some(Err(VerificationError::WrongCode)));
+    }
+    let participant: CohortParticipant =
+        serde_json::from_str(&participant).map_err(|e| AppError::Io(std::io::Error::other(e)))?;
+    update_participant(conn, email, &participant)?;
+    conn.execute(
+        "DELETE FROM pending_registration_updates WHERE email = ?1",
+        params![email],
+    )?;
+    Ok(Some(Ok(())))
+}
+
+// Checks `code` and marks the email verified. Participants with a seat get their bot invite
+// queued in the same transaction. A code sent for a registration update applies the update (and
+// verifies the email too). Verifying twice is harmless.
+pub fn verify_email(
+    db_path: &Path,
+    email: &str,
+    code: &str,
+) -> Result<Verification, VerificationError> {
+    let mut conn = Connection::open(db_path)?;
+    ensure_verification_table(&conn)?;
+    let tx = conn.transaction()?;
+    let email = email.trim();
+    let now = audit_timestamp();
+
+    let update = confirm_registration_update(&tx, email, code, &now)?;
+    let update_applied = matches!(update, Some(Ok(())));
+    let stored: Option<(String, String, String, i64, Option<String>)> = tx
+        .query_row(
+            "SELECT email, code, expires_at, failed_attempts, verified_at
+             FROM email_verifications WHERE email = ?1",
+            params![email],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    // Once verified (or if registered before verification existed) only a staged update can be
+    // confirmed, and its outcome is the answer
+    let (stored_email, stored_code, expires_at, failed_attempts) = match stored {
+        Some((stored_email, stored_code, expires_at, failed_attempts, None)) => {
+            (stored_email, stored_code, expires_at, failed_attempts)
+        }
+        Some((stored_email, .., Some(verified_at))) => {
+            tx.commit()?;
+            return match update {
+                Some(Err(e)) => Err(e),
+                _ => Ok(Verification {
+                    email: stored_email,
+                    verified_at,
+                    invite_queued: false,
+                }),
+            };
+        }
+        None => {
+            tx.commit()?;
+            return match update {
+                Some(Err(e)) => Err(e),
+                Some(Ok(())) => Ok(Verification {
+                    email: email.to_string(),
+                    verified_at: now,
+                    invite_queued: false,
+                }),
+                None => Err(VerificationError::NotFound),
+            };
+        }
+    };
+
+    if !update_applied {
+        if failed_attempts >= MAX_CODE_ATTEMPTS {
+            tx.commit()?;
+            return Err(VerificationError::TooManyAttempts);
+        }
+        if now >= expires_at {
+            tx.commit()?;
+            return Err(VerificationError::Expired);
+        }
+        if code.trim() != stored_code {
+            tx.execute(
+                "UPDATE email_verifications SET failed_attempts = failed_attempts + 1
+                 WHERE email = ?1",
+                params![stored_email],
+            )?;
+            tx.commit()?;
+            return Err(VerificationError::WrongCode);
+        }
+    }
+
+    tx.execute(
+        "UPDATE email_verifications SET verified_at = ?2 WHERE email = ?1",
+        params![stored_email, now],
+    )?;
+    let seated = holds_seat(&tx, &stored_email)?;
+    if seated {
+        enqueue_bot_invite(&tx, &stored_email)?;
+    }
+    tx.commit()?;
+    Ok(Verification {
+        email: stored_email,
+        verified_at: now,
+        invite_queued: seated,
+    })
+}
+
+// Sends a new code to a registered participant who hasn't verified yet, or for their staged
+// registration update. Returns false if there is nothing to resend.
+pub fn resend_verification(
+    db_path: &Path,
+    email: &str,
+    cohort: &str,
+    valid_for: Duration,
+) -> Result<bool, AppError> {
+    let mut conn = Connection::open(db_path)?;
+    ensure_verification_table(&conn)?;
+    ensure_pending_updates_table(&conn)?;
+    let tx = conn.transaction()?;
+    let staged: Option<(String, String)> = tx
+        .query_row(
+            "SELECT email, participant FROM pending_registration_updates WHERE email = trim(?1)",
+            params![email],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    if let Some((email, participant)) = staged {
+        let participant: CohortParticipant = serde_json::from_str(&participant)
+            .map_err(|e| AppError::Io(std::io::Error::other(e)))?;
+        stage_registration_update(&tx, &email, &participant, valid_for)?;
+        tx.commit()?;
+        return Ok(true);
+    }
+
+    let participant: Option<(String, String)> = tx
+        .query_row(
+            "SELECT p.name, p.email FROM participants p
+             JOIN email_verifications v ON v.email = p.email
+             WHERE lower(p.email) = lower(trim(?1)) AND v.verified_at IS NULL",
+            params![email],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((name, email)) = participant else {
+        return Ok(false);
+    };
+    start_verification(&tx, &name, &email, cohort, valid_for)?;
+    tx.commit()?;
+    Ok(true)
+}
@@ -0,0 +1,191 @@
+use crate::database::outbox::enqueue_bot_invite;
+use crate::database::reviews::ensure_review_tables;
+use crate::database::verification::is_verified;
+use crate::utils::types::AppError;
+use log::info;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::path::Path;
+
+// A participant waiting for a seat. Positions start at 1 and have no gaps.
+#[derive(Debug, Clone, Serialize)]
+pub struct WaitlistEntry {
+    pub position: i64,
+    pub name: String,
+    pub email: String,
+    pub registered_at: String,
+}
+
+// Participants with a `waitlist_position` are waiting for a seat; NULL means they have one.
+// Adds the column to participant tables created before waitlists existed.
+pub fn ensure_waitlist_column(conn: &Connection) -> rusqlite::Result<()> {
+    let has_column: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('participants') WHERE name = 'waitlist_position'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_column {
+        conn.execute(
+            "ALTER TABLE participants ADD COLUMN waitlist_position INTEGER",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// Where a new registration goes: None if one of `seats` is free, else the next waitlist position
+pub fn next_waitlist_position(
+    conn: &Connection,
+    seats: Option<usize>,
+) -> rusqlite::Result<Option<i64>> {
+    let Some(seats) = seats else {
+        return Ok(None);
+    };
+    ensure_review_tables(conn)?;
+    let (seated, last): (i64, i64) = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FILTER (WHERE waitlist_position IS NULL AND {NOT_REJECTED}),
+                    COALESCE(MAX(waitlist_position), 0)
+             FROM participants"
+        ),
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok((seated as usize >= seats).then_some(last + 1))
+}
+
+// Rejected applicants keep their registration but neither hold a seat nor wait for one
+const NOT_REJECTED: &str = "NOT EXISTS (SELECT 1 FROM application_decisions d
+                                        WHERE d.email = participants.email
+                                          AND d.decision = 'rejected')";
+
+// Whether `email` has a seat, i.e. gets a bot invite once their email is verified
+pub fn holds_seat(conn: &Connection, email: &str) -> rusqlite::Result<bool> {
+    ensure_review_tables(conn)?;
+    let seated: Option<bool> = conn
+        .query_row(
+            &format!(
+                "SELECT waitlist_position IS NULL AND {NOT_REJECTED} FROM participants
+                 WHERE lower(email) = lower(?1)"
+            ),
+            params![email],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(seated.unwrap_or(false))
+}
+
+// Takes `email` off the waitlist and moves everyone behind them up. Runs on the caller's
+// connection. Returns false if they weren't waiting.
+pub fn leave_waitlist(conn: &Connection, email: &str) -> rusqlite::Result<bool> {
+    ensure_waitlist_column(conn)?;
+    let position: Option<i64> = conn
+        .query_row(
+            "SELECT waitlist_position FROM participants WHERE lower(email) = lower(?1)",
+            params![email],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let Some(position) = position else {
+        return Ok(false);
+    };
+    conn.execute(
+        "UPDATE participants SET waitlist_position = NULL WHERE lower(email) = lower(?1)",
+        params![email],
+    )?;
+    conn.execute(
+        "UPDATE participants SET waitlist_position = waitlist_position - 1
+         WHERE waitlist_position > ?1",
+        params![position],
+    )?;
+    Ok(true)
+}
+
+fn has_participants(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'participants'",
+        [],
+        |row| row.get(0),
+    )
+}
+
+fn waitlist(conn: &Connection) -> rusqlite::Result<Vec<WaitlistEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT waitlist_position, name, email, created_at FROM participants
+         WHERE waitlist_position IS NOT NULL ORDER BY waitlist_position",
+    )?;
+    stmt.query_map([], |row| {
+        Ok(WaitlistEntry {
+            position: row.get(0)?,
+            name: row.get(1)?,
+            email: row.get(2)?,
+            registered_at: row.get(3)?,
+        })
+    })?
+    .collect()
+}
+
+pub fn read_waitlist(db_path: &Path) -> Result<Vec<WaitlistEntry>, AppError> {
+    let conn = Connection::open(db_path)?;
+    if !has_participants(&conn)? {
+        return Ok(Vec::new());
+    }
+    ensure_waitlist_column(&conn)?;
+    Ok(waitlist(&conn)?)
+}
+
+// Gives seats to the waitlisted participants in `emails`, or to the first `count` in line if
+// none are named, and queues the bot invites of those who verified their email. Returns who was
+// promoted; the rest move up.
+pub fn promote_from_waitlist(
+    db_path: &Path,
+    emails: &[String],
+    count: usize,
+) -> Result<Vec<WaitlistEntry>, AppError> {
+    let mut conn = Connection::open(db_path)?;
+    if !has_participants(&conn)? {
+        return Ok(Vec::new());
+    }
+    ensure_waitlist_column(&conn)?;
+    let tx = conn.transaction()?;
+
+    let waiting = waitlist(&tx)?;
+    let (promoted, remaining): (Vec<_>, Vec<_>) = if emails.is_empty() {
+        let count = count.min(waiting.len());
+        let mut waiting = waiting;
+        let remaining = waiting.split_off(count);
+        (waiting, remaining)
+    } else {
+        waiting.into_iter().partition(|e| {
+            emails
+                .iter()
+                .any(|m| m.trim().eq_ignore_ascii_case(&e.email))
+        })
+    };
+
+    for entry in &promoted {
+        tx.execute(
+            "UPDATE participants SET waitlist_position = NULL WHERE email = ?1",
+            params![entry.email],
+        )?;
+        // Unverified participants get their invite when they verify
+        if is_verified(&tx, &entry.email)? {
+            enqueue_bot_invite(&tx, &entry.email)?;
+        }
+    }
+    for (index, entry) in remaining.iter().enumerate() {
+        tx.execute(
+            "UPDATE participants SET waitlist_position = ?2 WHERE email = ?1",
+            params![entry.email, index as i64 + 1],
+        )?;
+    }
+    tx.commit()?;
+
+    info!(
+        "Promoted {} participants from the waitlist of {}",
+        promoted.len(),
+        db_path.display()
+    );
+    Ok(promoted)
+}
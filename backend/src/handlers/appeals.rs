@@ -0,0 +1,117 @@
+use crate::database::appeals::{
+    AppealDecision, AppealError, file_appeal, list_appeals, resolve_appeal,
+};
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::cohort::cohort_db;
+use crate::utils::session::{SessionStore, require_participant, require_ta};
+use crate::utils::types::Table;
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct NewAppeal {
+    pub week: i32,
+    pub criterion: String,
+    pub comment: String,
+}
+
+#[derive(Deserialize)]
+pub struct AppealQueueQuery {
+    // Defaults to "pending"; "all" lists every appeal
+    pub status: Option<String>,
+}
+
+fn appeal_error(e: AppealError) -> actix_web::Error {
+    match e {
+        AppealError::UnknownCriterion(_)
+        | AppealError::UnknownWeek(_)
+        | AppealError::InvalidValue(..)
+        | AppealError::MissingValue => ErrorBadRequest(e),
+        AppealError::AlreadyPending | AppealError::AlreadyResolved => ErrorConflict(e),
+        AppealError::NotFound => ErrorNotFound(e),
+        AppealError::App(e) => ErrorInternalServerError(e),
+    }
+}
+
+#[post("/me/appeals")]
+pub async fn file_my_appeal(
+    body: web::Json<NewAppeal>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (email, db_path) = require_participant(&req, &sessions)?;
+    if body.comment.trim().is_empty() {
+        return Err(ErrorBadRequest("Please explain the appeal in a comment"));
+    }
+    let appeal = file_appeal(
+        &db_path,
+        &email,
+        body.week,
+        &body.criterion,
+        body.comment.trim(),
+    )
+    .map_err(appeal_error)?;
+    info!(
+        "{} appealed {} of week {}",
+        appeal.student, appeal.criterion, appeal.week
+    );
+    Ok(HttpResponse::Created().json(appeal))
+}
+
+#[get("/me/appeals")]
+pub async fn get_my_appeals(
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (email, db_path) = require_participant(&req, &sessions)?;
+    let appeals = list_appeals(&db_path, None, Some(&email)).map_err(appeal_error)?;
+    Ok(HttpResponse::Ok().json(appeals))
+}
+
+#[get("/appeals/{cohort}")]
+pub async fn get_appeal_queue(
+    path: web::Path<String>,
+    query: web::Query<AppealQueueQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    let status = match query.status.as_deref() {
+        None => Some("pending"),
+        Some("all") => None,
+        Some(status) => Some(status),
+    };
+    let appeals = list_appeals(&db_path, status, None).map_err(appeal_error)?;
+    Ok(HttpResponse::Ok().json(appeals))
+}
+
+#[post("/appeals/{cohort}/{id}/resolve")]
+pub async fn resolve_cohort_appeal(
+    path: web::Path<(String, i64)>,
+    decision: web::Json<AppealDecision>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+    state: web::Data<Mutex<Table>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, id) = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let appeal =
+        resolve_appeal(&db_path, id, &decision, &actor.audit_name()).map_err(appeal_error)?;
+    reload_state_if_active(&state, &db_path);
+
+    info!(
+        "{} {} appeal {} ({} week {})",
+        actor.audit_name(),
+        appeal.status,
+        id,
+        appeal.student,
+        appeal.week
+    );
+    Ok(HttpResponse::Ok().json(appeal))
+}
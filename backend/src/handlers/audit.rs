@@ -0,0 +1,137 @@
+use crate::database::audit::{
+    AuditFilter, normalize_timestamp, read_audit_log, reconstruct_students_at,
+};
+use crate::database::operations::restore_students;
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::cohort::cohort_db;
+use crate::utils::session::{SessionStore, require_ta};
+use crate::utils::types::{AuditEntry, RowData, Table};
+use actix_web::{HttpRequest, HttpResponse, error::ErrorBadRequest, get, post, web};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct StudentHistoryResponse {
+    student: String,
+    // Changes per week, oldest first
+    weeks: BTreeMap<i32, Vec<AuditEntry>>,
+}
+
+#[derive(Deserialize)]
+pub struct PointInTimeQuery {
+    pub at: String,
+}
+
+#[derive(Serialize)]
+struct PointInTimePreview {
+    at: String,
+    rows: Vec<RowData>,
+}
+
+#[derive(Serialize)]
+struct RestoreResponse {
+    at: String,
+    rows: usize,
+    rows_deleted: usize,
+    changes_applied: usize,
+}
+
+#[get("/audit/{cohort_name}")]
+pub async fn get_audit_log(
+    path: web::Path<String>,
+    filter: web::Query<AuditFilter>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    for value in [&filter.since, &filter.until].into_iter().flatten() {
+        if normalize_timestamp(value.trim()).is_none() {
+            return Err(ErrorBadRequest(format!("Invalid timestamp: {}", value)));
+        }
+    }
+    let cohort_name = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let entries = read_audit_log(&db_path, &filter)?;
+    info!(
+        "Fetched {} audit entries for cohort: {}",
+        entries.len(),
+        cohort_name
+    );
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[get("/audit/{cohort_name}/students/{student_name}")]
+pub async fn get_student_history(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let (cohort_name, student_name) = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let filter = AuditFilter {
+        student: Some(student_name.clone()),
+        ..Default::default()
+    };
+    let entries = read_audit_log(&db_path, &filter)?;
+
+    let mut weeks: BTreeMap<i32, Vec<AuditEntry>> = BTreeMap::new();
+    for entry in entries.into_iter().rev() {
+        weeks.entry(entry.week).or_default().push(entry);
+    }
+
+    Ok(HttpResponse::Ok().json(StudentHistoryResponse {
+        student: student_name,
+        weeks,
+    }))
+}
+
+#[get("/audit/{cohort_name}/as_of")]
+pub async fn preview_students_as_of(
+    path: web::Path<String>,
+    query: web::Query<PointInTimeQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    let at = normalize_timestamp(&query.at)
+        .ok_or_else(|| ErrorBadRequest(format!("Invalid timestamp: {}", query.at)))?;
+
+    let table = reconstruct_students_at(&db_path, &at)?;
+    Ok(HttpResponse::Ok().json(PointInTimePreview {
+        at,
+        rows: table.rows,
+    }))
+}
+
+#[post("/audit/{cohort_name}/restore")]
+pub async fn restore_students_as_of(
+    path: web::Path<String>,
+    body: web::Json<PointInTimeQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+    state: web::Data<Mutex<Table>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    let at = normalize_timestamp(&body.at)
+        .ok_or_else(|| ErrorBadRequest(format!("Invalid timestamp: {}", body.at)))?;
+
+    let table = reconstruct_students_at(&db_path, &at)?;
+    let (rows_deleted, changes_applied) =
+        restore_students(&db_path, &table.rows, &actor.audit_name())?;
+    reload_state_if_active(&state, &db_path);
+
+    info!("{} restored {:?} to {}", actor.audit_name(), db_path, at);
+    Ok(HttpResponse::Ok().json(RestoreResponse {
+        at,
+        rows: table.rows.len(),
+        rows_deleted,
+        changes_applied,
+    }))
+}
@@ -1,7 +1,9 @@
 use crate::utils::constants::{TA_EMAILS, get_auth_token};
+use crate::utils::session::{Actor, SessionStore};
 use crate::utils::types::TaLogin;
 use actix_web::{HttpResponse, Responder, post, web};
 use log::info;
+use std::sync::Mutex;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TA {
@@ -44,12 +46,20 @@ impl TA {
 }
 
 #[post("/login")]
-pub async fn login(item: web::Json<TaLogin>) -> impl Responder {
+pub async fn login(
+    item: web::Json<TaLogin>,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> impl Responder {
     info!("TA login attempt: {:?}", item.gmail);
-    if let Some(_ta) = TA::from_email(&item.gmail) {
+    if let Some(ta) = TA::from_email(&item.gmail) {
         info!("TA login success.");
+        let session = sessions
+            .lock()
+            .unwrap()
+            .create(Actor::Ta(format!("{:?}", ta)));
         HttpResponse::Ok().json(serde_json::json!({
-        "token": get_auth_token("ta")
+        "token": get_auth_token("ta"),
+        "session": session
         }))
     } else {
         info!("TA login failed: not authorized.");
@@ -0,0 +1,107 @@
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::backup::{
+    BackupConfig, backup_database, find_backup, list_backups, restore_backup, table_row_counts,
+};
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::session::{SessionStore, require_ta};
+use crate::utils::types::Table;
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct BackupInfo {
+    file: String,
+    created: String,
+    size_bytes: u64,
+    row_counts: BTreeMap<String, i64>,
+}
+
+#[get("/backups/{cohort}")]
+pub async fn list_cohort_backups(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+    let config = BackupConfig::from_env();
+
+    let backups: Vec<BackupInfo> = list_backups(&db_path, &config)
+        .into_iter()
+        .map(|backup| BackupInfo {
+            file: backup
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            created: backup.created.to_string(),
+            size_bytes: std::fs::metadata(&backup.path)
+                .map(|m| m.len())
+                .unwrap_or(0),
+            row_counts: table_row_counts(&backup.path).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(backups))
+}
+
+#[post("/backups/{cohort}")]
+pub async fn create_cohort_backup(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let backup =
+        backup_database(&db_path, &BackupConfig::from_env()).map_err(ErrorInternalServerError)?;
+    info!(
+        "{} created backup {:?}",
+        actor.audit_name(),
+        backup.file_name()
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "file": backup.file_name().unwrap_or_default().to_string_lossy()
+    })))
+}
+
+#[post("/backups/{cohort}/{file}/restore")]
+pub async fn restore_cohort_backup(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+    state: web::Data<Mutex<Table>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, file_name) = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+    let config = BackupConfig::from_env();
+    let db_path = cohort.db_path();
+
+    let backup = find_backup(&db_path, &file_name, &config)
+        .ok_or_else(|| ErrorNotFound("Unknown backup"))?;
+    let safety_backup =
+        restore_backup(&db_path, &backup.path, &config).map_err(ErrorInternalServerError)?;
+    reload_state_if_active(&state, &db_path);
+
+    info!(
+        "{} restored {} from {}",
+        actor.audit_name(),
+        cohort.db,
+        file_name
+    );
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "restored_from": file_name,
+        "safety_backup": safety_backup
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string())),
+    })))
+}
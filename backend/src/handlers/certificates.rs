@@ -0,0 +1,72 @@
+use crate::utils::certificates::{
+    check_eligibility, find_certificate, issue_certificates, render_certificate_svg,
+};
+use crate::utils::cohort::cohort_config;
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use std::sync::Mutex;
+
+#[get("/certificates/{cohort}/eligibility")]
+pub async fn get_certificate_eligibility(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+
+    let students = check_eligibility(&cohort.db_path(), &cohort.certificate)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "rules": cohort.certificate,
+        "eligible": students.iter().filter(|s| s.eligible).collect::<Vec<_>>(),
+        "ineligible": students.iter().filter(|s| !s.eligible).collect::<Vec<_>>(),
+    })))
+}
+
+// Issues certificates to every eligible student that doesn't have one yet
+#[post("/certificates/{cohort}/issue")]
+pub async fn issue_cohort_certificates(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+
+    let certificates = issue_certificates(&cohort.db_path(), &cohort.name, &cohort.certificate)?;
+    info!(
+        "{} issued certificates for {} ({} total)",
+        actor.audit_name(),
+        cohort.name,
+        certificates.len()
+    );
+    Ok(HttpResponse::Ok().json(certificates))
+}
+
+// Public: anyone holding a certificate code can check it
+#[get("/certificates/verify/{code}")]
+pub async fn verify_certificate(code: web::Path<String>) -> Result<HttpResponse, actix_web::Error> {
+    match find_certificate(&code).map_err(ErrorInternalServerError)? {
+        Some(certificate) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": true,
+            "certificate": certificate,
+        }))),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "valid": false }))),
+    }
+}
+
+#[get("/certificates/verify/{code}/svg")]
+pub async fn download_certificate(
+    code: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let certificate = find_certificate(&code)
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("Unknown certificate"))?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .body(render_certificate_svg(&certificate)))
+}
@@ -0,0 +1,51 @@
+use crate::database::classroom_sync::read_sync_status;
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::classroom::ClassroomClient;
+use crate::utils::classroom_sync::sync_cohort;
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::session::{SessionStore, require_ta};
+use crate::utils::types::Table;
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use std::sync::Mutex;
+
+#[get("/classroom/{cohort}/sync")]
+pub async fn get_sync_status(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    Ok(HttpResponse::Ok().json(read_sync_status(&db_path)?))
+}
+
+// Syncs the cohort's exercise flags with GitHub Classroom now, skipping the grade cache
+#[post("/classroom/{cohort}/sync")]
+pub async fn sync_classroom_now(
+    path: web::Path<String>,
+    state: web::Data<Mutex<Table>>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort = cohort_config(&path)?;
+    let report = sync_cohort(
+        ClassroomClient::shared(),
+        &cohort,
+        &actor.audit_name(),
+        true,
+    )
+    .await;
+    if !report.changes.is_empty() {
+        reload_state_if_active(&state, &cohort.db_path());
+    }
+    info!(
+        "{} synced {} with Classroom: {} fields updated, {} errors",
+        actor.audit_name(),
+        cohort.name,
+        report.changes.len(),
+        report.errors.len()
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
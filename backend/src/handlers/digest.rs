@@ -0,0 +1,44 @@
+use crate::utils::cohort::cohort_config;
+use crate::utils::digest::{build_digest, classroom_submissions};
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::ErrorNotFound;
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct DigestQuery {
+    // "text" for the plain-text version that gets emailed
+    pub format: Option<String>,
+}
+
+#[get("/digest/{cohort}/{week}/{ta}")]
+pub async fn get_ta_digest(
+    path: web::Path<(String, i32, String)>,
+    query: web::Query<DigestQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let (cohort, week, ta) = path.into_inner();
+    let cohort = cohort_config(&cohort)?;
+    let submissions = if week > 1 {
+        classroom_submissions(week - 1).await
+    } else {
+        Ok(HashSet::new())
+    };
+    let digest = build_digest(&cohort.db_path(), &cohort.name, week, &ta, &submissions)?;
+    if digest.is_empty() {
+        return Err(ErrorNotFound(format!(
+            "{} has no groups in week {}",
+            ta, week
+        )));
+    }
+    if query.format.as_deref() == Some("text") {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(digest.to_text()));
+    }
+    Ok(HttpResponse::Ok().json(digest))
+}
@@ -0,0 +1,83 @@
+use crate::database::enrollment::{
+    EnrollmentError, EnrollmentStatus, current_enrollments, enrollment_history, enrollment_stats,
+    set_enrollment_status,
+};
+use crate::utils::cohort::cohort_db;
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, put, web};
+use serde::Deserialize;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct StatusChange {
+    pub status: String,
+    pub reason: Option<String>,
+}
+
+pub(crate) fn enrollment_error(e: EnrollmentError) -> actix_web::Error {
+    match e {
+        EnrollmentError::UnknownStatus(_) => ErrorBadRequest(e),
+        EnrollmentError::InvalidTransition(..) => ErrorConflict(e),
+        EnrollmentError::UnknownParticipant(_) => ErrorNotFound(e),
+        EnrollmentError::App(e) => ErrorInternalServerError(e),
+    }
+}
+
+// Current status of every participant of the cohort
+#[get("/enrollment/{cohort}")]
+pub async fn get_enrollments(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let enrollments = current_enrollments(&cohort_db(&path)?).map_err(enrollment_error)?;
+    Ok(HttpResponse::Ok().json(enrollments.into_values().collect::<Vec<_>>()))
+}
+
+#[get("/enrollment/{cohort}/stats")]
+pub async fn get_enrollment_stats(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let stats = enrollment_stats(&cohort_db(&path)?).map_err(enrollment_error)?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[get("/enrollment/{cohort}/{email}/history")]
+pub async fn get_enrollment_history(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let (cohort_name, email) = path.into_inner();
+    let history =
+        enrollment_history(&cohort_db(&cohort_name)?, &email).map_err(enrollment_error)?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[put("/enrollment/{cohort}/{email}")]
+pub async fn set_enrollment(
+    path: web::Path<(String, String)>,
+    body: web::Json<StatusChange>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, email) = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+    let status: EnrollmentStatus = body.status.parse().map_err(enrollment_error)?;
+    let change = set_enrollment_status(
+        &db_path,
+        &email,
+        status,
+        body.reason.as_deref(),
+        &actor.audit_name(),
+    )
+    .map_err(enrollment_error)?;
+    Ok(HttpResponse::Ok().json(change))
+}
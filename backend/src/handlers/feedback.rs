@@ -0,0 +1,183 @@
+use crate::database::operations::{count_enrolled_students, read_all_responses};
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::feedback_import::{FeedbackImportError, import_feedback};
+use crate::utils::feedback_links::{link_feedback_responses, students_without_feedback};
+use crate::utils::session::{SessionStore, require_ta};
+use crate::utils::types::FeedbackResponse;
+use actix_web::error::{ErrorBadGateway, ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+type Answer = fn(&FeedbackResponse) -> &str;
+
+// The "Which component of cohort did you find of help?" grid of the feedback form
+const COMPONENT_QUESTIONS: &[(&str, Answer)] = &[
+    ("session_instructions", |r| &r.session_instructions),
+    ("study_material", |r| &r.study_material),
+    ("group_discussions", |r| &r.group_discussions),
+    ("lounge_discussions", |r| &r.lounge_discussions),
+    ("deputy", |r| &r.deputy),
+    ("teaching_assistants", |r| &r.teaching_assistants),
+    ("bitshala_clubs", |r| &r.bitshala_clubs),
+    ("bitdev_meetups", |r| &r.bitdev_meetups),
+    ("bitspace", |r| &r.bitspace),
+    ("fellowships", |r| &r.fellowships),
+];
+
+// Testimonial answers that only decline to give one
+const NOT_A_TESTIMONIAL: &[&str] = &["", "-", ".", "na", "n/a", "no", "none", "nil", "nothing"];
+
+#[derive(Debug, Serialize)]
+pub struct QuestionSummary {
+    pub answered: usize,
+    // Number of responses per answer, e.g. {"Very helpful": 12, "Helpful": 5}
+    pub distribution: BTreeMap<String, usize>,
+    // Mean of the answers when the scale is numeric (e.g. 1-5)
+    pub average: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Testimonial {
+    pub name: String,
+    pub discord_name: String,
+    pub submitted_at: String,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedbackSummary {
+    pub responses: usize,
+    pub enrolled: usize,
+    // `responses / enrolled`, if anyone is enrolled
+    pub response_rate: Option<f64>,
+    pub components: BTreeMap<String, QuestionSummary>,
+    // Testimonials to consider for the website; the form asks for them for that purpose
+    pub testimonials: Vec<Testimonial>,
+}
+
+pub fn summarize_feedback(responses: &[FeedbackResponse], enrolled: usize) -> FeedbackSummary {
+    let components = COMPONENT_QUESTIONS
+        .iter()
+        .map(|(question, answer)| {
+            let answers: Vec<&str> = responses
+                .iter()
+                .map(|r| answer(r).trim())
+                .filter(|a| !a.is_empty())
+                .collect();
+            let mut distribution = BTreeMap::new();
+            for a in &answers {
+                *distribution.entry(a.to_string()).or_insert(0) += 1;
+            }
+            let numeric: Option<Vec<f64>> = answers.iter().map(|a| a.parse().ok()).collect();
+            let average = numeric
+                .filter(|values| !values.is_empty())
+                .map(|values| values.iter().sum::<f64>() / values.len() as f64);
+            (
+                question.to_string(),
+                QuestionSummary {
+                    answered: answers.len(),
+                    distribution,
+                    average,
+                },
+            )
+        })
+        .collect();
+
+    let testimonials = responses
+        .iter()
+        .filter(|r| !NOT_A_TESTIMONIAL.contains(&r.testimonial.trim().to_lowercase().as_str()))
+        .map(|r| Testimonial {
+            name: r.name_on_certificate.clone(),
+            discord_name: r.discord_name.clone(),
+            submitted_at: r.timestamp.clone(),
+            text: r.testimonial.trim().to_string(),
+        })
+        .collect();
+
+    FeedbackSummary {
+        responses: responses.len(),
+        enrolled,
+        response_rate: (enrolled > 0).then(|| responses.len() as f64 / enrolled as f64),
+        components,
+        testimonials,
+    }
+}
+
+#[get("/feedback/{cohort}/summary")]
+pub async fn get_feedback_summary(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+    let db_path = cohort.db_path();
+
+    let responses = read_all_responses(&db_path, &cohort.name).map_err(ErrorInternalServerError)?;
+    let enrolled = count_enrolled_students(&db_path).map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(summarize_feedback(&responses, enrolled)))
+}
+
+// Pulls the cohort's feedback form responses now instead of waiting for the schedule
+#[post("/feedback/{cohort}/import")]
+pub async fn import_cohort_feedback(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+
+    // The download uses a blocking client, so keep it off the async workers
+    let report = web::block(move || import_feedback(&cohort))
+        .await?
+        .map_err(|e| match e {
+            FeedbackImportError::NoSource(_) => ErrorBadRequest(e),
+            FeedbackImportError::Http(_) | FeedbackImportError::Empty => ErrorBadGateway(e),
+            _ => ErrorInternalServerError(e),
+        })?;
+
+    info!(
+        "{} imported {} feedback responses for {}",
+        actor.audit_name(),
+        report.imported,
+        cohort_name
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Re-runs the matching of responses to students, e.g. after the roster changed
+#[post("/feedback/{cohort}/link")]
+pub async fn link_cohort_feedback(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let report = link_feedback_responses(&db_path).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Enrolled students who haven't submitted the feedback form yet
+#[get("/feedback/{cohort}/missing")]
+pub async fn get_missing_feedback(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort_name = path.into_inner();
+    let db_path = cohort_db(&cohort_name)?;
+
+    let missing = students_without_feedback(&db_path).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(missing))
+}
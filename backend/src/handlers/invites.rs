@@ -0,0 +1,43 @@
+use crate::database::outbox::{list_messages, retry_message};
+use crate::utils::bot_invites::BOT_INVITE;
+use crate::utils::cohort::cohort_db;
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::{ErrorConflict, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// Latest bot invite of every registered participant with its delivery status
+#[get("/invites/{cohort}")]
+pub async fn get_invite_status(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let mut latest = BTreeMap::new();
+    // Newest first, so the first message seen per participant is the latest one
+    for message in list_messages(&cohort_db(&path)?, BOT_INVITE)? {
+        latest
+            .entry(message.email.to_lowercase())
+            .or_insert(message);
+    }
+    Ok(HttpResponse::Ok().json(latest.into_values().collect::<Vec<_>>()))
+}
+
+// Queues an invite that was given up on for another round of attempts
+#[post("/invites/{cohort}/{id}/retry")]
+pub async fn retry_invite(
+    path: web::Path<(String, i64)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let (cohort_name, id) = path.into_inner();
+    let message = retry_message(&cohort_db(&cohort_name)?, BOT_INVITE, id)?
+        .ok_or_else(|| ErrorNotFound("No such invite"))?;
+    if message.status != "pending" {
+        return Err(ErrorConflict("Only failed invites can be retried"));
+    }
+    Ok(HttpResponse::Ok().json(message))
+}
@@ -0,0 +1,92 @@
+use crate::database::operations::read_from_db;
+use crate::handlers::students::get_background_data;
+use crate::utils::session::{SessionStore, require_participant};
+use crate::utils::types::RowData;
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct ExerciseStatus {
+    week: i32,
+    submitted: Option<String>,
+    test_passing: Option<String>,
+    good_documentation: Option<String>,
+    good_structure: Option<String>,
+}
+
+// The logged-in participant's rows of their cohort, oldest week first
+fn own_rows(
+    req: &HttpRequest,
+    sessions: &Mutex<SessionStore>,
+) -> Result<(String, PathBuf, Vec<RowData>), actix_web::Error> {
+    let (email, db_path) = require_participant(req, sessions)?;
+    let mut rows: Vec<RowData> = read_from_db(&db_path)
+        .map_err(ErrorInternalServerError)?
+        .rows
+        .into_iter()
+        .filter(|row| row.mail.trim().eq_ignore_ascii_case(email.trim()))
+        .collect();
+    rows.sort_by_key(|row| row.week);
+    Ok((email, db_path, rows))
+}
+
+#[get("/me")]
+pub async fn get_me(
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (email, db_path, rows) = own_rows(&req, &sessions)?;
+    let latest = rows
+        .last()
+        .ok_or_else(|| ErrorNotFound("Not on the cohort roster"))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "name": latest.name,
+        "email": email,
+        "cohort": db_path,
+        "group": latest.group_id,
+        "ta": latest.ta,
+        "week": latest.week,
+    })))
+}
+
+#[get("/me/weekly")]
+pub async fn get_my_weekly_data(
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (_, _, rows) = own_rows(&req, &sessions)?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[get("/me/exercises")]
+pub async fn get_my_exercises(
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (_, _, rows) = own_rows(&req, &sessions)?;
+    let exercises: Vec<ExerciseStatus> = rows
+        .into_iter()
+        .filter(|row| row.week > 0)
+        .map(|row| ExerciseStatus {
+            week: row.week,
+            submitted: row.exercise_submitted,
+            test_passing: row.exercise_test_passing,
+            good_documentation: row.exercise_good_documentation,
+            good_structure: row.exercise_good_structure,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(exercises))
+}
+
+#[get("/me/background")]
+pub async fn get_my_background(
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (email, db_path) = require_participant(&req, &sessions)?;
+    Ok(HttpResponse::Ok().json(get_background_data(&db_path, &email)))
+}
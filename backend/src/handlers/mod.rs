@@ -1,3 +1,18 @@
+pub mod appeals;
+pub mod audit;
 pub mod auth;
+pub mod backup;
+pub mod certificates;
+pub mod classroom;
+pub mod digest;
+pub mod enrollment;
+pub mod feedback;
+pub mod invites;
+pub mod me;
+pub mod notes;
+pub mod notifications;
+pub mod reviews;
 pub mod students;
 pub mod universal;
+pub mod verification;
+pub mod waitlist;
@@ -0,0 +1,80 @@
+use crate::database::notes::{add_note, read_notes, read_tags, set_tags};
+use crate::utils::cohort::cohort_db;
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{HttpRequest, HttpResponse, get, post, put, web};
+use serde::Deserialize;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct NewNote {
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagList {
+    pub tags: Vec<String>,
+}
+
+#[get("/notes/{cohort}/{student}")]
+pub async fn get_student_notes(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let (cohort_name, student) = path.into_inner();
+    let notes = read_notes(&cohort_db(&cohort_name)?, &student)?;
+    Ok(HttpResponse::Ok().json(notes))
+}
+
+#[post("/notes/{cohort}/{student}")]
+pub async fn add_student_note(
+    path: web::Path<(String, String)>,
+    body: web::Json<NewNote>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, student) = path.into_inner();
+    if body.text.trim().is_empty() {
+        return Err(ErrorBadRequest("Note is empty"));
+    }
+    let note = add_note(
+        &cohort_db(&cohort_name)?,
+        &student,
+        &actor.audit_name(),
+        body.text.trim(),
+    )?;
+    Ok(HttpResponse::Created().json(note))
+}
+
+// Every tagged student of the cohort with their tags
+#[get("/tags/{cohort}")]
+pub async fn get_cohort_tags(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let tags = read_tags(&cohort_db(&path)?).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+#[put("/tags/{cohort}/{student}")]
+pub async fn set_student_tags(
+    path: web::Path<(String, String)>,
+    body: web::Json<TagList>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, student) = path.into_inner();
+    let tags = set_tags(
+        &cohort_db(&cohort_name)?,
+        &student,
+        &body.tags,
+        &actor.audit_name(),
+    )?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "student": student, "tags": tags })))
+}
@@ -0,0 +1,95 @@
+use crate::database::notifications::{publish_week, queue_weekly_reminders};
+use crate::database::outbox::list_messages;
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::digest::{classroom_submissions, digest_config, queue_ta_digests};
+use crate::utils::notifications::{NOTIFICATION, NotificationConfig};
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::error::ErrorBadRequest;
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::{info, warn};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+// Notifies each student of the week's groups of their group and TA, and sends each TA their
+// digest. Publishing again only notifies students whose group or TA changed.
+#[post("/weekly_data/{cohort}/{week}/publish")]
+pub async fn publish_weekly_groups(
+    path: web::Path<(String, i32)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort, week) = path.into_inner();
+    let cohort = cohort_config(&cohort)?;
+    let report = publish_week(
+        &cohort.db_path(),
+        &cohort.name,
+        week,
+        &NotificationConfig::from_env(),
+    )?;
+    if report.notified.is_empty() && report.unchanged == 0 {
+        return Err(ErrorBadRequest(format!("Week {} has no groups yet", week)));
+    }
+    info!(
+        "{} published week {} of {}: {} students notified",
+        actor.audit_name(),
+        week,
+        cohort.name,
+        report.notified.len()
+    );
+
+    let digests = digest_config();
+    if !report.notified.is_empty() && !digests.channels.is_empty() {
+        let submissions = if week > 1 {
+            classroom_submissions(week - 1).await
+        } else {
+            Ok(HashSet::new())
+        };
+        match queue_ta_digests(
+            &cohort.db_path(),
+            &cohort.name,
+            week,
+            &submissions,
+            &digests,
+        ) {
+            Ok(tas) => info!("Queued week {} digests for {}", week, tas.join(", ")),
+            Err(e) => warn!("Couldn't queue week {} digests: {}", week, e),
+        }
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Queues this week's reminders now instead of waiting for the schedule
+#[post("/notifications/{cohort}/reminders")]
+pub async fn send_reminders(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort = cohort_config(&path)?;
+    let report = queue_weekly_reminders(
+        &cohort.db_path(),
+        &cohort.name,
+        &NotificationConfig::from_env(),
+    )?;
+    info!(
+        "{} queued week {} reminders for {} students of {}",
+        actor.audit_name(),
+        report.week,
+        report.notified.len(),
+        cohort.name
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/notifications/{cohort}")]
+pub async fn get_notifications(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    Ok(HttpResponse::Ok().json(list_messages(&db_path, NOTIFICATION)?))
+}
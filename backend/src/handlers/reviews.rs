@@ -0,0 +1,122 @@
+use crate::database::reviews::{
+    Decision, ReviewError, decide_applications, review_queue, submit_review,
+};
+use crate::handlers::enrollment::enrollment_error;
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::session::{SessionStore, require_ta};
+use crate::utils::types::Table;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    // Include applications that were already accepted or rejected
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Deserialize)]
+pub struct NewReview {
+    pub scores: BTreeMap<String, u32>,
+    pub comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkDecision {
+    pub emails: Vec<String>,
+    pub decision: Decision,
+    pub comment: Option<String>,
+}
+
+fn review_error(e: ReviewError) -> actix_web::Error {
+    match e {
+        ReviewError::UnknownApplicant(_) => ErrorNotFound(e),
+        ReviewError::UnknownCriterion(_)
+        | ReviewError::ScoreOutOfRange(..)
+        | ReviewError::MissingScore(_) => ErrorBadRequest(e),
+        ReviewError::Enrollment(e) => enrollment_error(e),
+        ReviewError::App(e) => ErrorInternalServerError(e),
+    }
+}
+
+#[get("/applications/{cohort}/criteria")]
+pub async fn get_review_criteria(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    Ok(HttpResponse::Ok().json(cohort_config(&path)?.review_criteria))
+}
+
+#[get("/applications/{cohort}")]
+pub async fn get_review_queue(
+    path: web::Path<String>,
+    query: web::Query<QueueQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let applications = review_queue(&cohort_db(&path)?, query.all).map_err(review_error)?;
+    Ok(HttpResponse::Ok().json(applications))
+}
+
+#[post("/applications/{cohort}/{email}/review")]
+pub async fn review_application(
+    path: web::Path<(String, String)>,
+    body: web::Json<NewReview>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let (cohort_name, email) = path.into_inner();
+    let cohort = cohort_config(&cohort_name)?;
+    let review = submit_review(
+        &cohort.db_path(),
+        &email,
+        &actor.audit_name(),
+        &body.scores,
+        body.comment.as_deref(),
+        &cohort.review_criteria,
+    )
+    .map_err(review_error)?;
+    Ok(HttpResponse::Ok().json(review))
+}
+
+// Accepts or rejects several applications at once
+#[post("/applications/{cohort}/decide")]
+pub async fn decide_cohort_applications(
+    path: web::Path<String>,
+    body: web::Json<BulkDecision>,
+    state: web::Data<Mutex<Table>>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort = cohort_config(&path)?;
+    let db_path = cohort.db_path();
+    let report = decide_applications(
+        &db_path,
+        &body.emails,
+        body.decision,
+        body.comment.as_deref(),
+        &actor.audit_name(),
+    )
+    .map_err(review_error)?;
+    info!(
+        "{} accepted {} and rejected {} applications for {}",
+        actor.audit_name(),
+        report.accepted.len(),
+        report.rejected.len(),
+        cohort.name
+    );
+    if !report.accepted.is_empty() {
+        reload_state_if_active(&state, &db_path);
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
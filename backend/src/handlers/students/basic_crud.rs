@@ -1,16 +1,63 @@
-use crate::database::operations::{read_all_responses, read_from_db, write_to_db};
+use crate::database::notes::search_students;
+use crate::database::operations::{delete_from_db, read_all_responses, read_from_db, write_to_db};
+use crate::utils::session::{SessionStore, require_ta};
 use crate::utils::types::RowData;
-use actix_web::{HttpResponse, Responder, delete, get, post, put, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, post, put, web};
 use log::info;
+use serde::Deserialize;
 use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Optional filters of `GET /students/{cohort_name}`; both need a TA session
+#[derive(Deserialize)]
+pub struct StudentSearch {
+    // Comma separated; a student needs all of them
+    pub tag: Option<String>,
+    // Matches the student's name, tags or TA notes
+    pub q: Option<String>,
+}
 
 #[get("/students/{cohort_name}")]
-pub async fn get_students(path: web::Path<String>) -> impl Responder {
+pub async fn get_students(
+    path: web::Path<String>,
+    search: web::Query<StudentSearch>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> impl Responder {
     let cohort_name = path.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
+
+    let matching = if search.tag.is_some() || search.q.is_some() {
+        if require_ta(&req, &sessions).is_err() {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "TA authentication required to search notes and tags"
+            }));
+        }
+        let tags: Vec<String> = search
+            .tag
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::to_string)
+            .collect();
+        match search_students(&db_path, &tags, search.q.as_deref()) {
+            Ok(names) => Some(names),
+            Err(e) => {
+                info!("Error searching students: {:?}", e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Failed to search students"
+                }));
+            }
+        }
+    } else {
+        None
+    };
 
     match read_from_db(&db_path) {
-        Ok(table) => {
+        Ok(mut table) => {
+            if let Some(names) = matching {
+                table.rows.retain(|row| names.contains(&row.name));
+            }
             info!("Successfully fetched {} students", table.rows.len());
             HttpResponse::Ok().json(&table.rows)
         }
@@ -27,15 +74,25 @@ pub async fn get_students(path: web::Path<String>) -> impl Responder {
 pub async fn add_student(
     path: web::Path<String>,
     student_data: web::Json<RowData>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
 ) -> impl Responder {
     let cohort_name = path.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
+    let actor = match require_ta(&req, &sessions) {
+        Ok(actor) => actor,
+        Err(_) => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "TA authentication required"
+            }));
+        }
+    };
 
     match read_from_db(&db_path) {
         Ok(mut table) => {
             table.rows.push(student_data.into_inner());
 
-            match write_to_db(&db_path, &table) {
+            match write_to_db(&db_path, &table, &actor.audit_name()) {
                 Ok(_) => {
                     info!("Successfully added new student");
                     HttpResponse::Ok().json(serde_json::json!({
@@ -63,16 +120,26 @@ pub async fn add_student(
 pub async fn update_student(
     path: web::Path<(String, String)>,
     student_data: web::Json<RowData>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
 ) -> impl Responder {
     let (cohort_name, student_name) = path.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
+    let actor = match require_ta(&req, &sessions) {
+        Ok(actor) => actor,
+        Err(_) => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "TA authentication required"
+            }));
+        }
+    };
 
     match read_from_db(&db_path) {
         Ok(mut table) => {
             if let Some(student) = table.rows.iter_mut().find(|s| s.name == student_name) {
                 *student = student_data.into_inner();
 
-                match write_to_db(&db_path, &table) {
+                match write_to_db(&db_path, &table, &actor.audit_name()) {
                     Ok(_) => {
                         info!("Successfully updated student: {}", student_name);
                         HttpResponse::Ok().json(serde_json::json!({
@@ -102,40 +169,36 @@ pub async fn update_student(
 }
 
 #[delete("/students/{cohort_name}/{name}")]
-pub async fn remove_student(path: web::Path<(String, String)>) -> impl Responder {
+pub async fn remove_student(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> impl Responder {
     let (cohort_name, student_name) = path.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
-
-    match read_from_db(&db_path) {
-        Ok(mut table) => {
-            let initial_len = table.rows.len();
-            table.rows.retain(|s| s.name != student_name);
+    let db_path = PathBuf::from(&cohort_name);
+    let actor = match require_ta(&req, &sessions) {
+        Ok(actor) => actor,
+        Err(_) => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "TA authentication required"
+            }));
+        }
+    };
 
-            if table.rows.len() < initial_len {
-                match write_to_db(&db_path, &table) {
-                    Ok(_) => {
-                        info!("Successfully removed student: {}", student_name);
-                        HttpResponse::Ok().json(serde_json::json!({
-                            "message": "Student removed successfully"
-                        }))
-                    }
-                    Err(e) => {
-                        info!("Error removing student: {:?}", e);
-                        HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": "Failed to remove student"
-                        }))
-                    }
-                }
-            } else {
-                HttpResponse::NotFound().json(serde_json::json!({
-                    "error": "Student not found"
-                }))
-            }
+    match delete_from_db(&db_path, &student_name, None, &actor.audit_name()) {
+        Ok(0) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Student not found"
+        })),
+        Ok(_) => {
+            info!("Successfully removed student: {}", student_name);
+            HttpResponse::Ok().json(serde_json::json!({
+                "message": "Student removed successfully"
+            }))
         }
         Err(e) => {
-            info!("Error reading database: {:?}", e);
+            info!("Error removing student: {:?}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to read database"
+                "error": "Failed to remove student"
             }))
         }
     }
@@ -144,7 +207,7 @@ pub async fn remove_student(path: web::Path<(String, String)>) -> impl Responder
 #[get("/feedback/{cohort_name}")]
 pub async fn get_cohort_feedback(cohort_name: web::Path<String>) -> impl Responder {
     let cohort_name = cohort_name.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
 
     match read_all_responses(&db_path, &cohort_name) {
         Ok(responses) => {
@@ -1,15 +1,17 @@
 use crate::database::operations::register_cohort_participant;
 use crate::handlers::students::weekly_data::{get_github_to_name_mapping, get_github_username};
-use crate::utils::classroom::{Assignment, get_submitted_assignments};
+use crate::utils::classroom::{Assignment, week_grades, week_repositories};
+use crate::utils::registration::{RegistrationError, validate_registration};
+use crate::utils::session::{SessionStore, require_participant, require_ta};
 use crate::utils::types::{CohortParticipant, RowData, Table};
-use actix_web::{HttpResponse, Responder, get, post, web};
+use actix_web::error::ErrorUnauthorized;
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post, web};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct BackgroundData {
     pub describe_yourself: String,
     pub background: String,
@@ -20,30 +22,16 @@ pub struct BackgroundData {
     pub book: String,
 }
 
-impl Default for BackgroundData {
-    fn default() -> Self {
-        BackgroundData {
-            describe_yourself: String::new(),
-            background: String::new(),
-            skills: String::new(),
-            location: String::new(),
-            why: String::new(),
-            year: String::new(),
-            book: String::new(),
-        }
-    }
-}
-
+// The participant's application answers, or empty fields if they can't be read
 pub fn get_background_data(path: &PathBuf, email: &str) -> BackgroundData {
     use rusqlite::{Connection, params};
 
-    let conn = Connection::open(path).ok().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT  \"Describe Yourself\" , Background, Skills, Location, Year, Why, Books FROM participants WHERE Email = ?1",
-    ).ok().unwrap();
-
-    let rows = stmt
-        .query_map(params![email], |row| {
+    let read = || -> rusqlite::Result<Option<BackgroundData>> {
+        let conn = Connection::open(path)?;
+        let mut stmt = conn.prepare(
+            "SELECT  \"Describe Yourself\" , Background, Skills, Location, Year, Why, Books FROM participants WHERE Email = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![email], |row| {
             Ok(BackgroundData {
                 describe_yourself: row.get(0)?,
                 background: row.get(1)?,
@@ -53,36 +41,54 @@ pub fn get_background_data(path: &PathBuf, email: &str) -> BackgroundData {
                 why: row.get(5)?,
                 book: row.get(6)?,
             })
-        })
-        .ok()
-        .unwrap();
+        })?;
+        rows.next().transpose()
+    };
 
-    rows.flatten()
-        .next()
-        .unwrap_or_else(BackgroundData::default)
+    read().ok().flatten().unwrap_or_default()
 }
 
 #[get("/students/{week}/{cohort_name}/{student_name}")]
 pub async fn get_student_repo_link(info: web::Path<(i32, String, String)>) -> impl Responder {
     let (week, cohort_name, student_name) = info.into_inner();
-    let assignments = get_submitted_assignments(week).await.unwrap();
-    let submitted: Vec<&Assignment> = assignments.iter().filter(|a| a.is_submitted()).collect();
-
     let db_path = PathBuf::from(format!("{}_cohort.db", cohort_name));
+    let is_student = |login: &String| {
+        get_github_to_name_mapping(&db_path, login).is_some_and(|name| name == student_name)
+    };
+
+    // Students have a repository as soon as they accept the assignment
+    match week_repositories(week).await {
+        Ok(accepted) => {
+            let student_url = accepted
+                .iter()
+                .find(|a| a.students.iter().any(|s| is_student(&s.login)))
+                .map(|a| a.repository.html_url.clone())
+                .unwrap_or_default();
+            return HttpResponse::Ok()
+                .json(serde_json::json!({ "url": student_url, "stale": false }));
+        }
+        Err(e) => warn!(
+            "Falling back to Classroom grades for repository links: {}",
+            e
+        ),
+    }
+
+    let grades = week_grades(week).await;
+    let submitted: Vec<&Assignment> = grades
+        .assignments
+        .iter()
+        .filter(|a| a.is_submitted())
+        .collect();
     let mut student_url = "".to_string();
 
     //for loops conclude to unit type ()
     for assignment in &submitted {
-        if let Some(participant_name) =
-            get_github_to_name_mapping(&db_path, &assignment.github_username)
-        {
-            if participant_name == student_name {
-                student_url = (assignment.student_repository_url).to_string();
-            }
+        if is_student(&assignment.github_username) {
+            student_url = (assignment.student_repository_url).to_string();
         }
     }
-    // Return as JSON object with a "url" field
-    return HttpResponse::Ok().json(serde_json::json!({ "url": student_url }));
+    // Return as JSON object with a "url" field, and "stale" if GitHub couldn't be reached
+    HttpResponse::Ok().json(serde_json::json!({ "url": student_url, "stale": grades.stale }))
 }
 
 #[get("/data/{student_email}")]
@@ -107,40 +113,42 @@ pub async fn get_student_github_username(info: web::Path<(String, String)>) -> i
 
 #[post("/register")]
 pub async fn register_user(data: web::Json<CohortParticipant>) -> impl Responder {
-    let data = data.into_inner();
-    info!("Registering cohort participant: {:?}", data.role);
-
-    let db_path = PathBuf::from(format!("{}.db", data.role.clone()));
-
-    let participant_data = data.clone();
-
-    if let Err(e) = register_cohort_participant(&db_path, data) {
-        warn!("Failed to register cohort participant: {e}");
-        return HttpResponse::InternalServerError()
-            .json(serde_json::json!({ "error": e.to_string() }));
+    let mut data = data.into_inner();
+    info!("Registering cohort participant for {:?}", data.cohort_name);
+
+    // The database comes from the cohort registry, never from client input
+    let cohort = match validate_registration(&mut data) {
+        Ok(cohort) => cohort,
+        Err(RegistrationError::UnknownCohort(name)) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": format!("Unknown cohort {:?}", name) }));
+        }
+        Err(e @ RegistrationError::Closed(_)) => {
+            return HttpResponse::Forbidden().json(serde_json::json!({ "error": e.to_string() }));
+        }
+        Err(RegistrationError::Invalid(problems)) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "errors": problems }));
+        }
+    };
+
+    match register_cohort_participant(&cohort.db_path(), data, cohort.registration.seats) {
+        Ok(registration) => {
+            // New registrations with a seat queue the bot invite, which the invite worker sends
+            info!(
+                "Cohort participant registration {:?}.",
+                registration.outcome
+            );
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "registration": registration.outcome,
+                "waitlist_position": registration.waitlist_position,
+            }))
+        }
+        Err(e) => {
+            warn!("Failed to register cohort participant: {e}");
+            HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() }))
+        }
     }
-
-    info!("Cohort participant registered successfully.");
-
-    // if participant is registered successfully, send data to external API
-
-    let api_data = HashMap::from([
-        ("name", participant_data.name),
-        ("email", participant_data.email),
-        ("role", participant_data.role),
-    ]);
-
-    let client = reqwest::Client::new();
-    client
-        .post("http://localhost:8080/bot/invite")
-        .header("Content-Type", "application/json")
-        .json(&api_data)
-        .send()
-        .await
-        .map_err(|_| HttpResponse::InternalServerError().finish())
-        .unwrap();
-
-    HttpResponse::Ok().json(serde_json::json!({ "status": "success" }))
 }
 
 #[get("/individual_data/{student_name}")]
@@ -167,12 +175,21 @@ pub async fn get_individual_student_data(
     HttpResponse::Ok().json(student_data)
 }
 
+// TAs can look up anyone; participants only their own email
 #[get("/individual_data_email/{student_email}")]
 pub async fn get_individual_student_data_by_mail(
     info: web::Path<String>,
     state: web::Data<Mutex<Table>>,
-) -> impl Responder {
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
     let student_email = info.into_inner();
+    if require_ta(&req, &sessions).is_err() {
+        let (email, _) = require_participant(&req, &sessions)?;
+        if !email.trim().eq_ignore_ascii_case(student_email.trim()) {
+            return Err(ErrorUnauthorized("You can only see your own data"));
+        }
+    }
 
     // Single lock scope for data collection
     let mut student_data: Vec<RowData> = {
@@ -180,7 +197,7 @@ pub async fn get_individual_student_data_by_mail(
         state_table
             .rows
             .iter()
-            .filter(|row| row.mail == student_email)
+            .filter(|row| row.mail.trim().eq_ignore_ascii_case(student_email.trim()))
             .cloned()
             .collect()
     }; // Lock released here
@@ -188,6 +205,5 @@ pub async fn get_individual_student_data_by_mail(
     // Sort by week after releasing the lock
     student_data.sort_by_key(|row| row.week);
 
-    println!("Student data for {}: {:?}", student_email, student_data);
-    HttpResponse::Ok().json(student_data)
+    Ok(HttpResponse::Ok().json(student_data))
 }
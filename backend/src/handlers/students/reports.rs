@@ -1,10 +1,40 @@
+use crate::database::enrollment::inactive_emails;
+use crate::database::operations::read_from_db;
+use crate::utils::cohort::cohort_config;
+use crate::utils::feedback_links::feedback_by_student;
+use crate::utils::session::{SessionStore, require_ta};
 use crate::utils::types::{RowData, Table};
-use actix_web::{HttpResponse, Responder, get, web};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpRequest, HttpResponse, Responder, get, web};
 use log::info;
-use serde::Serialize;
-use std::collections::HashMap;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Mutex;
 
+// Students at or above these scores are reported as high / medium risk
+const HIGH_RISK_SCORE: u32 = 5;
+const MEDIUM_RISK_SCORE: u32 = 3;
+// How many of the latest weeks count towards missing exercise submissions
+const RECENT_WEEKS: usize = 3;
+
+#[derive(Debug, Serialize)]
+pub struct AtRiskStudent {
+    pub name: String,
+    pub email: String,
+    pub ta: Option<String>,
+    pub score: u32,
+    // "high", "medium" or "low"
+    pub level: &'static str,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AtRiskQuery {
+    // Only report students with at least this score (default 1)
+    pub min_score: Option<u32>,
+}
+
 #[derive(Serialize)]
 struct StudentScoreResponse {
     name: String,
@@ -88,7 +118,7 @@ pub async fn get_students_by_total_score(
     }
 
     let mut response: Vec<(RowData, u64, u8)> = student_data.into_values().collect();
-    response.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by total score descending
+    response.sort_by_key(|b| std::cmp::Reverse(b.1)); // Sort by total score descending
 
     let final_response: Vec<StudentScoreResponse> = response
         .into_iter()
@@ -102,3 +132,141 @@ pub async fn get_students_by_total_score(
 
     HttpResponse::Ok().json(final_response)
 }
+
+// Scores every student on recent absences, falling totals, missing exercise submissions and
+// missing feedback. `with_feedback` is `None` when the cohort has no linked feedback yet, in
+// which case feedback isn't held against anyone. Highest scores first; students with nothing
+// to report are left out.
+pub fn at_risk_students(
+    rows: &[RowData],
+    with_feedback: Option<&BTreeSet<String>>,
+) -> Vec<AtRiskStudent> {
+    let latest_week = rows.iter().map(|r| r.week).max().unwrap_or(0);
+    let mut students: BTreeMap<&str, Vec<&RowData>> = BTreeMap::new();
+    for row in rows {
+        students.entry(row.name.as_str()).or_default().push(row);
+    }
+
+    let mut report: Vec<AtRiskStudent> = students
+        .into_iter()
+        .filter_map(|(name, mut weeks)| {
+            weeks.sort_by_key(|r| r.week);
+            let latest = *weeks.last()?;
+            let weekly: Vec<&RowData> = weeks.into_iter().filter(|r| r.week > 0).collect();
+            let mut score = 0;
+            let mut reasons = Vec::new();
+
+            // Absent in the latest weeks, counting weeks with no row as absences
+            let attended: BTreeSet<i32> = weekly
+                .iter()
+                .filter(|r| r.attendance.as_deref() == Some("yes"))
+                .map(|r| r.week)
+                .collect();
+            let absences = (1..=latest_week)
+                .rev()
+                .take_while(|week| !attended.contains(week))
+                .count() as u32;
+            if absences >= 2 {
+                score += absences + 1;
+                reasons.push(format!("absent for the last {} weeks", absences));
+            } else if absences == 1 {
+                score += 1;
+                reasons.push("absent last week".to_string());
+            }
+
+            // Totals falling over the last three attended weeks
+            let totals: Vec<u64> = weekly
+                .iter()
+                .filter(|r| attended.contains(&r.week))
+                .map(|r| r.total.unwrap_or(0))
+                .collect();
+            if let [.., a, b, c] = totals.as_slice()
+                && a > b
+                && b > c
+            {
+                score += 2;
+                reasons.push(format!("totals declining ({} → {} → {})", a, b, c));
+            }
+
+            // Exercises of the cohort's last weeks, counting weeks with no row as not submitted
+            let submitted: BTreeSet<i32> = weekly
+                .iter()
+                .filter(|r| r.exercise_submitted.as_deref() == Some("yes"))
+                .map(|r| r.week)
+                .collect();
+            let recent_weeks: Vec<i32> = (1..=latest_week).rev().take(RECENT_WEEKS).collect();
+            let missing_exercises = recent_weeks
+                .iter()
+                .filter(|week| !submitted.contains(week))
+                .count() as u32;
+            if missing_exercises > 0 {
+                score += missing_exercises;
+                reasons.push(format!(
+                    "{} of the last {} exercises not submitted",
+                    missing_exercises,
+                    recent_weeks.len()
+                ));
+            }
+
+            if with_feedback.is_some_and(|linked| !linked.contains(name)) {
+                score += 1;
+                reasons.push("no feedback submitted".to_string());
+            }
+
+            (score > 0).then(|| AtRiskStudent {
+                name: name.to_string(),
+                email: latest.mail.clone(),
+                ta: latest.ta.clone(),
+                score,
+                level: if score >= HIGH_RISK_SCORE {
+                    "high"
+                } else if score >= MEDIUM_RISK_SCORE {
+                    "medium"
+                } else {
+                    "low"
+                },
+                reasons,
+            })
+        })
+        .collect();
+
+    report.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    report
+}
+
+#[get("/reports/{cohort}/at_risk")]
+pub async fn get_at_risk_students(
+    path: web::Path<String>,
+    query: web::Query<AtRiskQuery>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let cohort = cohort_config(&path)?;
+    let db_path = cohort.db_path();
+
+    // Students who dropped out or deferred aren't at risk of anything any more
+    let inactive = inactive_emails(&db_path).map_err(ErrorInternalServerError)?;
+    let mut table = read_from_db(&db_path)?;
+    table
+        .rows
+        .retain(|row| !inactive.contains(&row.mail.trim().to_lowercase()));
+    let conn = Connection::open(&db_path).map_err(ErrorInternalServerError)?;
+    let linked: BTreeSet<String> = feedback_by_student(&conn)
+        .map_err(ErrorInternalServerError)?
+        .into_keys()
+        .collect();
+
+    let min_score = query.min_score.unwrap_or(1);
+    let report: Vec<AtRiskStudent> =
+        at_risk_students(&table.rows, (!linked.is_empty()).then_some(&linked))
+            .into_iter()
+            .filter(|s| s.score >= min_score)
+            .collect();
+    info!(
+        "At-risk report for {}: {} students",
+        cohort.name,
+        report.len()
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
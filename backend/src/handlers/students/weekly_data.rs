@@ -1,11 +1,11 @@
-use crate::database::operations::write_to_db;
+use crate::database::enrollment::inactive_emails;
+use crate::database::operations::{delete_from_db, write_rows};
 use crate::handlers::auth::TA;
-use crate::utils::classroom::{Assignment, get_submitted_assignments};
+use crate::utils::session::{Actor, SessionStore, require_ta};
 use crate::utils::types::{RowData, Table};
-use actix_web::{HttpResponse, Responder, Result, get, post, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, Result, get, post, web};
 use log::{info, warn};
 use rusqlite::Connection;
-use std::collections::HashMap;
 use std::path::PathBuf; // Add this import
 // Helper function for GitHub to name mapping
 pub fn get_github_to_name_mapping(path: &PathBuf, github_username: &String) -> Option<String> {
@@ -17,7 +17,7 @@ pub fn get_github_to_name_mapping(path: &PathBuf, github_username: &String) -> O
     let pattern = format!("%{}", github_username);
     let mut rows = stmt
         .query_map([&pattern], |row| {
-            Ok(row.get::<_, String>(0)?) // Name
+            row.get::<_, String>(0) // Name
         })
         .ok()?;
     if let Some(Ok(name)) = rows.next() {
@@ -38,7 +38,7 @@ pub fn get_github_username(path: &PathBuf, name: &String) -> String {
     let pattern = format!("%{}", name);
     let mut result = stmt
         .query_map([&pattern], |row| {
-            Ok(row.get::<_, String>(0)?) // Name
+            row.get::<_, String>(0) // Name
         })
         .ok()
         .unwrap();
@@ -53,10 +53,10 @@ pub fn get_github_username(path: &PathBuf, name: &String) -> String {
 pub async fn get_weekly_data_or_common(
     info: web::Path<(String, i32)>,
     state: web::Data<std::sync::Mutex<Table>>,
-    req: actix_web::HttpRequest,
+    _req: actix_web::HttpRequest,
 ) -> impl Responder {
     let (cohort_name, week) = info.into_inner();
-    info!("Getting and updating weekly data for week: {}", week);
+    info!("Getting weekly data for week: {}", week);
 
     // Scope 1: Handle week == 0 case
     {
@@ -74,28 +74,23 @@ pub async fn get_weekly_data_or_common(
 
     // Handle week >= 1 case
     if week >= 1 {
-        // Step 1: Do all async work FIRST (without holding any locks)
-        let assignments = get_submitted_assignments(week).await.unwrap();
-        let submitted: Vec<&Assignment> = assignments.iter().filter(|a| a.is_submitted()).collect();
-
-        let mut name_to_assignment: HashMap<String, &Assignment> = HashMap::new();
-        let db_path = PathBuf::from(format!("{}", cohort_name));
-
-        for assignment in &submitted {
-            if let Some(participant_name) =
-                get_github_to_name_mapping(&db_path, &assignment.github_username)
-            {
-                name_to_assignment.insert(participant_name, assignment);
-            }
-        }
+        // Exercise flags come from the Classroom sync; this only lays out the week
+        let db_path = PathBuf::from(&cohort_name);
 
-        // Step 2: Get previous week data (short lock scope)
+        // Dropped and deferred students keep their history but aren't grouped any more
+        let inactive = inactive_emails(&db_path).unwrap_or_else(|e| {
+            warn!("Couldn't read enrollment statuses: {}", e);
+            Default::default()
+        });
+
+        // Step 1: Get previous week data (short lock scope)
         let prev_week_rows = {
             let state_table = state.lock().unwrap();
             let mut prev_week_rows: Vec<RowData> = state_table
                 .rows
                 .iter()
                 .filter(|row| row.week == week - 1)
+                .filter(|row| !inactive.contains(&row.mail.trim().to_lowercase()))
                 .cloned()
                 .collect();
 
@@ -114,29 +109,26 @@ pub async fn get_weekly_data_or_common(
 
             prev_week_rows
         }; // Lock released here
-        println!("this cohort name is {}", cohort_name);
-        let tas: Vec<TA>;
-        if cohort_name == "pb_cohort.db" {
-            tas = TA::all_variants()
+        let tas: Vec<TA> = if cohort_name == "pb_cohort.db" {
+            TA::all_variants()
                 .iter()
                 .cloned()
                 .filter(|ta| *ta != TA::Setu && *ta != TA::Delcin && *ta != TA::Raj)
-                .collect();
+                .collect()
         } else {
-            tas = TA::all_variants()
+            TA::all_variants()
                 .iter()
                 .cloned()
                 .filter(|ta| *ta != TA::Setu)
-                .collect();
-        }
-        // Step 3: Process data (no locks needed)
+                .collect()
+        };
+        // Step 2: Process data (no locks needed)
 
         let mut result_rows: Vec<RowData> = Vec::new();
         let mut group_id: isize = -1;
-        let mut data_changed = false;
 
-        // Process each row and prepare updates
-        let mut rows_to_update: Vec<RowData> = Vec::new();
+        // Rows of students who don't have the week yet
+        let mut new_rows: Vec<RowData> = Vec::new();
 
         for (index, mut row) in prev_week_rows.into_iter().enumerate() {
             if row.attendance.as_deref() == Some("no") {
@@ -184,83 +176,45 @@ pub async fn get_weekly_data_or_common(
                     .cloned()
             }; // Lock released here
 
+            // A student's week is laid out once; later changes come from TAs and the sync
             if let Some(existing_row) = existing_row {
-                row.attendance = existing_row.attendance.clone();
-                row.fa = existing_row.fa;
-                row.fb = existing_row.fb;
-                row.fc = existing_row.fc;
-                row.fd = existing_row.fd;
-                row.bonus_attempt = existing_row.bonus_attempt;
-                row.bonus_answer_quality = existing_row.bonus_answer_quality;
-                row.bonus_follow_up = existing_row.bonus_follow_up;
-                row.exercise_submitted = existing_row.exercise_submitted.clone();
-                row.exercise_test_passing = existing_row.exercise_test_passing.clone();
-                row.exercise_good_documentation = existing_row.exercise_good_documentation.clone();
-                row.exercise_good_structure = existing_row.exercise_good_structure.clone();
-                row.total = existing_row.total;
-            } else {
-                data_changed = true;
-                row.attendance = Some("no".to_string());
-                row.fa = Some(0);
-                row.fb = Some(0);
-                row.fc = Some(0);
-                row.fd = Some(0);
-                row.bonus_attempt = Some(0);
-                row.bonus_answer_quality = Some(0);
-                row.bonus_follow_up = Some(0);
-                row.exercise_submitted = Some("no".to_string());
-                row.exercise_test_passing = Some("no".to_string());
-                row.exercise_good_documentation = Some("no".to_string());
-                row.exercise_good_structure = Some("no".to_string());
-                row.total = Some(0);
-            }
-
-            // Check if assignment data changed
-            if let Some(matching_assignment) = name_to_assignment.get(&row.name) {
-                println!(
-                    "Found matching assignment for {} in week {}: {:#?}",
-                    row.name, week, matching_assignment
-                );
-                if matching_assignment.get_week_pattern() == Some(week as u32) {
-                    let new_exercise_submitted = Some("yes".to_string());
-                    let new_exercise_test_passing =
-                        Some(if matching_assignment.points_awarded == "100" {
-                            "yes".to_string()
-                        } else {
-                            "no".to_string()
-                        });
-
-                    if row.exercise_submitted != new_exercise_submitted
-                        || row.exercise_test_passing != new_exercise_test_passing
-                    {
-                        data_changed = true;
-                        row.exercise_submitted = new_exercise_submitted;
-                        row.exercise_test_passing = new_exercise_test_passing;
-                        println!("Data has changed for {} in week {}", row.name, week);
-                    }
-                }
+                result_rows.push(existing_row);
+                continue;
             }
-
-            rows_to_update.push(row.clone());
+            row.attendance = Some("no".to_string());
+            row.fa = Some(0);
+            row.fb = Some(0);
+            row.fc = Some(0);
+            row.fd = Some(0);
+            row.bonus_attempt = Some(0);
+            row.bonus_answer_quality = Some(0);
+            row.bonus_follow_up = Some(0);
+            row.exercise_submitted = Some("no".to_string());
+            row.exercise_test_passing = Some("no".to_string());
+            row.exercise_good_documentation = Some("no".to_string());
+            row.exercise_good_structure = Some("no".to_string());
+            row.total = Some(0);
+
+            new_rows.push(row.clone());
             result_rows.push(row);
         }
 
-        // Step 4: Batch update all changes (single lock scope)
-        {
+        // Step 3: Save the new rows once, so the sync, reports and `/me` see the week too
+        if !new_rows.is_empty() {
             let mut state_table = state.lock().unwrap();
-
-            for row in &rows_to_update {
-                state_table.insert_or_update(row).unwrap();
+            if let Err(e) = write_rows(
+                &db_path,
+                &new_rows,
+                &Actor::System("weekly-data".to_string()).audit_name(),
+            ) {
+                warn!("Couldn't save week {}: {}", week, e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "message": "Failed to save the new week"
+                }));
             }
-
-            if data_changed {
-                info!("Data changed - writing to database for week {}", week);
-                write_to_db(&PathBuf::from(format!("{}", cohort_name)), &state_table).unwrap();
-            } else {
-                info!(
-                    "No data changes detected for week {} - skipping database write",
-                    week
-                );
+            for row in &new_rows {
+                state_table.insert_or_update(row).unwrap();
             }
         } // Lock released here
 
@@ -279,7 +233,11 @@ pub async fn add_weekly_data(
     info: web::Path<(String, i32)>,
     student_data: web::Json<Vec<RowData>>,
     state: web::Data<std::sync::Mutex<Table>>,
+    req: HttpRequest,
+    sessions: web::Data<std::sync::Mutex<SessionStore>>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+
     // Validate input early (no locks needed)
     if student_data.is_empty() {
         return Err(actix_web::error::ErrorBadRequest(
@@ -289,7 +247,7 @@ pub async fn add_weekly_data(
 
     let (cohort_name, week) = info.into_inner();
 
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
     let week_num = week;
     let student_count = student_data.len();
 
@@ -303,8 +261,9 @@ pub async fn add_weekly_data(
         }
 
         // Write to database while still holding the lock
-        // This ensures consistency between memory and disk
-        write_to_db(&db_path, &state_table)?;
+        // This ensures consistency between memory and disk. Only the rows that were sent are
+        // written, so a save doesn't revert what the Classroom sync changed in the others.
+        write_rows(&db_path, &student_data, &actor.audit_name())?;
     } // Lock released here
 
     // Log after releasing the lock
@@ -321,9 +280,12 @@ pub async fn delete_data(
     info: web::Path<(String, i32)>,
     row_to_delete: web::Json<RowData>,
     state: web::Data<std::sync::Mutex<Table>>,
+    req: HttpRequest,
+    sessions: web::Data<std::sync::Mutex<SessionStore>>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let (cohort_name, _week) = info.into_inner();
-    let db_path = PathBuf::from(format!("{}", cohort_name));
+    let db_path = PathBuf::from(&cohort_name);
+    let actor = require_ta(&req, &sessions)?;
 
     // Extract data for logging before acquiring lock
     let student_name = row_to_delete.name.clone();
@@ -341,8 +303,13 @@ pub async fn delete_data(
         }) {
             state_table.rows.remove(pos);
 
-            // Write to database while holding the lock to ensure consistency
-            write_to_db(&db_path, &state_table)?;
+            // Delete from the database while holding the lock to ensure consistency
+            delete_from_db(
+                &db_path,
+                &row_to_delete.name,
+                Some(row_to_delete.week),
+                &actor.audit_name(),
+            )?;
             true
         } else {
             false
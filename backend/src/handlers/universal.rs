@@ -50,3 +50,19 @@ pub async fn switch_cohort_api(
         }
     }
 }
+
+// Reloads the shared table from disk if it currently holds `db_path`, so that the next
+// whole-table write doesn't overwrite changes made directly to the database.
+pub fn reload_state_if_active(state: &Mutex<Table>, db_path: &PathBuf) {
+    let mut table = state.lock().unwrap();
+    if table.db_path.as_ref() != Some(db_path) {
+        return;
+    }
+    match read_from_db(db_path) {
+        Ok(new_table) => {
+            *table = new_table;
+            info!("Reloaded in-memory state from {:?}", db_path);
+        }
+        Err(e) => info!("Failed to reload in-memory state: {:?}", e),
+    }
+}
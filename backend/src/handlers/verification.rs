@@ -0,0 +1,74 @@
+use crate::database::verification::{VerificationError, resend_verification, verify_email};
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::email_verification::VerificationConfig;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpResponse, get, post, web};
+use log::info;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CodeCheck {
+    pub email: String,
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendRequest {
+    pub email: String,
+}
+
+fn verification_error(e: VerificationError) -> actix_web::Error {
+    match e {
+        VerificationError::NotFound => ErrorNotFound(e.to_string()),
+        VerificationError::App(e) => ErrorInternalServerError(e),
+        e => ErrorBadRequest(e.to_string()),
+    }
+}
+
+// Target of the link in the verification email
+#[get("/verify/{cohort}")]
+pub async fn verify_link(
+    path: web::Path<String>,
+    query: web::Query<CodeCheck>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let db_path = cohort_db(&path)?;
+    let verification = verify_email(&db_path, &query.email, &query.code);
+    let message = match verification {
+        Ok(v) => {
+            info!("{} verified their email for {}", v.email, path);
+            "Your email is confirmed. Your Discord invite is on its way.".to_string()
+        }
+        Err(VerificationError::App(e)) => return Err(ErrorInternalServerError(e)),
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string())),
+    };
+    Ok(HttpResponse::Ok().content_type("text/plain").body(message))
+}
+
+#[post("/verify/{cohort}")]
+pub async fn verify_code(
+    path: web::Path<String>,
+    body: web::Json<CodeCheck>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let db_path = cohort_db(&path)?;
+    let verification =
+        verify_email(&db_path, &body.email, &body.code).map_err(verification_error)?;
+    info!("{} verified their email for {}", verification.email, path);
+    Ok(HttpResponse::Ok().json(verification))
+}
+
+// Always accepted, so the response doesn't tell who registered
+#[post("/verify/{cohort}/resend")]
+pub async fn resend_code(
+    path: web::Path<String>,
+    body: web::Json<ResendRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let cohort = cohort_config(&path)?;
+    let valid_for = VerificationConfig::from_env().code_valid_for;
+    if resend_verification(&cohort.db_path(), &body.email, &cohort.name, valid_for)? {
+        info!(
+            "Resent the verification code for {} to {}",
+            cohort.name, body.email
+        );
+    }
+    Ok(HttpResponse::Accepted().finish())
+}
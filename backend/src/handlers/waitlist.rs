@@ -0,0 +1,46 @@
+use crate::database::waitlist::{promote_from_waitlist, read_waitlist};
+use crate::utils::cohort::{cohort_config, cohort_db};
+use crate::utils::session::{SessionStore, require_ta};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use log::info;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+// Either the participants to promote or how many from the front of the line (default 1)
+#[derive(Deserialize)]
+pub struct Promotion {
+    #[serde(default)]
+    pub emails: Vec<String>,
+    pub count: Option<usize>,
+}
+
+#[get("/waitlist/{cohort}")]
+pub async fn get_waitlist(
+    path: web::Path<String>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    require_ta(&req, &sessions)?;
+    let db_path = cohort_db(&path)?;
+    Ok(HttpResponse::Ok().json(read_waitlist(&db_path)?))
+}
+
+// Gives waitlisted participants a seat and queues their bot invites
+#[post("/waitlist/{cohort}/promote")]
+pub async fn promote_waitlisted(
+    path: web::Path<String>,
+    body: web::Json<Promotion>,
+    req: HttpRequest,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let actor = require_ta(&req, &sessions)?;
+    let cohort = cohort_config(&path)?;
+    let promoted = promote_from_waitlist(&cohort.db_path(), &body.emails, body.count.unwrap_or(1))?;
+    info!(
+        "{} promoted {} participants from the {} waitlist",
+        actor.audit_name(),
+        promoted.len(),
+        cohort.name
+    );
+    Ok(HttpResponse::Ok().json(promoted))
+}
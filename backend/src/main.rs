@@ -2,21 +2,49 @@ use actix_cors::Cors;
 use actix_web::{App, HttpServer, http::header, middleware::Logger, web};
 use log::info;
 use std::sync::Mutex;
-// Import our modules
-mod database;
-mod handlers;
-mod utils;
 
 // Import functions
-use utils::backup::start_backup_thread;
-use utils::csv_dump::csv_dump;
+use backend::utils::backup::start_backup_thread;
+use backend::utils::classroom_sync::start_classroom_sync_thread;
+use backend::utils::feedback_import::start_feedback_import_thread;
+use backend::utils::notifications::start_reminder_thread;
+use backend::utils::outbox_worker::start_outbox_thread;
 
 // Import all handlers
-use handlers::auth::login; // Remove discord_callback
-use handlers::students::{
+use backend::handlers::appeals::{
+    file_my_appeal, get_appeal_queue, get_my_appeals, resolve_cohort_appeal,
+};
+use backend::handlers::audit::{
+    get_audit_log, get_student_history, preview_students_as_of, restore_students_as_of,
+};
+use backend::handlers::auth::login; // Remove discord_callback
+use backend::handlers::backup::{create_cohort_backup, list_cohort_backups, restore_cohort_backup};
+use backend::handlers::certificates::{
+    download_certificate, get_certificate_eligibility, issue_cohort_certificates,
+    verify_certificate,
+};
+use backend::handlers::classroom::{get_sync_status, sync_classroom_now};
+use backend::handlers::digest::get_ta_digest;
+use backend::handlers::enrollment::{
+    get_enrollment_history, get_enrollment_stats, get_enrollments, set_enrollment,
+};
+use backend::handlers::feedback::{
+    get_feedback_summary, get_missing_feedback, import_cohort_feedback, link_cohort_feedback,
+};
+use backend::handlers::invites::{get_invite_status, retry_invite};
+use backend::handlers::me::{get_me, get_my_background, get_my_exercises, get_my_weekly_data};
+use backend::handlers::notes::{
+    add_student_note, get_cohort_tags, get_student_notes, set_student_tags,
+};
+use backend::handlers::notifications::{get_notifications, publish_weekly_groups, send_reminders};
+use backend::handlers::reviews::{
+    decide_cohort_applications, get_review_criteria, get_review_queue, review_application,
+};
+use backend::handlers::students::{
     add_student,
     add_weekly_data,
     delete_data,
+    get_at_risk_students,
     get_cohort_feedback,
     get_individual_student_data,
     get_individual_student_data_by_mail,
@@ -37,9 +65,12 @@ use handlers::students::{
     remove_student,
     update_student,
 };
-use handlers::universal::switch_cohort_api;
-use utils::discord_participant_auth::discord_participant_oauth;
-use utils::discord_ta_auth::discord_ta_oauth;
+use backend::handlers::universal::switch_cohort_api;
+use backend::handlers::verification::{resend_code, verify_code, verify_link};
+use backend::handlers::waitlist::{get_waitlist, promote_waitlisted};
+use backend::utils::discord_participant_auth::discord_participant_oauth;
+use backend::utils::discord_ta_auth::discord_ta_oauth;
+use backend::utils::session::SessionStore;
 
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
@@ -50,16 +81,22 @@ async fn main() -> Result<(), std::io::Error> {
     log4rs::init_file("log4rs.yaml", Default::default()).unwrap();
     info!("Starting Bitshala Admin Server...");
 
-    if let Err(e) = csv_dump().await {
-        eprintln!("Error during CSV dump: {:?}", e);
-    }
-
-    // Start backup thread
+    // Start backup, feedback import, outbox and reminder threads
     start_backup_thread();
+    start_feedback_import_thread();
+    start_outbox_thread();
+    start_reminder_thread();
 
     // Initialize database state as empty - will be populated via switch_cohort_api
-    let empty_table = crate::utils::types::Table { rows: Vec::new() };
+    let empty_table = backend::utils::types::Table {
+        rows: Vec::new(),
+        db_path: None,
+    };
     let state = web::Data::new(Mutex::new(empty_table));
+    let sessions = web::Data::new(Mutex::new(SessionStore::new()));
+
+    // Keep exercise flags in sync with GitHub Classroom
+    start_classroom_sync_thread(state.clone());
 
     // Start HTTP server
     HttpServer::new(move || {
@@ -77,6 +114,7 @@ async fn main() -> Result<(), std::io::Error> {
 
         App::new()
             .app_data(state.clone())
+            .app_data(sessions.clone())
             .wrap(cors)
             .wrap(Logger::default())
             // Auth routes
@@ -94,10 +132,17 @@ async fn main() -> Result<(), std::io::Error> {
             .service(get_weekly_data_or_common)
             .service(add_weekly_data)
             .service(delete_data)
+            .service(publish_weekly_groups)
+            .service(send_reminders)
+            .service(get_notifications)
+            .service(get_ta_digest)
+            .service(get_sync_status)
+            .service(sync_classroom_now)
             // Report routes
             .service(get_total_student_count)
             .service(get_weekly_attendance_count_for_week)
             .service(get_students_by_total_score)
+            .service(get_at_risk_students)
             // Individual student routes
             .service(get_student_repo_link)
             .service(get_student_background_data)
@@ -105,8 +150,56 @@ async fn main() -> Result<(), std::io::Error> {
             .service(get_individual_student_data_by_mail)
             .service(get_student_github_username)
             .service(get_cohort_feedback)
+            .service(import_cohort_feedback)
+            .service(get_feedback_summary)
+            .service(link_cohort_feedback)
+            .service(get_missing_feedback)
+            // Certificate routes
+            .service(get_certificate_eligibility)
+            .service(issue_cohort_certificates)
+            .service(verify_certificate)
+            .service(download_certificate)
+            // Participant self-service routes
+            .service(get_me)
+            .service(get_my_weekly_data)
+            .service(get_my_exercises)
+            .service(get_my_background)
+            .service(file_my_appeal)
+            .service(get_my_appeals)
+            // Note and tag routes
+            .service(get_student_notes)
+            .service(add_student_note)
+            .service(get_cohort_tags)
+            .service(set_student_tags)
+            .service(get_enrollments)
+            .service(get_enrollment_stats)
+            .service(get_enrollment_history)
+            .service(set_enrollment)
+            .service(get_invite_status)
+            .service(retry_invite)
+            .service(get_waitlist)
+            .service(promote_waitlisted)
+            .service(verify_link)
+            .service(verify_code)
+            .service(resend_code)
+            .service(get_review_criteria)
+            .service(get_review_queue)
+            .service(review_application)
+            .service(decide_cohort_applications)
+            // Appeal routes
+            .service(get_appeal_queue)
+            .service(resolve_cohort_appeal)
             //register
             .service(register_user)
+            // Audit routes
+            .service(get_audit_log)
+            .service(get_student_history)
+            .service(preview_students_as_of)
+            .service(restore_students_as_of)
+            // Backup routes
+            .service(list_cohort_backups)
+            .service(create_cohort_backup)
+            .service(restore_cohort_backup)
     })
     .bind("127.0.0.1:8081")?
     .run()
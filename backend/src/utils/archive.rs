@@ -0,0 +1,157 @@
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use flate2::Compression as GzLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rand::RngCore;
+use sha2::Sha256;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+// Encrypted archives start with this marker, followed by the salt, the nonce and the ciphertext
+const ENCRYPTED_MAGIC: &[u8] = b"BSENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KDF_ROUNDS: u32 = 100_000;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Archive is encrypted but no passphrase was given")]
+    PassphraseRequired,
+    #[error("Failed to encrypt or decrypt archive (wrong passphrase?)")]
+    Crypto,
+    #[error("Unknown compression: {0}")]
+    UnknownCompression(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Result<Self, ArchiveError> {
+        match name.to_lowercase().as_str() {
+            "none" | "" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(ArchiveError::UnknownCompression(name.to_string())),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    // Compression used by an archive, from its file name
+    pub fn from_file_name(file_name: &str) -> Self {
+        let name = file_name.trim_end_matches(".enc");
+        if name.ends_with(".gz") {
+            Compression::Gzip
+        } else if name.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    Key::from(key)
+}
+
+fn encrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, ArchiveError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), data)
+        .map_err(|_| ArchiveError::Crypto)?;
+
+    let mut out =
+        Vec::with_capacity(ENCRYPTED_MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(ENCRYPTED_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, ArchiveError> {
+    let body = &data[ENCRYPTED_MAGIC.len()..];
+    if body.len() < SALT_LEN + NONCE_LEN {
+        return Err(ArchiveError::Crypto);
+    }
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| ArchiveError::Crypto)
+}
+
+// Compresses and, when a passphrase is given, encrypts `data`.
+// Returns the archive bytes and the suffix to append to the file name (e.g. `.gz.enc`).
+pub fn pack_archive(
+    data: &[u8],
+    compression: Compression,
+    passphrase: Option<&str>,
+) -> Result<(Vec<u8>, String), ArchiveError> {
+    let compressed = match compression {
+        Compression::None => data.to_vec(),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+        Compression::Zstd => zstd::encode_all(data, 0)?,
+    };
+
+    let mut suffix = compression.extension().to_string();
+    let archive = match passphrase {
+        Some(passphrase) => {
+            suffix.push_str(".enc");
+            encrypt(&compressed, passphrase)?
+        }
+        None => compressed,
+    };
+    Ok((archive, suffix))
+}
+
+// Reverses `pack_archive`
+pub fn unpack_archive(
+    archive: &[u8],
+    compression: Compression,
+    passphrase: Option<&str>,
+) -> Result<Vec<u8>, ArchiveError> {
+    let compressed = if archive.starts_with(ENCRYPTED_MAGIC) {
+        let passphrase = passphrase.ok_or(ArchiveError::PassphraseRequired)?;
+        decrypt(archive, passphrase)?
+    } else {
+        archive.to_vec()
+    };
+
+    match compression {
+        Compression::None => Ok(compressed),
+        Compression::Gzip => {
+            let mut out = Vec::new();
+            GzDecoder::new(compressed.as_slice()).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        Compression::Zstd => Ok(zstd::decode_all(compressed.as_slice())?),
+    }
+}
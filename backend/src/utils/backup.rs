@@ -1,65 +1,329 @@
-use chrono::{Datelike, Local};
-use log::error;
+use crate::utils::archive::{Compression, pack_archive};
+use crate::utils::backup_targets::{BackupTarget, targets_from_env};
+use crate::utils::cohort::load_cohorts;
+use crate::utils::schedule::{Schedule, spawn_scheduled};
+use chrono::{Local, NaiveDateTime};
+use log::{error, info, warn};
+use rusqlite::backup::{Backup, Progress};
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H%M%S%3f";
+// Backups written before millisecond timestamps
+const LEGACY_BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
 #[derive(Debug, Error)]
 pub enum DbError {
     #[error("Database error: {0}")]
     DatabaseError(String),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Integrity check failed for {0}: {1}")]
+    IntegrityCheck(String, String),
 }
 
-fn cleanup_old_backups(db_name: &str, days_ago: i64) {
-    let target_month = (Local::now() - chrono::Duration::days(days_ago))
-        .format("%Y-%m")
-        .to_string();
-    glob::glob(&format!("backup/{}*{}*.db", db_name, target_month))
-        .unwrap()
-        .for_each(|file| {
-            fs::remove_file(file.unwrap()).ok();
-        });
+// Runs when `BACKUP_SCHEDULE` isn't set: 03:00 on Mondays and Saturdays
+const DEFAULT_BACKUP_SCHEDULE: &str = "0 3 * * 1,6";
+
+// Backup settings, read from the environment:
+// - `BACKUP_DIR`: where verified snapshots go (default `./backup`)
+// - `BACKUP_RETENTION_DAYS`: delete snapshots older than this (default 35)
+// - `BACKUP_MIN_KEEP`: always keep at least this many of the newest snapshots per cohort (default 3)
+// - `BACKUP_COMPRESSION`: `none`, `gzip` or `zstd` for archives shipped to targets (default gzip)
+// - `BACKUP_PASSPHRASE`: encrypt shipped archives with this passphrase
+// Targets themselves come from `BACKUP_TARGETS`, see `backup_targets::targets_from_env`.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub retention_days: i64,
+    pub min_keep: usize,
+    pub compression: Compression,
+    pub passphrase: Option<String>,
 }
 
-fn backup(db_name: &str) -> Result<(), DbError> {
-    let db_path = Path::new("./").join(db_name);
+impl BackupConfig {
+    pub fn from_env() -> Self {
+        BackupConfig {
+            dir: PathBuf::from(env::var("BACKUP_DIR").unwrap_or_else(|_| "./backup".to_string())),
+            retention_days: env::var("BACKUP_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(35),
+            min_keep: env::var("BACKUP_MIN_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            compression: env::var("BACKUP_COMPRESSION")
+                .ok()
+                .and_then(|v| match Compression::from_name(&v) {
+                    Ok(compression) => Some(compression),
+                    Err(e) => {
+                        warn!("{}, falling back to gzip", e);
+                        None
+                    }
+                })
+                .unwrap_or(Compression::Gzip),
+            passphrase: env::var("BACKUP_PASSPHRASE").ok().filter(|p| !p.is_empty()),
+        }
+    }
+}
 
-    if db_path.exists() {
-        let backup_dir = Path::new("./backup");
-        fs::create_dir_all(Path::new("./backup")).unwrap();
+// A backup file found in the backup directory
+#[derive(Debug, Clone)]
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub created: NaiveDateTime,
+}
 
-        let now = Local::now();
-        let day_of_week = now.format("%A");
-        let date_time = now.format("%Y-%m-%d");
-        let backup_file = backup_dir.join(format!("{}_{}_{}", db_name, day_of_week, date_time));
-        fs::copy(&db_path, &backup_file).unwrap();
+fn db_stem(db_path: &Path) -> String {
+    db_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// Backups of `db_path` in the backup directory, newest first.
+// Files are named `{stem}_{YYYY-MM-DD_HHMMSSmmm}.db`, e.g. `pb_cohort_2025-06-02_030000000.db`.
+pub fn list_backups(db_path: &Path, config: &BackupConfig) -> Vec<BackupFile> {
+    let prefix = format!("{}_", db_stem(db_path));
+    let Ok(entries) = fs::read_dir(&config.dir) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<BackupFile> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let timestamp = file_name.strip_prefix(&prefix)?.strip_suffix(".db")?;
+            let created = NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT)
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(timestamp, LEGACY_BACKUP_TIMESTAMP_FORMAT)
+                })
+                .ok()?;
+            Some(BackupFile {
+                path: entry.path(),
+                created,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created));
+    backups
+}
 
-        cleanup_old_backups(db_name, 35);
+// Runs `PRAGMA integrity_check` on a database file
+pub fn verify_backup(path: &Path) -> Result<(), DbError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let result: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if result == "ok" {
+        Ok(())
     } else {
+        Err(DbError::IntegrityCheck(path.display().to_string(), result))
+    }
+}
+
+// Copies a live database with SQLite's online backup API, so writes made by the server
+// during the copy can't produce a torn file, then verifies the copy.
+pub fn backup_database(db_path: &Path, config: &BackupConfig) -> Result<PathBuf, DbError> {
+    if !db_path.exists() {
         return Err(DbError::DatabaseError(format!(
-            "Database file '{}' not found in project root.",
-            db_name
+            "Database file '{}' not found.",
+            db_path.display()
         )));
     }
+    fs::create_dir_all(&config.dir)?;
+
+    let timestamp = Local::now().format(BACKUP_TIMESTAMP_FORMAT);
+    let backup_file = config
+        .dir
+        .join(format!("{}_{}.db", db_stem(db_path), timestamp));
+    let partial_file = backup_file.with_extension("db.partial");
+
+    {
+        let src = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut dst = Connection::open(&partial_file)?;
+        let backup = Backup::new(&src, &mut dst)?;
+        backup.run_to_completion(256, Duration::from_millis(50), None)?;
+    }
+
+    if let Err(e) = verify_backup(&partial_file) {
+        fs::remove_file(&partial_file).ok();
+        return Err(e);
+    }
+    fs::rename(&partial_file, &backup_file)?;
 
-    Ok(())
+    info!(
+        "Backed up {} to {}",
+        db_path.display(),
+        backup_file.display()
+    );
+    Ok(backup_file)
 }
 
-pub fn start_backup_thread() {
-    std::thread::spawn(|| {
-        loop {
-            let now = chrono::Local::now();
-            let weekday = now.date_naive().weekday();
-            if weekday == chrono::Weekday::Mon || weekday == chrono::Weekday::Sat {
-                let db_name = "classroom.db";
-                let result: std::result::Result<(), DbError> = backup(&db_name);
-                if let Err(e) = result {
-                    error!("Failed to backup database: {}", e);
+// Finds a backup of `db_path` by file name. Only names produced by `list_backups` are
+// accepted, so a request can't point outside the backup directory.
+pub fn find_backup(db_path: &Path, file_name: &str, config: &BackupConfig) -> Option<BackupFile> {
+    list_backups(db_path, config)
+        .into_iter()
+        .find(|b| b.path.file_name().is_some_and(|name| name == file_name))
+}
+
+// Number of rows in each table of a database file
+pub fn table_row_counts(path: &Path) -> Result<BTreeMap<String, i64>, DbError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let tables: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")),
+            [],
+            |row| row.get(0),
+        )?;
+        counts.insert(table, count);
+    }
+    Ok(counts)
+}
+
+// Replaces the contents of `db_path` with `backup_path`. The backup is verified first and a
+// safety backup of the current database is taken, whose path is returned.
+pub fn restore_backup(
+    db_path: &Path,
+    backup_path: &Path,
+    config: &BackupConfig,
+) -> Result<Option<PathBuf>, DbError> {
+    verify_backup(backup_path)?;
+
+    let safety_backup = if db_path.exists() {
+        Some(backup_database(db_path, config)?)
+    } else {
+        None
+    };
+
+    let mut conn = Connection::open(db_path)?;
+    conn.restore(DatabaseName::Main, backup_path, None::<fn(Progress)>)?;
+    drop(conn);
+    verify_backup(db_path)?;
+
+    info!(
+        "Restored {} from {} (safety backup: {:?})",
+        db_path.display(),
+        backup_path.display(),
+        safety_backup
+    );
+    Ok(safety_backup)
+}
+
+// Deletes backups of `db_path` older than the retention period, keeping at least
+// `min_keep` of the newest. Returns the removed files.
+pub fn apply_retention(db_path: &Path, config: &BackupConfig) -> Vec<PathBuf> {
+    let cutoff = (Local::now() - chrono::Duration::days(config.retention_days)).naive_local();
+    let mut removed = Vec::new();
+
+    for backup in list_backups(db_path, config)
+        .into_iter()
+        .skip(config.min_keep)
+    {
+        if backup.created < cutoff {
+            match fs::remove_file(&backup.path) {
+                Ok(_) => removed.push(backup.path),
+                Err(e) => warn!("Failed to remove old backup {:?}: {}", backup.path, e),
+            }
+        }
+    }
+    removed
+}
+
+// Compresses/encrypts a snapshot and sends it to every target. Returns one result per target.
+pub fn ship_backup(
+    backup_path: &Path,
+    config: &BackupConfig,
+    targets: &[Box<dyn BackupTarget>],
+) -> Vec<Result<String, DbError>> {
+    if targets.is_empty() {
+        return Vec::new();
+    }
+    let packed = fs::read(backup_path)
+        .map_err(DbError::from)
+        .and_then(|data| {
+            pack_archive(&data, config.compression, config.passphrase.as_deref())
+                .map_err(|e| DbError::DatabaseError(e.to_string()))
+        });
+    let (archive, suffix) = match packed {
+        Ok(packed) => packed,
+        Err(e) => return vec![Err(e)],
+    };
+    let file_name = format!(
+        "{}{}",
+        backup_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy(),
+        suffix
+    );
+
+    targets
+        .iter()
+        .map(|target| {
+            target
+                .store(&file_name, &archive)
+                .map_err(|e| DbError::DatabaseError(format!("{}: {}", target.name(), e)))
+        })
+        .collect()
+}
+
+// Backs up every cohort in the registry, ships the snapshot to the configured targets and
+// applies the retention policy to local snapshots
+pub fn backup_all_cohorts(config: &BackupConfig) {
+    let targets = targets_from_env();
+    for cohort in load_cohorts() {
+        let db_path = cohort.db_path();
+        if !db_path.exists() {
+            continue;
+        }
+        match backup_database(&db_path, config) {
+            Ok(backup) => {
+                for result in ship_backup(&backup, config, &targets) {
+                    match result {
+                        Ok(location) => info!("Shipped {} backup to {}", cohort.name, location),
+                        Err(e) => error!("Failed to ship {} backup: {}", cohort.name, e),
+                    }
+                }
+                let removed = apply_retention(&db_path, config);
+                if !removed.is_empty() {
+                    info!("Removed {} old backups of {}", removed.len(), cohort.db);
                 }
-                std::thread::sleep(std::time::Duration::from_secs(60 * 60 * 24));
-            } else {
-                std::thread::sleep(std::time::Duration::from_secs(60 * 60));
             }
+            Err(e) => error!("Failed to backup {} database: {}", cohort.name, e),
+        }
+    }
+}
+
+// Schedules `backup_all_cohorts` according to `BACKUP_SCHEDULE` (a five-field cron expression)
+pub fn start_backup_thread() {
+    let expr = env::var("BACKUP_SCHEDULE").unwrap_or_else(|_| DEFAULT_BACKUP_SCHEDULE.to_string());
+    let schedule = match expr.parse::<Schedule>() {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            error!(
+                "Invalid BACKUP_SCHEDULE {:?}: {}. Using {:?}.",
+                expr, e, DEFAULT_BACKUP_SCHEDULE
+            );
+            DEFAULT_BACKUP_SCHEDULE.parse().unwrap()
         }
+    };
+    spawn_scheduled("backup", schedule, || {
+        backup_all_cohorts(&BackupConfig::from_env())
     });
 }
@@ -0,0 +1,183 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TargetError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Upload rejected with status {0}: {1}")]
+    Rejected(u16, String),
+    #[error("Invalid target configuration: {0}")]
+    Config(String),
+}
+
+// Somewhere a backup archive can be shipped to
+pub trait BackupTarget: Send + Sync {
+    fn name(&self) -> String;
+    // Stores `data` as `file_name` and returns where it ended up
+    fn store(&self, file_name: &str, data: &[u8]) -> Result<String, TargetError>;
+}
+
+// A local (or mounted network) directory
+pub struct LocalTarget {
+    pub dir: PathBuf,
+}
+
+impl BackupTarget for LocalTarget {
+    fn name(&self) -> String {
+        format!("dir:{}", self.dir.display())
+    }
+
+    fn store(&self, file_name: &str, data: &[u8]) -> Result<String, TargetError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(file_name);
+        fs::write(&path, data)?;
+        Ok(path.display().to_string())
+    }
+}
+
+// An S3-compatible bucket (AWS, MinIO, ...) addressed path-style: `{endpoint}/{bucket}/{key}`
+pub struct S3Target {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub prefix: String,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl S3Target {
+    // Reads `BACKUP_S3_ENDPOINT`, `BACKUP_S3_BUCKET`, `BACKUP_S3_ACCESS_KEY`, `BACKUP_S3_SECRET_KEY`
+    // and optionally `BACKUP_S3_REGION` (default `us-east-1`) and `BACKUP_S3_PREFIX`.
+    pub fn from_env() -> Result<Self, TargetError> {
+        let var = |name: &str| {
+            env::var(name).map_err(|_| TargetError::Config(format!("{} is not set", name)))
+        };
+        Ok(S3Target {
+            endpoint: var("BACKUP_S3_ENDPOINT")?.trim_end_matches('/').to_string(),
+            bucket: var("BACKUP_S3_BUCKET")?,
+            region: env::var("BACKUP_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            access_key: var("BACKUP_S3_ACCESS_KEY")?,
+            secret_key: var("BACKUP_S3_SECRET_KEY")?,
+            prefix: env::var("BACKUP_S3_PREFIX").unwrap_or_default(),
+        })
+    }
+
+    fn object_path(&self, file_name: &str) -> String {
+        let key = format!("{}{}", self.prefix, file_name);
+        let encoded: Vec<String> = key
+            .split('/')
+            .map(|segment| urlencoding::encode(segment).to_string())
+            .collect();
+        format!("/{}/{}", self.bucket, encoded.join("/"))
+    }
+
+    // AWS Signature Version 4 headers for an unsigned-query PUT of `payload` to `path`
+    fn signed_headers(&self, host: &str, path: &str, payload: &[u8]) -> Vec<(String, String)> {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(payload));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), &date);
+        let key = hmac(&key, &self.region);
+        let key = hmac(&key, "s3");
+        let key = hmac(&key, "aws4_request");
+        let signature = hex::encode(hmac(&key, &string_to_sign));
+
+        vec![
+            ("x-amz-date".to_string(), amz_date),
+            ("x-amz-content-sha256".to_string(), payload_hash),
+            (
+                "Authorization".to_string(),
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, signed_headers, signature
+                ),
+            ),
+        ]
+    }
+}
+
+impl BackupTarget for S3Target {
+    fn name(&self) -> String {
+        format!("s3:{}/{}", self.endpoint, self.bucket)
+    }
+
+    fn store(&self, file_name: &str, data: &[u8]) -> Result<String, TargetError> {
+        let url = reqwest::Url::parse(&self.endpoint)
+            .map_err(|e| TargetError::Config(format!("invalid endpoint: {}", e)))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(TargetError::Config("endpoint has no host".to_string())),
+        };
+        let path = self.object_path(file_name);
+
+        let client = reqwest::blocking::Client::new();
+        let mut request = client
+            .put(format!("{}{}", self.endpoint, path))
+            .body(data.to_vec());
+        for (name, value) in self.signed_headers(&host, &path, data) {
+            request = request.header(name, value);
+        }
+
+        let response = request.send()?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(TargetError::Rejected(status.as_u16(), body));
+        }
+        Ok(format!("{}{}", self.endpoint, path))
+    }
+}
+
+// Targets listed in `BACKUP_TARGETS`, comma separated: `dir:/some/path` or `s3`
+pub fn targets_from_env() -> Vec<Box<dyn BackupTarget>> {
+    let spec = env::var("BACKUP_TARGETS").unwrap_or_default();
+    let mut targets: Vec<Box<dyn BackupTarget>> = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        if let Some(dir) = entry.strip_prefix("dir:") {
+            targets.push(Box::new(LocalTarget {
+                dir: PathBuf::from(dir),
+            }));
+        } else if entry == "s3" {
+            match S3Target::from_env() {
+                Ok(target) => targets.push(Box::new(target)),
+                Err(e) => warn!("Skipping S3 backup target: {}", e),
+            }
+        } else {
+            warn!("Unknown backup target {:?}", entry);
+        }
+    }
+    targets
+}
@@ -0,0 +1,56 @@
+use crate::utils::outbox_worker::{DeliveryReport, RetryPolicy, deliver_outbox};
+use crate::utils::types::AppError;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+pub const BOT_INVITE: &str = "bot_invite";
+
+// Bot invite settings, read from the environment:
+// - `BOT_INVITE_URL`: where invites are POSTed (default `http://localhost:8080/bot/invite`)
+// - `BOT_INVITE_MAX_ATTEMPTS`: give up after this many failed attempts (default 10)
+// - `BOT_INVITE_RETRY_SECONDS`: wait after the first failure, doubled after each one (default 30)
+#[derive(Debug, Clone)]
+pub struct BotInviteConfig {
+    pub url: String,
+    pub retry: RetryPolicy,
+}
+
+impl BotInviteConfig {
+    pub fn from_env() -> Self {
+        BotInviteConfig {
+            url: env::var("BOT_INVITE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/bot/invite".to_string()),
+            retry: RetryPolicy::from_env("BOT_INVITE"),
+        }
+    }
+}
+
+pub fn send_invite(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    payload: &str,
+) -> Result<(), String> {
+    client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Sends every due invite of one cohort database. Blocking; run it off the async runtime.
+pub fn deliver_bot_invites(
+    db_path: &Path,
+    config: &BotInviteConfig,
+) -> Result<DeliveryReport, AppError> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| AppError::Io(std::io::Error::other(e)))?;
+    deliver_outbox(db_path, BOT_INVITE, &config.retry, |message| {
+        send_invite(&client, &config.url, &message.payload)
+    })
+}
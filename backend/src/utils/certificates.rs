@@ -0,0 +1,262 @@
+use crate::database::operations::read_from_db;
+use crate::utils::cohort::load_cohorts;
+use crate::utils::feedback_links::feedback_by_student;
+use crate::utils::types::AppError;
+use chrono::Utc;
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Verification codes avoid characters that are easy to misread (0/O, 1/I)
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+// What a student needs for a certificate, set per cohort under `certificate` in `cohorts.json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CertificateRules {
+    pub min_attendance_weeks: usize,
+    pub min_total: u64,
+    pub min_exercises_passed: usize,
+    pub require_feedback: bool,
+}
+
+impl Default for CertificateRules {
+    fn default() -> Self {
+        CertificateRules {
+            min_attendance_weeks: 0,
+            min_total: 0,
+            min_exercises_passed: 0,
+            require_feedback: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Eligibility {
+    pub name: String,
+    pub mail: String,
+    // From the student's feedback response, falling back to the roster name
+    pub name_on_certificate: String,
+    pub attended_weeks: usize,
+    pub total: u64,
+    pub exercises_passed: usize,
+    pub feedback_submitted: bool,
+    pub eligible: bool,
+    // Why the student isn't eligible; empty when they are
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Certificate {
+    pub code: String,
+    pub cohort: String,
+    pub student_name: String,
+    pub name_on_certificate: String,
+    pub issued_at: String,
+}
+
+pub fn check_eligibility(
+    db_path: &Path,
+    rules: &CertificateRules,
+) -> Result<Vec<Eligibility>, AppError> {
+    let table = read_from_db(&db_path.to_path_buf())?;
+    let feedback = feedback_by_student(&Connection::open(db_path)?)?;
+
+    let mut students: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for row in &table.rows {
+        students.entry(row.name.as_str()).or_default().push(row);
+    }
+
+    Ok(students
+        .into_iter()
+        .map(|(name, rows)| {
+            let attended_weeks = rows
+                .iter()
+                .filter(|r| r.week > 0 && r.attendance.as_deref() == Some("yes"))
+                .count();
+            let total = rows.iter().map(|r| r.total.unwrap_or(0)).sum();
+            let exercises_passed = rows
+                .iter()
+                .filter(|r| r.exercise_test_passing.as_deref() == Some("yes"))
+                .count();
+            let certificate_name = feedback.get(name);
+
+            let mut reasons = Vec::new();
+            if attended_weeks < rules.min_attendance_weeks {
+                reasons.push(format!(
+                    "attended {} weeks, needs {}",
+                    attended_weeks, rules.min_attendance_weeks
+                ));
+            }
+            if total < rules.min_total {
+                reasons.push(format!("total score {}, needs {}", total, rules.min_total));
+            }
+            if exercises_passed < rules.min_exercises_passed {
+                reasons.push(format!(
+                    "passed {} exercises, needs {}",
+                    exercises_passed, rules.min_exercises_passed
+                ));
+            }
+            if rules.require_feedback && certificate_name.is_none() {
+                reasons.push("no feedback submitted".to_string());
+            }
+
+            Eligibility {
+                name: name.to_string(),
+                mail: rows[0].mail.clone(),
+                name_on_certificate: certificate_name
+                    .filter(|n| !n.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| name.to_string()),
+                attended_weeks,
+                total,
+                exercises_passed,
+                feedback_submitted: certificate_name.is_some(),
+                eligible: reasons.is_empty(),
+                reasons,
+            }
+        })
+        .collect())
+}
+
+fn ensure_certificates_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS certificates (
+            code TEXT PRIMARY KEY,
+            cohort TEXT NOT NULL,
+            student_name TEXT NOT NULL UNIQUE,
+            name_on_certificate TEXT NOT NULL,
+            issued_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn certificate_from_row(row: &rusqlite::Row) -> rusqlite::Result<Certificate> {
+    Ok(Certificate {
+        code: row.get("code")?,
+        cohort: row.get("cohort")?,
+        student_name: row.get("student_name")?,
+        name_on_certificate: row.get("name_on_certificate")?,
+        issued_at: row.get("issued_at")?,
+    })
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: Vec<char> = (0..12)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    chars
+        .chunks(4)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Issues a certificate to every eligible student who doesn't have one yet and returns all
+// certificates of the cohort. Codes already handed out never change.
+pub fn issue_certificates(
+    db_path: &Path,
+    cohort: &str,
+    rules: &CertificateRules,
+) -> Result<Vec<Certificate>, AppError> {
+    let eligible: Vec<Eligibility> = check_eligibility(db_path, rules)?
+        .into_iter()
+        .filter(|e| e.eligible)
+        .collect();
+
+    let mut conn = Connection::open(db_path)?;
+    ensure_certificates_table(&conn)?;
+    let tx = conn.transaction()?;
+    let issued_at = Utc::now().to_rfc3339();
+    for student in &eligible {
+        tx.execute(
+            "INSERT OR IGNORE INTO certificates (code, cohort, student_name, name_on_certificate, issued_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                generate_code(),
+                cohort,
+                student.name,
+                student.name_on_certificate,
+                issued_at
+            ],
+        )?;
+    }
+    tx.commit()?;
+
+    let mut stmt = conn.prepare("SELECT * FROM certificates ORDER BY student_name")?;
+    let certificates = stmt
+        .query_map([], certificate_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(certificates)
+}
+
+pub fn find_certificate_in(db_path: &Path, code: &str) -> Result<Option<Certificate>, AppError> {
+    let conn = Connection::open(db_path)?;
+    let issued: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'certificates'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !issued {
+        return Ok(None);
+    }
+    Ok(conn
+        .query_row(
+            "SELECT * FROM certificates WHERE code = ?1",
+            params![code.trim().to_uppercase()],
+            certificate_from_row,
+        )
+        .optional()?)
+}
+
+// Looks a verification code up in every registered cohort
+pub fn find_certificate(code: &str) -> Result<Option<Certificate>, AppError> {
+    for cohort in load_cohorts() {
+        let db_path = cohort.db_path();
+        if !db_path.exists() {
+            continue;
+        }
+        if let Some(certificate) = find_certificate_in(&db_path, code)? {
+            return Ok(Some(certificate));
+        }
+    }
+    Ok(None)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// A landscape A4 certificate as SVG, printable to PDF from any browser
+pub fn render_certificate_svg(certificate: &Certificate) -> String {
+    let issued = certificate
+        .issued_at
+        .get(..10)
+        .unwrap_or(&certificate.issued_at);
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="297mm" height="210mm" viewBox="0 0 1123 794">
+  <rect width="1123" height="794" fill="#fffdf7"/>
+  <rect x="30" y="30" width="1063" height="734" fill="none" stroke="#f7931a" stroke-width="6"/>
+  <text x="561" y="170" text-anchor="middle" font-family="Georgia, serif" font-size="56" fill="#222">Certificate of Completion</text>
+  <text x="561" y="270" text-anchor="middle" font-family="Georgia, serif" font-size="24" fill="#555">This certifies that</text>
+  <text x="561" y="360" text-anchor="middle" font-family="Georgia, serif" font-size="48" fill="#111">{name}</text>
+  <text x="561" y="440" text-anchor="middle" font-family="Georgia, serif" font-size="24" fill="#555">has successfully completed the Bitshala {cohort} cohort</text>
+  <text x="561" y="640" text-anchor="middle" font-family="monospace" font-size="18" fill="#555">Issued {issued} · Verification code {code}</text>
+  <text x="561" y="670" text-anchor="middle" font-family="monospace" font-size="14" fill="#888">Verify at /certificates/verify/{code}</text>
+</svg>
+"##,
+        name = escape_xml(&certificate.name_on_certificate),
+        cohort = escape_xml(&certificate.cohort),
+        issued = escape_xml(issued),
+        code = escape_xml(&certificate.code),
+    )
+}
@@ -1,8 +1,24 @@
+use crate::database::audit::audit_timestamp;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use log::{error, info, warn};
 use octocrab::Octocrab;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 use thiserror::Error;
-use serde_json::Value;
+
+// Largest page GitHub serves
+const PAGE_SIZE: usize = 100;
+// Guards against a listing that keeps pointing at more pages
+const MAX_PAGES: usize = 100;
+
+// Longest wait after repeated failures without rate-limit headers
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
 
 // Represents a GitHub Classroom
 #[derive(Debug, Deserialize)]
@@ -34,11 +50,18 @@ pub enum ClassroomError {
     #[error("GitHub API error: {0}")]
     Octocrab(#[from] octocrab::Error),
     #[error("Environment variable GITHUB_TOKEN not set")]
-    MissingToken(#[from] env::VarError),
+    MissingToken,
     #[error("Failed to parse API response: {0}")]
     ParseError(#[from] serde_json::Error),
+    #[error("GitHub is rate limiting us until {0}")]
+    RateLimited(String),
+    #[error("GitHub answered {0}: {1}")]
+    Status(u16, String),
+    #[error("Classroom cache error: {0}")]
+    Cache(#[from] rusqlite::Error),
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum WEEK {
     One,
@@ -48,6 +71,13 @@ enum WEEK {
     Five,
 }
 
+// Classroom assignment of each week's exercise, used for cohorts that don't bind their own
+pub fn default_assignment_bindings() -> BTreeMap<i32, u32> {
+    (1..=5)
+        .filter_map(|week| WEEK::from_number(week).map(|w| (week, w.to_assign_id())))
+        .collect()
+}
+
 impl WEEK {
     pub fn from_number(week_number: i32) -> Option<Self> {
         match week_number {
@@ -60,7 +90,7 @@ impl WEEK {
         }
     }
 
-    pub fn to_assign_id(&self) -> u32 {
+    pub fn to_assign_id(self) -> u32 {
         match self {
             WEEK::One => 829989,
             WEEK::Two => 831654,
@@ -71,42 +101,17 @@ impl WEEK {
     }
 }
 
-// pub async fn get_classroom_info(
-// ) -> Result<Vec<Value>, ClassroomError> {
-//     let token = env::var("GITHUB_TOKEN")?;
-//     println!("GitHub token: {}", token);
-//     let octocrab = Octocrab::builder().personal_token(token).build()?;
-
-//     let classroom = octocrab.get("/classrooms/274924/assignments", None::<&()>).await?;
-
-//     Ok(classroom)
-// }
-
-pub async fn get_submitted_assignments(
-    week_number: i32,
-) -> Result<Vec<Assignment>, ClassroomError> {
-    let token = env::var("GITHUB_TOKEN")?;
-    println!("GitHub token: {}", token);
-    let octocrab = Octocrab::builder().personal_token(token).build()?;
-
-    let week = if let Some(week) = WEEK::from_number(week_number) {
-        week
-    } else {
-        return Ok(vec![]);
-    };
-
-    let assignment_id = week.to_assign_id();
-    let endpoint = format!("/assignments/{assignment_id}/grades");
-    let assignments: Vec<Assignment> = octocrab.get(endpoint, None::<&()>).await?;
-    Ok(assignments)
-}
-
 impl Assignment {
     // Check if assignment was submitted
     pub fn is_submitted(&self) -> bool {
         self.submission_timestamp != Some("".to_string())
     }
 
+    // Full marks means every test passes
+    pub fn is_passing(&self) -> bool {
+        self.points_awarded == "100"
+    }
+
     // Check if assignment was submitted
     pub fn get_week_pattern(&self) -> Option<u32> {
         let name = self.assignment_name.to_lowercase();
@@ -119,3 +124,413 @@ impl Assignment {
         None
     }
 }
+
+// Classroom grades of one assignment as the rest of the server sees them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClassroomGrades {
+    pub assignments: Vec<Assignment>,
+    // When the grades were fetched from GitHub; None if they never were
+    pub fetched_at: Option<String>,
+    // GitHub couldn't be reached and these are the last cached grades (possibly none)
+    pub stale: bool,
+    pub error: Option<String>,
+}
+
+// Talks to the GitHub Classroom API and caches grades per assignment in SQLite
+pub struct ClassroomClient {
+    octocrab: Octocrab,
+    has_token: bool,
+    // SQLite file with the cached grades and the rate-limit backoff
+    pub cache_db: PathBuf,
+    // How long cached grades are served without asking GitHub
+    pub ttl: Duration,
+    // First wait after a failure without rate-limit headers, doubled after each one
+    pub retry_delay: Duration,
+}
+
+// A student's repository for an assignment, from the accepted-assignments listing
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct AcceptedAssignment {
+    pub id: u64,
+    #[serde(default)]
+    pub submitted: bool,
+    #[serde(default)]
+    pub passing: bool,
+    #[serde(default)]
+    pub commit_count: u64,
+    #[serde(default)]
+    pub grade: Option<String>,
+    #[serde(default)]
+    pub students: Vec<ClassroomStudent>,
+    pub repository: ClassroomRepository,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct ClassroomStudent {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct ClassroomRepository {
+    pub full_name: String,
+    pub html_url: String,
+}
+
+// Path and query of the `rel="next"` entry of a `Link` header, so the next page is requested
+// through the configured API URL
+fn next_page(link: &str) -> Option<String> {
+    link.split(',').find_map(|entry| {
+        let (url, params) = entry.split_once(';')?;
+        if !params.split(';').any(|p| p.trim() == "rel=\"next\"") {
+            return None;
+        }
+        let url = url.trim().trim_start_matches('<').trim_end_matches('>');
+        match url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map(|i| rest[i..].to_string()),
+            None => Some(url.to_string()),
+        }
+    })
+}
+
+fn ensure_cache_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS classroom_grades (
+            assignment_id INTEGER PRIMARY KEY,
+            grades        TEXT NOT NULL,
+            fetched_at    TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS classroom_backoff (
+            id       INTEGER PRIMARY KEY CHECK (id = 1),
+            failures INTEGER NOT NULL,
+            until    TEXT NOT NULL
+        );",
+    )
+}
+
+fn open_cache(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    ensure_cache_tables(&conn)?;
+    Ok(conn)
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// When GitHub wants us back, from `Retry-After` or an exhausted `X-RateLimit-Remaining` with
+// its `X-RateLimit-Reset`
+fn rate_limit_reset(
+    header: impl Fn(&str) -> Option<String>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if let Some(seconds) = header("retry-after").and_then(|v| v.trim().parse::<i64>().ok()) {
+        return Some(now + chrono::Duration::seconds(seconds));
+    }
+    if header("x-ratelimit-remaining").as_deref().map(str::trim) == Some("0") {
+        return header("x-ratelimit-reset")
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())
+            .map(|reset| reset.max(now));
+    }
+    None
+}
+
+impl ClassroomClient {
+    pub fn new(
+        api_url: &str,
+        token: Option<String>,
+        cache_db: PathBuf,
+        ttl: Duration,
+        retry_delay: Duration,
+    ) -> Result<Self, ClassroomError> {
+        let mut builder = Octocrab::builder()
+            .base_uri(api_url)?
+            .set_connect_timeout(Some(Duration::from_secs(10)))
+            .set_read_timeout(Some(Duration::from_secs(30)));
+        let has_token = token.is_some();
+        if let Some(token) = token {
+            builder = builder.personal_token(token);
+        }
+        Ok(ClassroomClient {
+            octocrab: builder.build()?,
+            has_token,
+            cache_db,
+            ttl,
+            retry_delay,
+        })
+    }
+
+    // Reads `GITHUB_TOKEN`, `GITHUB_API_URL` (default `https://api.github.com`),
+    // `CLASSROOM_CACHE_DB` (default `classroom_cache.db`), `CLASSROOM_CACHE_SECONDS` (default
+    // 300) and `CLASSROOM_RETRY_SECONDS` (default 60)
+    pub fn from_env() -> Result<Self, ClassroomError> {
+        let seconds = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        ClassroomClient::new(
+            &env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string()),
+            env::var("GITHUB_TOKEN")
+                .ok()
+                .filter(|t| !t.trim().is_empty()),
+            PathBuf::from(
+                env::var("CLASSROOM_CACHE_DB").unwrap_or_else(|_| "classroom_cache.db".to_string()),
+            ),
+            Duration::from_secs(seconds("CLASSROOM_CACHE_SECONDS", 300)),
+            Duration::from_secs(seconds("CLASSROOM_RETRY_SECONDS", 60)),
+        )
+    }
+
+    // The client every request shares, built from the environment on first use
+    pub fn shared() -> Option<&'static ClassroomClient> {
+        static CLIENT: OnceLock<Option<ClassroomClient>> = OnceLock::new();
+        CLIENT
+            .get_or_init(|| match ClassroomClient::from_env() {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    error!("Couldn't set up the GitHub Classroom client: {}", e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    fn cached(&self, assignment_id: u32) -> rusqlite::Result<Option<(Vec<Assignment>, String)>> {
+        let conn = open_cache(&self.cache_db)?;
+        let cached: Option<(String, String)> = conn
+            .query_row(
+                "SELECT grades, fetched_at FROM classroom_grades WHERE assignment_id = ?1",
+                params![assignment_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(cached.map(|(grades, fetched_at)| {
+            (
+                serde_json::from_str(&grades).unwrap_or_default(),
+                fetched_at,
+            )
+        }))
+    }
+
+    fn store(&self, assignment_id: u32, assignments: &[Assignment]) -> rusqlite::Result<String> {
+        let conn = open_cache(&self.cache_db)?;
+        let now = audit_timestamp();
+        conn.execute(
+            "INSERT OR REPLACE INTO classroom_grades (assignment_id, grades, fetched_at)
+             VALUES (?1, ?2, ?3)",
+            params![
+                assignment_id,
+                serde_json::to_string(assignments).unwrap_or_default(),
+                now
+            ],
+        )?;
+        conn.execute("DELETE FROM classroom_backoff", [])?;
+        Ok(now)
+    }
+
+    fn blocked_until(&self) -> rusqlite::Result<Option<String>> {
+        let conn = open_cache(&self.cache_db)?;
+        conn.query_row(
+            "SELECT until FROM classroom_backoff WHERE until > ?1",
+            params![audit_timestamp()],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    // Holds requests back until `reset`, or for a doubling delay when GitHub didn't say
+    fn back_off(&self, reset: Option<DateTime<Utc>>) -> rusqlite::Result<String> {
+        let conn = open_cache(&self.cache_db)?;
+        let failures: i64 = conn
+            .query_row("SELECT failures FROM classroom_backoff", [], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .unwrap_or(0)
+            + 1;
+        let until = reset.unwrap_or_else(|| {
+            let factor = 2u32.saturating_pow((failures - 1).min(31) as u32);
+            let delay = self.retry_delay.saturating_mul(factor).min(MAX_BACKOFF);
+            Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default()
+        });
+        let until = timestamp(until);
+        conn.execute(
+            "INSERT OR REPLACE INTO classroom_backoff (id, failures, until) VALUES (1, ?1, ?2)",
+            params![failures, until],
+        )?;
+        Ok(until)
+    }
+
+    // GETs one page and returns its body and the link to the next page, if any
+    async fn fetch_page(&self, uri: &str) -> Result<(String, Option<String>), ClassroomError> {
+        if !self.has_token {
+            return Err(ClassroomError::MissingToken);
+        }
+        if let Some(until) = self.blocked_until()? {
+            return Err(ClassroomError::RateLimited(until));
+        }
+        let response = match self.octocrab._get(uri).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.back_off(None)?;
+                return Err(e.into());
+            }
+        };
+        let status = response.status().as_u16();
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let reset = rate_limit_reset(header, Utc::now());
+        let next = header("link").and_then(|link| next_page(&link));
+        let body = self.octocrab.body_to_string(response).await?;
+        if !(200..300).contains(&status) {
+            let until = self.back_off(reset)?;
+            return Err(if reset.is_some() {
+                ClassroomError::RateLimited(until)
+            } else {
+                ClassroomError::Status(status, body)
+            });
+        }
+        if let Some(reset) = reset {
+            // That was the last request GitHub allows until the reset
+            warn!("GitHub rate limit used up until {}", timestamp(reset));
+            self.back_off(Some(reset))?;
+        }
+        Ok((body, next))
+    }
+
+    // Every item of a paginated listing at `path`, following the `next` links
+    async fn fetch_all<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>, ClassroomError> {
+        let mut items = Vec::new();
+        let mut uri = format!("{}?per_page={}", path, PAGE_SIZE);
+        for _ in 0..MAX_PAGES {
+            let (body, next) = self.fetch_page(&uri).await?;
+            items.extend(serde_json::from_str::<Vec<T>>(&body)?);
+            match next {
+                Some(next) => uri = next,
+                None => return Ok(items),
+            }
+        }
+        warn!("Stopped reading {} after {} pages", path, MAX_PAGES);
+        Ok(items)
+    }
+
+    async fn fetch(&self, assignment_id: u32) -> Result<Vec<Assignment>, ClassroomError> {
+        self.fetch_all(&format!("/assignments/{assignment_id}/grades"))
+            .await
+    }
+
+    // Repositories students created by accepting `assignment_id`. Not cached.
+    pub async fn accepted_assignments(
+        &self,
+        assignment_id: u32,
+    ) -> Result<Vec<AcceptedAssignment>, ClassroomError> {
+        self.fetch_all(&format!(
+            "/assignments/{assignment_id}/accepted_assignments"
+        ))
+        .await
+    }
+
+    // Grades of `assignment_id`, from the cache while it's fresh. When GitHub can't be reached
+    // the last cached grades are served and marked stale.
+    pub async fn grades(&self, assignment_id: u32) -> ClassroomGrades {
+        self.load(assignment_id, self.ttl).await
+    }
+
+    // Like `grades`, but asks GitHub even if the cache is fresh
+    pub async fn refresh(&self, assignment_id: u32) -> ClassroomGrades {
+        self.load(assignment_id, Duration::ZERO).await
+    }
+
+    async fn load(&self, assignment_id: u32, ttl: Duration) -> ClassroomGrades {
+        let cached = self.cached(assignment_id).unwrap_or_else(|e| {
+            warn!("Couldn't read the Classroom cache: {}", e);
+            None
+        });
+        if let Some((assignments, fetched_at)) = &cached {
+            let age = DateTime::parse_from_rfc3339(fetched_at)
+                .map(|at| Utc::now().signed_duration_since(at))
+                .ok()
+                .and_then(|age| age.to_std().ok());
+            if age.is_some_and(|age| age < ttl) {
+                return ClassroomGrades {
+                    assignments: assignments.clone(),
+                    fetched_at: Some(fetched_at.clone()),
+                    stale: false,
+                    error: None,
+                };
+            }
+        }
+
+        let fetched = match self.fetch(assignment_id).await {
+            Ok(assignments) => self
+                .store(assignment_id, &assignments)
+                .map(|fetched_at| (assignments, fetched_at))
+                .map_err(ClassroomError::from),
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok((assignments, fetched_at)) => {
+                info!(
+                    "Fetched {} Classroom grades of assignment {}",
+                    assignments.len(),
+                    assignment_id
+                );
+                ClassroomGrades {
+                    assignments,
+                    fetched_at: Some(fetched_at),
+                    stale: false,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Serving cached Classroom grades of assignment {}: {}",
+                    assignment_id, e
+                );
+                let (assignments, fetched_at) = match cached {
+                    Some((assignments, fetched_at)) => (assignments, Some(fetched_at)),
+                    None => (Vec::new(), None),
+                };
+                ClassroomGrades {
+                    assignments,
+                    fetched_at,
+                    stale: true,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+// Classroom grades of the exercise of `week`. Weeks without an exercise have none.
+pub async fn week_grades(week: i32) -> ClassroomGrades {
+    let Some(week) = WEEK::from_number(week) else {
+        return ClassroomGrades::default();
+    };
+    match ClassroomClient::shared() {
+        Some(client) => client.grades(week.to_assign_id()).await,
+        None => ClassroomGrades {
+            stale: true,
+            error: Some("GitHub Classroom client isn't configured".to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+// Repositories of the exercise of `week`, including those of students who haven't submitted yet
+pub async fn week_repositories(week: i32) -> Result<Vec<AcceptedAssignment>, ClassroomError> {
+    let Some(week) = WEEK::from_number(week) else {
+        return Ok(Vec::new());
+    };
+    match ClassroomClient::shared() {
+        Some(client) => client.accepted_assignments(week.to_assign_id()).await,
+        None => Err(ClassroomError::MissingToken),
+    }
+}
@@ -0,0 +1,126 @@
+use crate::database::audit::audit_timestamp;
+use crate::database::classroom_sync::{SyncChange, apply_classroom_grades, record_sync};
+use crate::handlers::universal::reload_state_if_active;
+use crate::utils::classroom::ClassroomClient;
+use crate::utils::cohort::{CohortConfig, load_cohorts};
+use crate::utils::outbox_worker::env_number;
+use crate::utils::session::Actor;
+use crate::utils::types::Table;
+use actix_web::web;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub cohort: String,
+    pub synced_at: String,
+    pub changes: Vec<SyncChange>,
+    // One entry per week that couldn't be synced
+    pub errors: Vec<String>,
+}
+
+// Pulls the grades of every bound assignment of `cohort` and updates the exercise flags.
+// `refresh` skips the grade cache. The outcome is recorded in the cohort database.
+pub async fn sync_cohort(
+    client: Option<&ClassroomClient>,
+    cohort: &CohortConfig,
+    actor: &str,
+    refresh: bool,
+) -> SyncReport {
+    let db_path = cohort.db_path();
+    let mut report = SyncReport {
+        cohort: cohort.name.clone(),
+        synced_at: audit_timestamp(),
+        ..Default::default()
+    };
+    match client {
+        None => report
+            .errors
+            .push("GitHub Classroom client isn't configured".to_string()),
+        Some(client) => {
+            for (week, assignment_id) in cohort.assignment_bindings() {
+                let grades = if refresh {
+                    client.refresh(assignment_id).await
+                } else {
+                    client.grades(assignment_id).await
+                };
+                if let Some(e) = grades.error {
+                    report.errors.push(format!("Week {}: {}", week, e));
+                    continue;
+                }
+                match apply_classroom_grades(&db_path, week, &grades.assignments, actor) {
+                    Ok(changes) => report.changes.extend(changes),
+                    Err(e) => report.errors.push(format!("Week {}: {}", week, e)),
+                }
+            }
+        }
+    }
+
+    let error = (!report.errors.is_empty()).then(|| report.errors.join("; "));
+    if let Err(e) = record_sync(
+        &db_path,
+        &report.synced_at,
+        report.changes.len(),
+        error.as_deref(),
+    ) {
+        error!("Couldn't record the {} Classroom sync: {}", cohort.name, e);
+    }
+    report
+}
+
+// Runs `sync_cohort` on `runtime` from a thread outside of it. `client` is only called inside
+// the runtime, since building the Octocrab client spawns tasks.
+pub fn sync_cohort_on<'a>(
+    runtime: &tokio::runtime::Runtime,
+    client: impl FnOnce() -> Option<&'a ClassroomClient>,
+    cohort: &CohortConfig,
+    actor: &str,
+) -> SyncReport {
+    runtime.block_on(async { sync_cohort(client(), cohort, actor, false).await })
+}
+
+// Syncs every registered cohort every `CLASSROOM_SYNC_SECONDS` (default 900, 0 turns it off)
+// and reloads the in-memory table when the active cohort changed
+pub fn start_classroom_sync_thread(state: web::Data<Mutex<Table>>) {
+    let interval = env_number("CLASSROOM_SYNC_SECONDS", 900);
+    if interval == 0 {
+        info!("Classroom sync is turned off");
+        return;
+    }
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                error!("Couldn't start the Classroom sync runtime: {}", e);
+                return;
+            }
+        };
+        let actor = Actor::System("classroom-sync".to_string()).audit_name();
+        loop {
+            for cohort in load_cohorts() {
+                let db_path = cohort.db_path();
+                if !db_path.exists() {
+                    continue;
+                }
+                let report = sync_cohort_on(&runtime, ClassroomClient::shared, &cohort, &actor);
+                for e in &report.errors {
+                    warn!("Classroom sync of {}: {}", cohort.name, e);
+                }
+                if !report.changes.is_empty() {
+                    info!(
+                        "Classroom sync updated {} fields of {}",
+                        report.changes.len(),
+                        cohort.name
+                    );
+                    reload_state_if_active(&state, &db_path);
+                }
+            }
+            std::thread::sleep(Duration::from_secs(interval));
+        }
+    });
+}
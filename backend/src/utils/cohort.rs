@@ -0,0 +1,103 @@
+use crate::database::reviews::{ReviewCriterion, default_review_criteria};
+use crate::utils::certificates::CertificateRules;
+use crate::utils::classroom::default_assignment_bindings;
+use crate::utils::registration::RegistrationRules;
+use actix_web::error::ErrorNotFound;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// One cohort known to the server. The registry is read from `COHORTS_FILE` (default
+// `cohorts.json`), a JSON array of these; the built-in cohorts are used if it's missing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CohortConfig {
+    // Short name, e.g. "PB"
+    pub name: String,
+    // SQLite database file, e.g. "pb_cohort.db"
+    pub db: String,
+    // Where the feedback form responses come from: an http(s) CSV export URL or a local CSV path
+    #[serde(default)]
+    pub feedback_source: Option<String>,
+    // JSON file mapping feedback sheet headers to response fields; the built-in mapping if unset
+    #[serde(default)]
+    pub feedback_mapping: Option<String>,
+    #[serde(default)]
+    pub certificate: CertificateRules,
+    #[serde(default)]
+    pub registration: RegistrationRules,
+    #[serde(default = "default_review_criteria")]
+    pub review_criteria: Vec<ReviewCriterion>,
+    // GitHub Classroom assignment id of each week's exercise; the built-in ones if empty
+    #[serde(default)]
+    pub classroom_assignments: BTreeMap<i32, u32>,
+}
+
+impl CohortConfig {
+    fn new(name: &str, db: &str) -> Self {
+        CohortConfig {
+            name: name.to_string(),
+            db: db.to_string(),
+            feedback_source: None,
+            feedback_mapping: None,
+            certificate: CertificateRules::default(),
+            registration: RegistrationRules::default(),
+            review_criteria: default_review_criteria(),
+            classroom_assignments: BTreeMap::new(),
+        }
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        PathBuf::from(&self.db)
+    }
+
+    pub fn assignment_bindings(&self) -> BTreeMap<i32, u32> {
+        if self.classroom_assignments.is_empty() {
+            default_assignment_bindings()
+        } else {
+            self.classroom_assignments.clone()
+        }
+    }
+}
+
+fn default_cohorts() -> Vec<CohortConfig> {
+    vec![
+        CohortConfig::new("BPD", "bpd_cohort.db"),
+        CohortConfig::new("PB", "pb_cohort.db"),
+        CohortConfig::new("LBTCL", "lbtcl_cohort.db"),
+        CohortConfig::new("MB", "mb_cohort.db"),
+    ]
+}
+
+pub fn load_cohorts() -> Vec<CohortConfig> {
+    let path = env::var("COHORTS_FILE").unwrap_or_else(|_| "cohorts.json".to_string());
+    match fs::read_to_string(&path) {
+        Ok(text) => match serde_json::from_str(&text) {
+            Ok(cohorts) => cohorts,
+            Err(e) => {
+                warn!("Invalid cohort registry {}: {}. Using defaults.", path, e);
+                default_cohorts()
+            }
+        },
+        Err(_) => default_cohorts(),
+    }
+}
+
+// Looks a cohort up by name (case-insensitive) or by its database file name
+pub fn find_cohort(key: &str) -> Option<CohortConfig> {
+    load_cohorts()
+        .into_iter()
+        .find(|c| c.name.eq_ignore_ascii_case(key) || c.db == key)
+}
+
+// The cohort `key` for handlers; unknown cohorts are a 404
+pub fn cohort_config(key: &str) -> Result<CohortConfig, actix_web::Error> {
+    find_cohort(key).ok_or_else(|| ErrorNotFound("Unknown cohort"))
+}
+
+// Database of the cohort `key` for handlers; unknown cohorts are a 404
+pub fn cohort_db(key: &str) -> Result<PathBuf, actix_web::Error> {
+    cohort_config(key).map(|cohort| cohort.db_path())
+}
@@ -0,0 +1,298 @@
+use crate::database::appeals::{Appeal, AppealError, list_appeals};
+use crate::database::enrollment::{EnrollmentError, inactive_emails};
+use crate::database::operations::read_from_db;
+use crate::utils::classroom::week_grades;
+use crate::utils::constants::TA_EMAILS;
+use crate::utils::notifications::{Channel, NotificationConfig, TA_DIGEST, queue_notification};
+use crate::utils::types::{AppError, RowData};
+use log::warn;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestStudent {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestGroup {
+    pub group: String,
+    pub members: Vec<DigestStudent>,
+}
+
+// What a TA needs to know about their groups for a week. Absences and exercises are those of
+// the week before.
+#[derive(Debug, Serialize)]
+pub struct TaDigest {
+    pub cohort: String,
+    pub week: i32,
+    pub ta: String,
+    pub groups: Vec<DigestGroup>,
+    pub absent_last_week: Vec<DigestStudent>,
+    pub missing_exercise: Vec<DigestStudent>,
+    pub pending_appeals: Vec<Appeal>,
+    // Set when GitHub Classroom couldn't be reached and exercises come from the table only
+    pub classroom_error: Option<String>,
+}
+
+impl TaDigest {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // Plain-text version for email and Discord
+    pub fn to_text(&self) -> String {
+        let names = |students: &[DigestStudent]| {
+            if students.is_empty() {
+                "nobody".to_string()
+            } else {
+                students
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        let mut text = String::new();
+        for group in &self.groups {
+            text.push_str(&format!("{}: {}\n", group.group, names(&group.members)));
+        }
+        if self.week > 1 {
+            text.push_str(&format!(
+                "\nAbsent in week {}: {}\n",
+                self.week - 1,
+                names(&self.absent_last_week)
+            ));
+            text.push_str(&format!(
+                "Week {} exercise not submitted: {}\n",
+                self.week - 1,
+                names(&self.missing_exercise)
+            ));
+        }
+        if let Some(e) = &self.classroom_error {
+            text.push_str(&format!("(GitHub Classroom unavailable: {})\n", e));
+        }
+        if self.pending_appeals.is_empty() {
+            text.push_str("\nNo pending appeals.\n");
+        } else {
+            text.push_str("\nPending appeals:\n");
+            for appeal in &self.pending_appeals {
+                text.push_str(&format!(
+                    "- {}, week {} {}: {}\n",
+                    appeal.student, appeal.week, appeal.criterion, appeal.comment
+                ));
+            }
+        }
+        text
+    }
+}
+
+fn app_error(e: impl std::error::Error + Send + Sync + 'static) -> AppError {
+    AppError::Io(std::io::Error::other(e))
+}
+
+// GitHub handles of registered participants by lowercase email
+fn github_handles(db_path: &Path) -> Result<HashMap<String, String>, AppError> {
+    let conn = Connection::open(db_path)?;
+    let has_participants: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'participants'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_participants {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT lower(email), lower(github) FROM participants
+         WHERE github IS NOT NULL AND github != ''",
+    )?;
+    let handles = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(handles)
+}
+
+// Lowercase GitHub handles that submitted the exercise of `week`. Stale cached grades are used
+// if there are any; an error means there's nothing to go on.
+pub async fn classroom_submissions(week: i32) -> Result<HashSet<String>, String> {
+    let grades = week_grades(week).await;
+    if let Some(e) = grades.error
+        && grades.fetched_at.is_none()
+    {
+        return Err(e);
+    }
+    Ok(grades
+        .assignments
+        .iter()
+        .filter(|a| a.is_submitted() && a.get_week_pattern() == Some(week as u32))
+        .map(|a| a.github_username.to_lowercase())
+        .collect())
+}
+
+// Builds the digest of `ta` for `week`. `submissions` are the Classroom submissions of the week
+// before, or the error fetching them; the table's `exercise_submitted` counts either way.
+pub fn build_digest(
+    db_path: &Path,
+    cohort: &str,
+    week: i32,
+    ta: &str,
+    submissions: &Result<HashSet<String>, String>,
+) -> Result<TaDigest, AppError> {
+    let inactive = inactive_emails(db_path).map_err(|e| match e {
+        EnrollmentError::App(e) => e,
+        e => app_error(e),
+    })?;
+    let rows = read_from_db(&db_path.to_path_buf())?.rows;
+    let is_active = |row: &RowData| !inactive.contains(&row.mail.trim().to_lowercase());
+    let student = |row: &RowData| DigestStudent {
+        name: row.name.clone(),
+        email: row.mail.trim().to_string(),
+    };
+
+    let mine: Vec<&RowData> = rows
+        .iter()
+        .filter(|row| row.week == week && !row.group_id.is_empty() && is_active(row))
+        .filter(|row| {
+            row.ta
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(ta))
+        })
+        .collect();
+    let mut groups: BTreeMap<&str, Vec<DigestStudent>> = BTreeMap::new();
+    for row in &mine {
+        groups.entry(&row.group_id).or_default().push(student(row));
+    }
+    let names: BTreeSet<&str> = mine.iter().map(|row| row.name.as_str()).collect();
+    let emails: HashSet<String> = mine
+        .iter()
+        .map(|row| row.mail.trim().to_lowercase())
+        .collect();
+
+    let last_week: Vec<&RowData> = rows
+        .iter()
+        .filter(|row| row.week == week - 1 && names.contains(row.name.as_str()))
+        .collect();
+    let absent_last_week = last_week
+        .iter()
+        .filter(|row| row.attendance.as_deref() != Some("yes"))
+        .map(|row| student(row))
+        .collect();
+    let handles = github_handles(db_path)?;
+    let missing_exercise = last_week
+        .iter()
+        .filter(|row| row.exercise_submitted.as_deref() != Some("yes"))
+        .filter(|row| {
+            let handle = handles.get(&row.mail.trim().to_lowercase());
+            !matches!((submissions, handle), (Ok(submitted), Some(h)) if submitted.contains(h))
+        })
+        .map(|row| student(row))
+        .collect();
+
+    let pending_appeals = list_appeals(db_path, Some("pending"), None)
+        .map_err(|e| match e {
+            AppealError::App(e) => e,
+            e => app_error(e),
+        })?
+        .into_iter()
+        .filter(|a| emails.contains(&a.email.trim().to_lowercase()))
+        .collect();
+
+    Ok(TaDigest {
+        cohort: cohort.to_string(),
+        week,
+        ta: ta.to_string(),
+        groups: groups
+            .into_iter()
+            .map(|(group, members)| DigestGroup {
+                group: group.to_string(),
+                members,
+            })
+            .collect(),
+        absent_last_week,
+        missing_exercise,
+        pending_appeals,
+        classroom_error: submissions.as_ref().err().cloned(),
+    })
+}
+
+// Channels digests go out on, from `DIGEST_CHANNELS` (default `email`, empty to turn digests
+// off). Email goes to the TA's address.
+pub fn digest_config() -> NotificationConfig {
+    let mut config = NotificationConfig::from_env();
+    config.channels = env::var("DIGEST_CHANNELS")
+        .unwrap_or_else(|_| "email".to_string())
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .filter_map(|name| match name.parse::<Channel>() {
+            Ok(Channel::Discord) if config.discord_webhook_url.is_none() => {
+                warn!("DIGEST_CHANNELS includes discord but DISCORD_WEBHOOK_URL is not set");
+                None
+            }
+            Ok(channel) => Some(channel),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect();
+    config
+}
+
+// Queues the digest of every TA with groups in `week`. Returns the TAs it was queued for.
+pub fn queue_ta_digests(
+    db_path: &Path,
+    cohort: &str,
+    week: i32,
+    submissions: &Result<HashSet<String>, String>,
+    config: &NotificationConfig,
+) -> Result<Vec<String>, AppError> {
+    let tas: BTreeSet<String> = read_from_db(&db_path.to_path_buf())?
+        .rows
+        .into_iter()
+        .filter(|row| row.week == week && !row.group_id.is_empty())
+        .filter_map(|row| row.ta)
+        .collect();
+
+    let mut digests = Vec::new();
+    for ta in tas {
+        let digest = build_digest(db_path, cohort, week, &ta, submissions)?;
+        if !digest.is_empty() {
+            digests.push(digest);
+        }
+    }
+
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    let mut queued = Vec::new();
+    for digest in digests {
+        let vars = BTreeMap::from([
+            ("cohort", cohort.to_string()),
+            ("week", week.to_string()),
+            ("ta", digest.ta.clone()),
+            ("digest", digest.to_text()),
+        ]);
+        for channel in &config.channels {
+            let to = match channel {
+                Channel::Email => {
+                    let Some((email, _)) = TA_EMAILS
+                        .iter()
+                        .find(|(_, name)| name.eq_ignore_ascii_case(&digest.ta))
+                    else {
+                        warn!("No email for TA {}, skipping their digest", digest.ta);
+                        continue;
+                    };
+                    email.to_string()
+                }
+                Channel::Discord => String::new(),
+            };
+            queue_notification(&tx, config, *channel, TA_DIGEST, &to, &vars)?;
+        }
+        queued.push(digest.ta);
+    }
+    tx.commit()?;
+    Ok(queued)
+}
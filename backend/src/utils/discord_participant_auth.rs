@@ -1,10 +1,12 @@
 use crate::database::operations::match_discord_username;
 use crate::utils::constants::get_auth_token;
+use crate::utils::session::{Actor, SessionStore};
 use actix_web::{HttpResponse, Responder, Result, error::ErrorInternalServerError, get, web};
 use reqwest::Client;
 use serde::Deserialize;
 use std::env;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 #[derive(Deserialize)]
 pub struct OAuthQuery {
@@ -30,7 +32,10 @@ struct Member {
 }
 
 #[get("/participant/callback")]
-pub async fn discord_participant_oauth(query: web::Query<OAuthQuery>) -> Result<impl Responder> {
+pub async fn discord_participant_oauth(
+    query: web::Query<OAuthQuery>,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<impl Responder> {
     let client_id = env::var("DISCORD_CLIENT_ID").expect("Missing DISCORD_CLIENT_ID");
     let client_secret = env::var("DISCORD_CLIENT_SECRET").expect("Missing DISCORD_CLIENT_SECRET");
     let redirect_uri =
@@ -168,28 +173,38 @@ pub async fn discord_participant_oauth(query: web::Query<OAuthQuery>) -> Result<
     let db_path = PathBuf::from("pb_cohort.db");
     println!("Looking for database at: {:?}", db_path.canonicalize());
     println!("Current working directory: {:?}", std::env::current_dir());
-    let user_in_db = match match_discord_username(&db_path, &user.username, user.email.clone()) {
+    let participant = match match_discord_username(&db_path, &user.username, user.email.clone()) {
         Ok(participant) => {
             println!("User found in pb_cohort database: {}", participant.name);
-            true
+            Some(participant)
         }
         Err(e) => {
             println!(
                 "User not found in pb_cohort database: {} - Error: {:?}",
                 user.username, e
             );
-            false
+            None
         }
     };
 
-    let redirect_url = if has_pb_role && user_in_db {
+    let redirect_url = if let Some(participant) = participant.filter(|_| has_pb_role) {
         // Redirect to StudentDetailPage with student parameter and participant token
         let encoded_email = urlencoding::encode(user.email.as_deref().unwrap_or(""));
         let encoded_username = urlencoding::encode(&user.username);
         let participant_token = get_auth_token("participant");
+        // Session for the /me endpoints, bound to the participant's registered email
+        let session = sessions.lock().unwrap().create_for_cohort(
+            Actor::Participant(participant.email),
+            &db_path.to_string_lossy(),
+        );
         format!(
-            "{}/cohortSelector?student={}&auth=discord&email={}&username={}&token={}&role=participant",
-            student_url, encoded_username, encoded_email, encoded_username, participant_token
+            "{}/cohortSelector?student={}&auth=discord&email={}&username={}&token={}&role=participant&session={}",
+            student_url,
+            encoded_username,
+            encoded_email,
+            encoded_username,
+            participant_token,
+            session
         )
     } else {
         // Unauthorized page - either no PB role or not in database
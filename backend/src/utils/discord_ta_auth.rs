@@ -1,8 +1,10 @@
 use crate::utils::constants::get_auth_token;
+use crate::utils::session::{Actor, SessionStore};
 use actix_web::{HttpResponse, Responder, Result, error::ErrorInternalServerError, get, web};
 use reqwest::Client;
 use serde::Deserialize;
 use std::env;
+use std::sync::Mutex;
 
 #[derive(Deserialize)]
 pub struct OAuthQuery {
@@ -28,7 +30,10 @@ struct Member {
 }
 
 #[get("/callback")]
-pub async fn discord_ta_oauth(query: web::Query<OAuthQuery>) -> Result<impl Responder> {
+pub async fn discord_ta_oauth(
+    query: web::Query<OAuthQuery>,
+    sessions: web::Data<Mutex<SessionStore>>,
+) -> Result<impl Responder> {
     let client_id = env::var("DISCORD_CLIENT_ID").expect("Missing DISCORD_CLIENT_ID");
     let client_secret = env::var("DISCORD_CLIENT_SECRET").expect("Missing DISCORD_CLIENT_SECRET");
     let redirect_uri = env::var("DISCORD_TA_URI").expect("Missing DISCORD_REDIRECT_URI");
@@ -165,7 +170,14 @@ pub async fn discord_ta_oauth(query: web::Query<OAuthQuery>) -> Result<impl Resp
     let redirect_url = if has_ta_role {
         // Redirect back to your login page with token or flags
         let encoded_token = urlencoding::encode(&auth_token);
-        format!("{}/select?auth=discord&token={}", ta_url, encoded_token)
+        let session = sessions
+            .lock()
+            .unwrap()
+            .create(Actor::Ta(user.username.clone()));
+        format!(
+            "{}/select?auth=discord&token={}&session={}",
+            ta_url, encoded_token, session
+        )
     } else {
         // Unauthorized page - either no TA role or not in database
         let reason = if !has_ta_role {
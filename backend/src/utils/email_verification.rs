@@ -0,0 +1,95 @@
+use crate::database::outbox::OutboxMessage;
+use crate::utils::mailer::{SmtpConfig, send_email};
+use crate::utils::outbox_worker::{DeliveryReport, RetryPolicy, deliver_outbox, env_number};
+use crate::utils::types::AppError;
+use serde::Deserialize;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+pub const VERIFICATION_EMAIL: &str = "verification_email";
+
+// Verification settings, read from the environment:
+// - `VERIFICATION_LINK_BASE`: public URL of this server, used in the link (default
+//   `http://127.0.0.1:8081`)
+// - `VERIFICATION_CODE_HOURS`: how long a code stays valid (default 48)
+// - `VERIFICATION_EMAIL_MAX_ATTEMPTS` / `VERIFICATION_EMAIL_RETRY_SECONDS`: retries of the email
+#[derive(Debug, Clone)]
+pub struct VerificationConfig {
+    pub link_base: String,
+    pub code_valid_for: Duration,
+    pub retry: RetryPolicy,
+}
+
+impl VerificationConfig {
+    pub fn from_env() -> Self {
+        VerificationConfig {
+            link_base: env::var("VERIFICATION_LINK_BASE")
+                .unwrap_or_else(|_| "http://127.0.0.1:8081".to_string()),
+            code_valid_for: Duration::from_secs(env_number("VERIFICATION_CODE_HOURS", 48) * 3600),
+            retry: RetryPolicy::from_env("VERIFICATION_EMAIL"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VerificationPayload {
+    name: String,
+    email: String,
+    cohort: String,
+    code: String,
+}
+
+// Subject and body of the email for a queued verification
+pub fn render_verification(
+    config: &VerificationConfig,
+    payload: &str,
+) -> Result<(String, String), String> {
+    let payload: VerificationPayload = serde_json::from_str(payload).map_err(|e| e.to_string())?;
+    let link = format!(
+        "{}/verify/{}?email={}&code={}",
+        config.link_base.trim_end_matches('/'),
+        urlencoding::encode(&payload.cohort),
+        urlencoding::encode(&payload.email),
+        payload.code
+    );
+    let body = format!(
+        "Hi {},\n\n\
+         Thanks for registering for the Bitshala {} cohort. Confirm your email by opening\n\n\
+         {}\n\n\
+         or by entering the code {} on the registration page. The code is valid for {} hours.\n\n\
+         Once your email is confirmed you'll get your Discord invite.\n",
+        payload.name,
+        payload.cohort,
+        link,
+        payload.code,
+        config.code_valid_for.as_secs() / 3600
+    );
+    Ok((
+        format!(
+            "Confirm your email for the Bitshala {} cohort",
+            payload.cohort
+        ),
+        body,
+    ))
+}
+
+fn send_verification(
+    smtp: &SmtpConfig,
+    config: &VerificationConfig,
+    message: &OutboxMessage,
+) -> Result<(), String> {
+    let (subject, body) = render_verification(config, &message.payload)?;
+    send_email(smtp, &message.email, &subject, &body).map_err(|e| e.to_string())
+}
+
+// Sends every due verification email of one cohort database. Blocking.
+pub fn deliver_verification_emails(
+    db_path: &Path,
+    smtp: &SmtpConfig,
+    config: &VerificationConfig,
+) -> Result<DeliveryReport, AppError> {
+    deliver_outbox(db_path, VERIFICATION_EMAIL, &config.retry, |message| {
+        send_verification(smtp, config, message)
+    })
+}
@@ -0,0 +1,365 @@
+use crate::utils::cohort::{CohortConfig, load_cohorts};
+use crate::utils::feedback_links::link_feedback_responses;
+use crate::utils::schedule::{Schedule, spawn_scheduled};
+use crate::utils::types::FEEDBACK_FIELDS;
+use log::{error, info, warn};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use thiserror::Error;
+
+// Runs when `FEEDBACK_IMPORT_SCHEDULE` isn't set: every hour
+const DEFAULT_FEEDBACK_IMPORT_SCHEDULE: &str = "0 * * * *";
+
+#[derive(Debug, Error)]
+pub enum FeedbackImportError {
+    #[error("Cohort {0} has no feedback source configured")]
+    NoSource(String),
+    #[error("Failed to download feedback: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Feedback source returned no responses")]
+    Empty,
+    #[error("Invalid feedback mapping: {0}")]
+    Mapping(String),
+}
+
+// Downloads (http/https) or reads (anything else) the CSV behind a feedback source
+pub fn fetch_feedback_csv(source: &str) -> Result<Vec<u8>, FeedbackImportError> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let response = reqwest::blocking::get(source)?.error_for_status()?;
+        Ok(response.bytes()?.to_vec())
+    } else {
+        Ok(fs::read(source)?)
+    }
+}
+
+// Sheet headers of the feedback form the cohorts have used so far
+const DEFAULT_FEEDBACK_MAPPING: &[(&str, &str)] = &[
+    ("Timestamp", "timestamp"),
+    ("Email Address", "email"),
+    ("Email", "email"),
+    ("Discord Name", "discord_name"),
+    ("Name on certificate", "name_on_certificate"),
+    ("Academic background", "academic_background"),
+    ("Skills", "skills"),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Session Instructions ]",
+        "session_instructions",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Study Material (book & questions) ]",
+        "study_material",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Group Discussions]",
+        "group_discussions",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Lounge Discussions]",
+        "lounge_discussions",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Deputy]",
+        "deputy",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Teaching Assistants]",
+        "teaching_assistants",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Bitshala clubs]",
+        "bitshala_clubs",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Bitdev Meetups]",
+        "bitdev_meetups",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Bitspace]",
+        "bitspace",
+    ),
+    (
+        "Which component of cohort did you find of help (or not so much)? [Fellowships ]",
+        "fellowships",
+    ),
+    (
+        "What were your expectations from the cohort?",
+        "expectations",
+    ),
+    (
+        "What could we do help you have a better Cohort experience. Please give us your ideas, we need 'em.",
+        "improvement_ideas",
+    ),
+    (
+        "What kind of opportunities do you wish to pursue in Bitcoin? ",
+        "bitcoin_opportunities",
+    ),
+    (
+        "Any project amongst our fellowships (https://bitshala.org/fellowship/) excite you? ",
+        "fellowship_projects",
+    ),
+    (
+        "(Optional) What would be your ideal bitcoin project and your role in it? You may or may not chose an existing bitcoin/bitshala project, or even choose your own project.\nP.S. - We have a few internships at Bitshala and fellowships at Bitshala incubated projects!",
+        "ideal_project",
+    ),
+    (
+        "(Optional) We'd really appreciate it if you can share a testimonial. It might go at Bitshala website.",
+        "testimonial",
+    ),
+];
+
+// Maps feedback sheet headers to `FeedbackResponse` fields. Several headers may map to the
+// same field (e.g. a question that was reworded between cohorts); the first one in the sheet wins.
+#[derive(Debug, Clone)]
+pub struct FeedbackMapping {
+    columns: Vec<(String, String)>,
+}
+
+// Headers are compared ignoring case, `_` vs space and runs of whitespace, so both the raw
+// sheet headers and the old underscored column names match
+fn normalize_header(header: &str) -> String {
+    header
+        .replace('_', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Default for FeedbackMapping {
+    fn default() -> Self {
+        FeedbackMapping {
+            columns: DEFAULT_FEEDBACK_MAPPING
+                .iter()
+                .map(|(header, field)| (header.to_string(), field.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl FeedbackMapping {
+    // Reads a JSON object of `"sheet header": "field"` pairs
+    pub fn load(path: &Path) -> Result<Self, FeedbackImportError> {
+        let text = fs::read_to_string(path)?;
+        let columns: BTreeMap<String, String> = serde_json::from_str(&text)
+            .map_err(|e| FeedbackImportError::Mapping(format!("{}: {}", path.display(), e)))?;
+        Self::new(columns.into_iter().collect())
+    }
+
+    pub fn new(columns: Vec<(String, String)>) -> Result<Self, FeedbackImportError> {
+        if let Some((header, field)) = columns
+            .iter()
+            .find(|(_, field)| !FEEDBACK_FIELDS.contains(&field.as_str()))
+        {
+            return Err(FeedbackImportError::Mapping(format!(
+                "{:?} is mapped to unknown field {:?}",
+                header, field
+            )));
+        }
+        Ok(FeedbackMapping { columns })
+    }
+
+    pub fn for_cohort(cohort: &CohortConfig) -> Result<Self, FeedbackImportError> {
+        match &cohort.feedback_mapping {
+            Some(path) => Self::load(Path::new(path)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn field_for(&self, header: &str) -> Option<&str> {
+        let header = normalize_header(header);
+        self.columns
+            .iter()
+            .find(|(mapped, _)| normalize_header(mapped) == header)
+            .map(|(_, field)| field.as_str())
+    }
+}
+
+// What an import did with the sheet's columns
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    // Responses matched to a student on the roster
+    pub linked: usize,
+    // Sheet columns with no mapping; their answers are kept in `extra`
+    pub unmapped_columns: Vec<String>,
+    // Fields no sheet column was mapped to; they are left empty
+    pub missing_fields: Vec<String>,
+    // Set when the responses were imported but couldn't be linked to the roster
+    pub link_error: Option<String>,
+}
+
+// Replaces the `responses` table of `db_path` with the rows of `data`, mapped through `mapping`.
+// Everything is parsed before the database is touched and the swap happens in one transaction,
+// so a bad download leaves the previous responses in place.
+pub fn import_feedback_csv(
+    db_path: &Path,
+    data: &[u8],
+    mapping: &FeedbackMapping,
+) -> Result<ImportReport, FeedbackImportError> {
+    let mut rdr = csv::Reader::from_reader(Cursor::new(data));
+    let headers: Vec<String> = rdr.headers()?.iter().map(str::to_string).collect();
+    let records = rdr.records().collect::<Result<Vec<_>, _>>()?;
+    if headers.is_empty() || records.is_empty() {
+        return Err(FeedbackImportError::Empty);
+    }
+
+    // Column index of each field, and the columns left over
+    let mut field_columns: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut extra_columns = Vec::new();
+    for (index, header) in headers.iter().enumerate() {
+        match mapping.field_for(header) {
+            Some(field) => {
+                field_columns.entry(field).or_insert(index);
+            }
+            None => extra_columns.push(index),
+        }
+    }
+    if field_columns.is_empty() {
+        return Err(FeedbackImportError::Mapping(format!(
+            "none of the sheet columns are mapped: {:?}",
+            headers
+        )));
+    }
+
+    let mut report = ImportReport {
+        imported: records.len(),
+        linked: 0,
+        unmapped_columns: extra_columns.iter().map(|&i| headers[i].clone()).collect(),
+        missing_fields: FEEDBACK_FIELDS
+            .iter()
+            .filter(|field| !field_columns.contains_key(*field))
+            .map(|field| field.to_string())
+            .collect(),
+        link_error: None,
+    };
+
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    tx.execute("DROP TABLE IF EXISTS responses", [])?;
+
+    let column_defs = FEEDBACK_FIELDS
+        .iter()
+        .map(|field| format!("{} TEXT", field))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tx.execute(
+        &format!(
+            "CREATE TABLE responses ({}, extra TEXT, student_name TEXT, link_method TEXT)",
+            column_defs
+        ),
+        [],
+    )?;
+
+    let sql = format!(
+        "INSERT INTO responses ({}, extra) VALUES ({})",
+        FEEDBACK_FIELDS.join(", "),
+        vec!["?"; FEEDBACK_FIELDS.len() + 1].join(", ")
+    );
+    {
+        let mut stmt = tx.prepare(&sql)?;
+        for record in &records {
+            let mut values: Vec<String> = FEEDBACK_FIELDS
+                .iter()
+                .map(|field| {
+                    field_columns
+                        .get(field)
+                        .and_then(|&i| record.get(i))
+                        .unwrap_or("")
+                        .to_string()
+                })
+                .collect();
+            let extra: BTreeMap<&str, &str> = extra_columns
+                .iter()
+                .filter_map(|&i| Some((headers[i].as_str(), record.get(i)?)))
+                .filter(|(_, answer)| !answer.is_empty())
+                .collect();
+            values.push(serde_json::to_string(&extra).unwrap_or_default());
+            stmt.execute(rusqlite::params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+
+    // The responses are in by now, so a linking failure doesn't fail the import
+    match link_feedback_responses(db_path) {
+        Ok(links) => report.linked = links.linked,
+        Err(e) => {
+            warn!(
+                "Imported feedback into {} but couldn't link it: {}",
+                db_path.display(),
+                e
+            );
+            report.link_error = Some(e.to_string());
+        }
+    }
+    Ok(report)
+}
+
+pub fn import_feedback(cohort: &CohortConfig) -> Result<ImportReport, FeedbackImportError> {
+    let source = cohort
+        .feedback_source
+        .as_deref()
+        .ok_or_else(|| FeedbackImportError::NoSource(cohort.name.clone()))?;
+
+    let mapping = FeedbackMapping::for_cohort(cohort)?;
+    let data = fetch_feedback_csv(source)?;
+    let report = import_feedback_csv(&cohort.db_path(), &data, &mapping)?;
+    info!(
+        "Imported {} feedback responses for {} from {} ({} linked to students)",
+        report.imported, cohort.name, source, report.linked
+    );
+    if !report.unmapped_columns.is_empty() {
+        warn!(
+            "Feedback columns for {} without a mapping (kept as extra answers): {:?}",
+            cohort.name, report.unmapped_columns
+        );
+    }
+    if !report.missing_fields.is_empty() {
+        warn!(
+            "Feedback fields for {} missing from the sheet: {:?}",
+            cohort.name, report.missing_fields
+        );
+    }
+    Ok(report)
+}
+
+// Imports feedback for every cohort that has a source; failures are logged and the
+// cohort keeps its previous responses
+pub fn import_all_feedback() {
+    for cohort in load_cohorts() {
+        if cohort.feedback_source.is_none() {
+            continue;
+        }
+        if let Err(e) = import_feedback(&cohort) {
+            error!(
+                "Feedback import for {} failed, keeping previous data: {}",
+                cohort.name, e
+            );
+        }
+    }
+}
+
+// Imports once in the background at startup, then on `FEEDBACK_IMPORT_SCHEDULE`
+pub fn start_feedback_import_thread() {
+    std::thread::spawn(import_all_feedback);
+
+    let expr = env::var("FEEDBACK_IMPORT_SCHEDULE")
+        .unwrap_or_else(|_| DEFAULT_FEEDBACK_IMPORT_SCHEDULE.to_string());
+    match expr.parse::<Schedule>() {
+        Ok(schedule) => spawn_scheduled("feedback import", schedule, import_all_feedback),
+        Err(e) => error!(
+            "Invalid FEEDBACK_IMPORT_SCHEDULE {:?}: {}. Scheduled imports are disabled.",
+            expr, e
+        ),
+    }
+}
@@ -0,0 +1,201 @@
+use crate::database::operations::match_discord_username;
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// A student on the cohort roster (week 0 row)
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterStudent {
+    pub name: String,
+    pub mail: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LinkReport {
+    pub linked: usize,
+    // Responses no student could be found for, as "name on certificate (discord name)"
+    pub unlinked: Vec<String>,
+}
+
+pub fn read_roster(conn: &Connection) -> Result<Vec<RosterStudent>> {
+    let mut stmt =
+        conn.prepare("SELECT DISTINCT name, mail FROM students WHERE week = 0 ORDER BY name")?;
+    stmt.query_map([], |row| {
+        Ok(RosterStudent {
+            name: row.get(0)?,
+            mail: row.get(1)?,
+        })
+    })?
+    .collect()
+}
+
+// Lowercase alphanumeric words in sorted order, so "Doe, Jane" and "jane doe" compare equal
+fn normalize_name(name: &str) -> String {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    words.sort();
+    words.join(" ")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Same name up to case, word order and punctuation, or a couple of typos away from exactly
+// one student. Short names need an exact match.
+fn fuzzy_match<'a>(name: &str, roster: &'a [RosterStudent]) -> Option<&'a RosterStudent> {
+    let name = normalize_name(name);
+    if name.is_empty() {
+        return None;
+    }
+    if let Some(student) = roster.iter().find(|s| normalize_name(&s.name) == name) {
+        return Some(student);
+    }
+    if name.len() < 6 {
+        return None;
+    }
+    let close: Vec<&RosterStudent> = roster
+        .iter()
+        .filter(|s| edit_distance(&normalize_name(&s.name), &name) <= 2)
+        .collect();
+    match close.as_slice() {
+        [student] => Some(student),
+        _ => None,
+    }
+}
+
+fn by_mail<'a>(mail: &str, roster: &'a [RosterStudent]) -> Option<&'a RosterStudent> {
+    let mail = mail.trim();
+    if mail.is_empty() {
+        return None;
+    }
+    roster.iter().find(|s| {
+        s.mail
+            .as_deref()
+            .is_some_and(|m| m.trim().eq_ignore_ascii_case(mail))
+    })
+}
+
+// Matches every response to a roster student and stores the link in `responses.student_name`
+// and `responses.link_method`: by email, then by Discord name through the participants table,
+// then by the name on the certificate or the Discord name.
+pub fn link_feedback_responses(db_path: &Path) -> Result<LinkReport> {
+    let mut conn = Connection::open(db_path)?;
+    let roster = read_roster(&conn)?;
+
+    let responses: Vec<(i64, String, String, String)> = {
+        let mut stmt =
+            conn.prepare("SELECT rowid, email, discord_name, name_on_certificate FROM responses")?;
+        stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            ))
+        })?
+        .collect::<Result<_>>()?
+    };
+
+    let tx = conn.transaction()?;
+    let mut report = LinkReport::default();
+    for (rowid, email, discord_name, certificate_name) in responses {
+        let by_discord = || {
+            if discord_name.trim().is_empty() {
+                return None;
+            }
+            let participant = match_discord_username(
+                &db_path.to_path_buf(),
+                discord_name.trim(),
+                Some(email.trim().to_string()).filter(|e| !e.is_empty()),
+            )
+            .ok()?;
+            by_mail(&participant.email, &roster).or_else(|| {
+                roster
+                    .iter()
+                    .find(|s| s.name.eq_ignore_ascii_case(participant.name.trim()))
+            })
+        };
+
+        let found = by_mail(&email, &roster)
+            .map(|s| (s, "email"))
+            .or_else(|| by_discord().map(|s| (s, "discord")))
+            .or_else(|| fuzzy_match(&certificate_name, &roster).map(|s| (s, "name")))
+            .or_else(|| fuzzy_match(&discord_name, &roster).map(|s| (s, "name")));
+
+        let (student_name, method) = match found {
+            Some((student, method)) => {
+                report.linked += 1;
+                (Some(student.name.clone()), Some(method))
+            }
+            None => {
+                report
+                    .unlinked
+                    .push(format!("{} ({})", certificate_name, discord_name));
+                (None, None)
+            }
+        };
+        tx.execute(
+            "UPDATE responses SET student_name = ?1, link_method = ?2 WHERE rowid = ?3",
+            params![student_name, method, rowid],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(report)
+}
+
+// Name on certificate of each student with a linked response (the latest non-empty one)
+pub fn feedback_by_student(conn: &Connection) -> Result<BTreeMap<String, String>> {
+    let imported: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'responses'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut linked = BTreeMap::new();
+    if !imported {
+        return Ok(linked);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT student_name, name_on_certificate FROM responses
+         WHERE student_name IS NOT NULL ORDER BY timestamp",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+        ))
+    })?;
+    for row in rows {
+        let (student, certificate_name) = row?;
+        let entry = linked.entry(student).or_insert_with(String::new);
+        if !certificate_name.trim().is_empty() {
+            *entry = certificate_name.trim().to_string();
+        }
+    }
+    Ok(linked)
+}
+
+// Roster students with no linked feedback response
+pub fn students_without_feedback(db_path: &Path) -> Result<Vec<RosterStudent>> {
+    let conn = Connection::open(db_path)?;
+    let linked = feedback_by_student(&conn)?;
+    Ok(read_roster(&conn)?
+        .into_iter()
+        .filter(|s| !linked.contains_key(&s.name))
+        .collect())
+}
@@ -0,0 +1,79 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::env;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("Invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("Couldn't build email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+// How mail leaves the server, read from the environment:
+// - `SMTP_HOST` / `SMTP_PORT`: the relay (default `localhost:1025`, where MailHog listens)
+// - `SMTP_TLS`: `none` (default), `starttls` or `tls`
+// - `SMTP_USERNAME` / `SMTP_PASSWORD`: credentials, if the relay wants them
+// - `SMTP_FROM`: sender (default `Bitshala <noreply@bitshala.org>`)
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+impl SmtpConfig {
+    pub fn from_env() -> Self {
+        SmtpConfig {
+            host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string()),
+            port: env::var("SMTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1025),
+            tls: env::var("SMTP_TLS").unwrap_or_else(|_| "none".to_string()),
+            username: env::var("SMTP_USERNAME").ok(),
+            password: env::var("SMTP_PASSWORD").ok(),
+            from: env::var("SMTP_FROM")
+                .unwrap_or_else(|_| "Bitshala <noreply@bitshala.org>".to_string()),
+        }
+    }
+
+    fn transport(&self) -> Result<SmtpTransport, MailError> {
+        let builder = match self.tls.to_lowercase().as_str() {
+            "tls" => SmtpTransport::relay(&self.host)?,
+            "starttls" => SmtpTransport::starttls_relay(&self.host)?,
+            _ => SmtpTransport::builder_dangerous(&self.host),
+        };
+        let mut builder = builder
+            .port(self.port)
+            .timeout(Some(Duration::from_secs(10)));
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+}
+
+// Sends a plain-text email. Blocking; run it off the async runtime.
+pub fn send_email(
+    config: &SmtpConfig,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), MailError> {
+    let message = Message::builder()
+        .from(config.from.parse::<Mailbox>()?)
+        .to(to.parse::<Mailbox>()?)
+        .subject(subject)
+        .body(body.to_string())?;
+    config.transport()?.send(&message)?;
+    Ok(())
+}
@@ -1,7 +1,22 @@
+pub mod archive;
 pub mod backup;
+pub mod backup_targets;
+pub mod bot_invites;
+pub mod certificates;
 pub mod classroom;
+pub mod classroom_sync;
+pub mod cohort;
 pub mod constants;
-pub mod csv_dump;
+pub mod digest;
 pub mod discord_participant_auth;
 pub mod discord_ta_auth;
+pub mod email_verification;
+pub mod feedback_import;
+pub mod feedback_links;
+pub mod mailer;
+pub mod notifications;
+pub mod outbox_worker;
+pub mod registration;
+pub mod schedule;
+pub mod session;
 pub mod types;
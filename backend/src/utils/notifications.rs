@@ -0,0 +1,348 @@
+use crate::database::notifications::queue_weekly_reminders;
+use crate::database::outbox::{OutboxMessage, enqueue_message};
+use crate::utils::cohort::load_cohorts;
+use crate::utils::mailer::{SmtpConfig, send_email};
+use crate::utils::outbox_worker::{DeliveryReport, RetryPolicy, deliver_outbox};
+use crate::utils::schedule::{Schedule, spawn_scheduled};
+use crate::utils::types::AppError;
+use log::{error, info, warn};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const NOTIFICATION: &str = "notification";
+
+const DEFAULT_REMINDER_SCHEDULE: &str = "0 10 * * 5";
+
+// Built-in templates. Each can be replaced by `<name>.txt` in `NOTIFICATION_TEMPLATES_DIR`,
+// whose first line is the subject and the rest the body.
+pub const GROUP_ASSIGNMENT: &str = "group_assignment";
+pub const WEEK_PUBLISHED: &str = "week_published";
+pub const WEEKLY_REMINDER: &str = "weekly_reminder";
+pub const REMINDER_ANNOUNCEMENT: &str = "reminder_announcement";
+pub const TA_DIGEST: &str = "ta_digest";
+
+// Discord rejects longer messages
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+// Where a notification goes. Email reaches each student; a Discord webhook posts one
+// announcement to the cohort channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Discord,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Email => write!(f, "email"),
+            Channel::Discord => write!(f, "discord"),
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "email" => Ok(Channel::Email),
+            "discord" => Ok(Channel::Discord),
+            other => Err(format!("Unknown notification channel {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template {
+    fn builtin(name: &str) -> Option<Template> {
+        let (subject, body) = match name {
+            GROUP_ASSIGNMENT => (
+                "{cohort} week {week}: you're in {group}",
+                "Hi {name},\n\n\
+                 Groups for week {week} of the {cohort} cohort are out. You're in {group} and \
+                 your TA is {ta}.\n\nSee you in the session!\n",
+            ),
+            WEEK_PUBLISHED => (
+                "{cohort} week {week} groups are out",
+                "Groups for week {week}:\n{groups}",
+            ),
+            WEEKLY_REMINDER => (
+                "{cohort} week {week} reminder",
+                "Hi {name},\n\n\
+                 A reminder that this week you're in {group} with {ta}. {exercise}\n",
+            ),
+            REMINDER_ANNOUNCEMENT => (
+                "{cohort} week {week} reminder",
+                "Week {week} sessions are coming up. {missing} students haven't submitted the \
+                 exercise yet.",
+            ),
+            TA_DIGEST => ("{cohort} week {week} digest for {ta}", "{digest}"),
+            _ => return None,
+        };
+        Some(Template {
+            subject: subject.to_string(),
+            body: body.to_string(),
+        })
+    }
+
+    // The template called `name`, from `dir` if it has one, else the built-in one
+    pub fn load(name: &str, dir: Option<&Path>) -> Option<Template> {
+        if let Some(dir) = dir {
+            let path = dir.join(format!("{}.txt", name));
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    let (subject, body) = text.split_once('\n').unwrap_or((&text, ""));
+                    return Some(Template {
+                        subject: subject.trim().to_string(),
+                        body: body.to_string(),
+                    });
+                }
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    warn!("Couldn't read template {}: {}", path.display(), e);
+                }
+                Err(_) => {}
+            }
+        }
+        Template::builtin(name)
+    }
+
+    // Replaces every `{key}` with its value. Unknown placeholders are left as they are.
+    pub fn render(&self, vars: &BTreeMap<&str, String>) -> (String, String) {
+        let fill = |text: &str| {
+            vars.iter().fold(text.to_string(), |text, (key, value)| {
+                text.replace(&format!("{{{}}}", key), value)
+            })
+        };
+        (fill(&self.subject), fill(&self.body))
+    }
+}
+
+// A rendered notification as it sits in the outbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub channel: Channel,
+    pub template: String,
+    // Email address; empty for channel announcements
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Something that can deliver a notification
+pub trait NotificationChannel {
+    fn name(&self) -> String;
+    fn send(&self, notification: &Notification) -> Result<(), String>;
+}
+
+pub struct EmailChannel {
+    pub smtp: SmtpConfig,
+}
+
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> String {
+        format!("smtp:{}:{}", self.smtp.host, self.smtp.port)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), String> {
+        send_email(
+            &self.smtp,
+            &notification.to,
+            &notification.subject,
+            &notification.body,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+pub struct DiscordWebhook {
+    pub url: String,
+    pub client: reqwest::blocking::Client,
+}
+
+impl NotificationChannel for DiscordWebhook {
+    fn name(&self) -> String {
+        "discord webhook".to_string()
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), String> {
+        let mut content = format!("**{}**\n{}", notification.subject, notification.body);
+        if content.chars().count() > DISCORD_MAX_LENGTH {
+            content = content.chars().take(DISCORD_MAX_LENGTH - 1).collect();
+            content.push('…');
+        }
+        self.client
+            .post(&self.url)
+            .json(&serde_json::json!({ "content": content }))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Notification settings, read from the environment:
+// - `NOTIFY_CHANNELS`: comma-separated channels to use, `email` and/or `discord` (default `email`)
+// - `DISCORD_WEBHOOK_URL`: webhook the Discord announcements are posted to
+// - `NOTIFICATION_TEMPLATES_DIR`: directory with template overrides
+// - `NOTIFICATION_MAX_ATTEMPTS` / `NOTIFICATION_RETRY_SECONDS`: retries of failed deliveries
+// Email goes through the `SMTP_*` relay.
+#[derive(Debug, Clone)]
+pub struct NotificationConfig {
+    pub channels: Vec<Channel>,
+    pub discord_webhook_url: Option<String>,
+    pub smtp: SmtpConfig,
+    pub templates_dir: Option<PathBuf>,
+    pub retry: RetryPolicy,
+}
+
+impl NotificationConfig {
+    pub fn from_env() -> Self {
+        let discord_webhook_url = env::var("DISCORD_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty());
+        let mut channels = Vec::new();
+        for name in env::var("NOTIFY_CHANNELS")
+            .unwrap_or_else(|_| "email".to_string())
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+        {
+            match name.parse::<Channel>() {
+                Ok(Channel::Discord) if discord_webhook_url.is_none() => {
+                    warn!("NOTIFY_CHANNELS includes discord but DISCORD_WEBHOOK_URL is not set")
+                }
+                Ok(channel) if !channels.contains(&channel) => channels.push(channel),
+                Ok(_) => {}
+                Err(e) => warn!("{}", e),
+            }
+        }
+        NotificationConfig {
+            channels,
+            discord_webhook_url,
+            smtp: SmtpConfig::from_env(),
+            templates_dir: env::var("NOTIFICATION_TEMPLATES_DIR")
+                .ok()
+                .map(PathBuf::from),
+            retry: RetryPolicy::from_env("NOTIFICATION"),
+        }
+    }
+
+    pub fn uses(&self, channel: Channel) -> bool {
+        self.channels.contains(&channel)
+    }
+}
+
+// Renders `template` and queues it for `channel`. Takes a connection so the notification
+// commits with the caller's writes.
+pub fn queue_notification(
+    conn: &Connection,
+    config: &NotificationConfig,
+    channel: Channel,
+    template: &str,
+    to: &str,
+    vars: &BTreeMap<&str, String>,
+) -> Result<(), AppError> {
+    let template_text = Template::load(template, config.templates_dir.as_deref())
+        .ok_or_else(|| AppError::Io(std::io::Error::other(format!("No template {}", template))))?;
+    let (subject, body) = template_text.render(vars);
+    let notification = Notification {
+        channel,
+        template: template.to_string(),
+        to: to.to_string(),
+        subject,
+        body,
+    };
+    enqueue_message(
+        conn,
+        NOTIFICATION,
+        to,
+        &serde_json::to_value(&notification).unwrap_or_default(),
+    )?;
+    Ok(())
+}
+
+// Sends every due notification of one cohort database through its channel. Blocking; run it
+// off the async runtime.
+pub fn deliver_notifications(
+    db_path: &Path,
+    config: &NotificationConfig,
+) -> Result<DeliveryReport, AppError> {
+    let email = EmailChannel {
+        smtp: config.smtp.clone(),
+    };
+    let discord = match &config.discord_webhook_url {
+        Some(url) => Some(DiscordWebhook {
+            url: url.clone(),
+            client: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .map_err(|e| AppError::Io(std::io::Error::other(e)))?,
+        }),
+        None => None,
+    };
+    deliver_outbox(
+        db_path,
+        NOTIFICATION,
+        &config.retry,
+        |message: &OutboxMessage| {
+            let notification: Notification =
+                serde_json::from_str(&message.payload).map_err(|e| e.to_string())?;
+            let adapter: &dyn NotificationChannel = match notification.channel {
+                Channel::Email => &email,
+                Channel::Discord => discord
+                    .as_ref()
+                    .ok_or_else(|| "DISCORD_WEBHOOK_URL is not set".to_string())?,
+            };
+            adapter
+                .send(&notification)
+                .map_err(|e| format!("{}: {}", adapter.name(), e))
+        },
+    )
+}
+
+fn remind_all_cohorts() {
+    let config = NotificationConfig::from_env();
+    for cohort in load_cohorts() {
+        let db_path = cohort.db_path();
+        if !db_path.exists() {
+            continue;
+        }
+        match queue_weekly_reminders(&db_path, &cohort.name, &config) {
+            Ok(report) if !report.notified.is_empty() => info!(
+                "Queued week {} reminders for {} students of {}",
+                report.week,
+                report.notified.len(),
+                cohort.name
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Couldn't queue reminders for {}: {}", cohort.name, e),
+        }
+    }
+}
+
+// Queues weekly reminders according to `NOTIFY_REMINDER_SCHEDULE` (a five-field cron
+// expression, default Fridays at 10:00). The outbox worker delivers them.
+pub fn start_reminder_thread() {
+    let expr = env::var("NOTIFY_REMINDER_SCHEDULE")
+        .unwrap_or_else(|_| DEFAULT_REMINDER_SCHEDULE.to_string());
+    match expr.parse::<Schedule>() {
+        Ok(schedule) => spawn_scheduled("weekly reminders", schedule, remind_all_cohorts),
+        Err(e) => error!(
+            "Invalid NOTIFY_REMINDER_SCHEDULE {:?}: {}. Weekly reminders are disabled.",
+            expr, e
+        ),
+    }
+}
@@ -0,0 +1,131 @@
+use crate::database::outbox::{OutboxMessage, due_messages, mark_attempt_failed, mark_delivered};
+use crate::utils::bot_invites::{BotInviteConfig, deliver_bot_invites};
+use crate::utils::cohort::load_cohorts;
+use crate::utils::email_verification::{VerificationConfig, deliver_verification_emails};
+use crate::utils::mailer::SmtpConfig;
+use crate::utils::notifications::{NotificationConfig, deliver_notifications};
+use crate::utils::types::AppError;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+// Longest wait between two attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+// How failed deliveries of one kind of message are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // Give up after this many failed attempts
+    pub max_attempts: i64,
+    // Wait after the first failure, doubled after each one up to 6 hours
+    pub retry_delay: Duration,
+}
+
+pub fn env_number(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl RetryPolicy {
+    // Reads `<PREFIX>_MAX_ATTEMPTS` (default 10) and `<PREFIX>_RETRY_SECONDS` (default 30)
+    pub fn from_env(prefix: &str) -> Self {
+        RetryPolicy {
+            max_attempts: env_number(&format!("{}_MAX_ATTEMPTS", prefix), 10) as i64,
+            retry_delay: Duration::from_secs(env_number(&format!("{}_RETRY_SECONDS", prefix), 30)),
+        }
+    }
+
+    // Wait before the next attempt after `attempts` failures
+    fn backoff(&self, attempts: i64) -> Duration {
+        let factor = 2u32.saturating_pow((attempts.max(1) - 1).min(31) as u32);
+        self.retry_delay.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DeliveryReport {
+    pub delivered: usize,
+    // Failed attempts that will be tried again
+    pub retrying: usize,
+    // Messages given up on
+    pub failed: usize,
+}
+
+// Hands every due message of `kind` to `send` and records the outcome. Blocking; run it off
+// the async runtime.
+pub fn deliver_outbox<F>(
+    db_path: &Path,
+    kind: &str,
+    policy: &RetryPolicy,
+    mut send: F,
+) -> Result<DeliveryReport, AppError>
+where
+    F: FnMut(&OutboxMessage) -> Result<(), String>,
+{
+    let mut report = DeliveryReport::default();
+    for message in due_messages(db_path, kind)? {
+        match send(&message) {
+            Ok(()) => {
+                mark_delivered(db_path, message.id)?;
+                info!("Delivered {} for {}", kind, message.email);
+                report.delivered += 1;
+            }
+            Err(e) => {
+                let attempts = message.attempts + 1;
+                if attempts >= policy.max_attempts {
+                    mark_attempt_failed(db_path, message.id, &e, None)?;
+                    error!(
+                        "Giving up on {} for {} after {} attempts: {}",
+                        kind, message.email, attempts, e
+                    );
+                    report.failed += 1;
+                } else {
+                    let delay = policy.backoff(attempts);
+                    mark_attempt_failed(db_path, message.id, &e, Some(delay))?;
+                    warn!(
+                        "{} for {} failed (attempt {}), retrying in {:?}: {}",
+                        kind, message.email, attempts, delay, e
+                    );
+                    report.retrying += 1;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn deliver_all(db_path: &Path) -> Result<(), AppError> {
+    deliver_bot_invites(db_path, &BotInviteConfig::from_env())?;
+    deliver_verification_emails(
+        db_path,
+        &SmtpConfig::from_env(),
+        &VerificationConfig::from_env(),
+    )?;
+    deliver_notifications(db_path, &NotificationConfig::from_env())?;
+    Ok(())
+}
+
+// Delivers pending outbox messages of every registered cohort every `OUTBOX_POLL_SECONDS`
+// (default 10)
+pub fn start_outbox_thread() {
+    std::thread::spawn(|| {
+        loop {
+            for cohort in load_cohorts() {
+                let db_path = cohort.db_path();
+                if !db_path.exists() {
+                    continue;
+                }
+                if let Err(e) = deliver_all(&db_path) {
+                    error!("Outbox delivery for {} failed: {}", cohort.name, e);
+                }
+            }
+            std::thread::sleep(Duration::from_secs(
+                env_number("OUTBOX_POLL_SECONDS", 10).max(1),
+            ));
+        }
+    });
+}
@@ -0,0 +1,123 @@
+use crate::database::audit::{audit_timestamp, normalize_timestamp};
+use crate::utils::cohort::{CohortConfig, find_cohort};
+use crate::utils::types::CohortParticipant;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// When a cohort takes registrations and how many, set per cohort under `registration` in
+// `cohorts.json`. Times are RFC 3339 or `YYYY-MM-DD` (midnight UTC); unset means no limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RegistrationRules {
+    pub opens_at: Option<String>,
+    pub closes_at: Option<String>,
+    // Registrations beyond this go on the waitlist
+    pub seats: Option<usize>,
+}
+
+impl RegistrationRules {
+    fn bound(&self, value: &Option<String>) -> Option<String> {
+        let value = value.as_deref()?;
+        let normalized = normalize_timestamp(value.trim());
+        if normalized.is_none() {
+            warn!("Ignoring invalid registration time {:?}", value);
+        }
+        normalized
+    }
+
+    // Why registration isn't open at `now` (a stored-format timestamp), if it isn't
+    pub fn closed_reason(&self, now: &str) -> Option<String> {
+        if let Some(opens_at) = self.bound(&self.opens_at)
+            && now < opens_at.as_str()
+        {
+            return Some(format!("Registration opens at {}", opens_at));
+        }
+        if let Some(closes_at) = self.bound(&self.closes_at)
+            && now >= closes_at.as_str()
+        {
+            return Some(format!("Registration closed at {}", closes_at));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RegistrationError {
+    #[error("Unknown cohort {0:?}")]
+    UnknownCohort(String),
+    #[error("{0}")]
+    Closed(String),
+    #[error("Invalid registration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(char::is_whitespace)
+        && domain.contains('.')
+        && domain.split('.').all(|part| !part.is_empty())
+}
+
+// GitHub usernames: up to 39 letters, digits and single hyphens, not starting or ending with one
+fn is_valid_github_handle(handle: &str) -> bool {
+    !handle.is_empty()
+        && handle.len() <= 39
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !handle.starts_with('-')
+        && !handle.ends_with('-')
+        && !handle.contains("--")
+}
+
+// Accepts `name`, `@name` and profile URLs
+fn normalize_github_handle(input: &str) -> String {
+    let handle = input.trim().trim_end_matches('/');
+    let handle = handle
+        .rsplit_once("github.com/")
+        .map_or(handle, |(_, rest)| rest);
+    handle.trim_start_matches('@').to_string()
+}
+
+// Checks that the cohort is taking registrations and the form is valid, normalizes email and
+// GitHub handle in place and returns the cohort. Form problems are reported together.
+pub fn validate_registration(
+    participant: &mut CohortParticipant,
+) -> Result<CohortConfig, RegistrationError> {
+    let cohort = find_cohort(participant.cohort_name.trim())
+        .ok_or_else(|| RegistrationError::UnknownCohort(participant.cohort_name.clone()))?;
+    if let Some(reason) = cohort.registration.closed_reason(&audit_timestamp()) {
+        return Err(RegistrationError::Closed(reason));
+    }
+
+    participant.name = participant.name.trim().to_string();
+    participant.email = participant.email.trim().to_lowercase();
+    participant.github = normalize_github_handle(&participant.github);
+
+    let mut problems = Vec::new();
+    if participant.name.is_empty() {
+        problems.push("name is required".to_string());
+    }
+    if !is_valid_email(&participant.email) {
+        problems.push(format!(
+            "{:?} is not a valid email address",
+            participant.email
+        ));
+    }
+    if !is_valid_github_handle(&participant.github) {
+        problems.push(format!(
+            "{:?} is not a valid GitHub username",
+            participant.github
+        ));
+    }
+    if problems.is_empty() {
+        Ok(cohort)
+    } else {
+        Err(RegistrationError::Invalid(problems))
+    }
+}
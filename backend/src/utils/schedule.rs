@@ -0,0 +1,136 @@
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
+use log::{info, warn};
+use std::str::FromStr;
+
+// A standard five-field cron expression: `minute hour day-of-month month day-of-week`.
+// Fields accept `*`, numbers, lists (`1,15`), ranges (`1-5`) and steps (`*/15`, `0-30/10`).
+// Day of week is 0-6 starting on Sunday (7 is also Sunday). Times are local.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut allowed = vec![false; (max + 1) as usize];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step in {:?}", part))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start
+                    .parse()
+                    .map_err(|_| format!("invalid value {:?}", start))?,
+                end.parse()
+                    .map_err(|_| format!("invalid value {:?}", end))?,
+            )
+        } else {
+            let value: u32 = range
+                .parse()
+                .map_err(|_| format!("invalid value {:?}", range))?;
+            // `5/10` means "from 5 to the end, every 10"
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{:?} is out of range {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "expected 5 fields in cron expression {:?}, got {}",
+                expr,
+                fields.len()
+            ));
+        }
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            day_of_month_restricted: fields[2] != "*",
+            day_of_week_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl Schedule {
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let day_of_month = self.days_of_month[time.day() as usize];
+        let day_of_week = self.days_of_week[time.weekday().num_days_from_sunday() as usize];
+        // Like cron: when both day fields are restricted, either one matching is enough
+        let day = match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        day && self.minutes[time.minute() as usize]
+            && self.hours[time.hour() as usize]
+            && self.months[time.month() as usize]
+    }
+
+    // First matching minute strictly after `after`, looking at most a year ahead
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut candidate = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = after + Duration::days(366);
+        while candidate <= limit {
+            if self.matches(&candidate) {
+                return Some(candidate);
+            }
+            candidate += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+// Runs `job` on its own thread every time `schedule` fires, until the process exits
+pub fn spawn_scheduled<F>(name: &str, schedule: Schedule, job: F)
+where
+    F: Fn() + Send + 'static,
+{
+    let name = name.to_string();
+    std::thread::spawn(move || {
+        loop {
+            let now = Local::now();
+            let Some(next) = schedule.next_after(now) else {
+                warn!("Schedule for {} never fires, stopping", name);
+                return;
+            };
+            info!("Next {} run at {}", name, next);
+            let wait = (next - now).to_std().unwrap_or_default();
+            std::thread::sleep(wait);
+            job();
+        }
+    });
+}
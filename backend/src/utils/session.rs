@@ -0,0 +1,130 @@
+use crate::utils::constants::get_auth_token;
+use actix_web::{HttpRequest, error::ErrorUnauthorized};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Who is behind a request. TAs are identified by their TA name, participants by email.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Actor {
+    Ta(String),
+    Participant(String),
+    // Changes made by the server itself (Classroom sync, restores, ...)
+    System(String),
+    Anonymous,
+}
+
+impl Actor {
+    // Name recorded in the audit log for this actor
+    pub fn audit_name(&self) -> String {
+        match self {
+            Actor::Ta(name) => format!("ta:{}", name),
+            Actor::Participant(email) => format!("participant:{}", email),
+            Actor::System(task) => format!("system:{}", task),
+            Actor::Anonymous => "anonymous".to_string(),
+        }
+    }
+
+    pub fn is_ta(&self) -> bool {
+        matches!(self, Actor::Ta(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    actor: Actor,
+    // Database of the cohort a participant logged into
+    cohort_db: Option<String>,
+}
+
+// In-memory session tokens handed out at login. Sessions don't survive a restart.
+#[derive(Debug, Default)]
+pub struct SessionStore {
+    sessions: HashMap<String, Session>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&mut self, actor: Actor) -> String {
+        self.insert(Session {
+            actor,
+            cohort_db: None,
+        })
+    }
+
+    // A participant session, tied to the cohort database they are enrolled in
+    pub fn create_for_cohort(&mut self, actor: Actor, cohort_db: &str) -> String {
+        self.insert(Session {
+            actor,
+            cohort_db: Some(cohort_db.to_string()),
+        })
+    }
+
+    fn insert(&mut self, session: Session) -> String {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        self.sessions.insert(token.clone(), session);
+        token
+    }
+
+    pub fn get(&self, token: &str) -> Option<Actor> {
+        self.sessions.get(token).map(|s| s.actor.clone())
+    }
+}
+
+fn request_token(req: &HttpRequest) -> Option<&str> {
+    let header = req.headers().get("Authorization")?.to_str().ok()?;
+    Some(header.strip_prefix("Bearer ").unwrap_or(header).trim())
+}
+
+// Resolve the actor from the `Authorization` header. Accepts `Bearer <session>` as well as the
+// shared TA token the frontend sends today, which identifies a TA but not which one.
+pub fn actor_from_request(req: &HttpRequest, sessions: &Mutex<SessionStore>) -> Actor {
+    let Some(token) = request_token(req) else {
+        return Actor::Anonymous;
+    };
+
+    if let Some(actor) = sessions.lock().unwrap().get(token) {
+        return actor;
+    }
+    if token == get_auth_token("ta") {
+        return Actor::Ta("shared-token".to_string());
+    }
+    Actor::Anonymous
+}
+
+pub fn require_ta(
+    req: &HttpRequest,
+    sessions: &Mutex<SessionStore>,
+) -> Result<Actor, actix_web::Error> {
+    let actor = actor_from_request(req, sessions);
+    if actor.is_ta() {
+        Ok(actor)
+    } else {
+        Err(ErrorUnauthorized("TA authentication required"))
+    }
+}
+
+// The logged-in participant's email and cohort database, from their session token
+pub fn require_participant(
+    req: &HttpRequest,
+    sessions: &Mutex<SessionStore>,
+) -> Result<(String, PathBuf), actix_web::Error> {
+    let sessions = sessions.lock().unwrap();
+    let session = request_token(req).and_then(|token| sessions.sessions.get(token));
+    match session {
+        Some(Session {
+            actor: Actor::Participant(email),
+            cohort_db: Some(cohort_db),
+        }) => Ok((email.clone(), PathBuf::from(cohort_db))),
+        _ => Err(ErrorUnauthorized("Participant session required")),
+    }
+}
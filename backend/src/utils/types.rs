@@ -1,6 +1,8 @@
 use actix_web::ResponseError;
 use rusqlite::Row;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
@@ -19,9 +21,9 @@ impl ResponseError for AppError {}
 impl From<AppError> for std::io::Error {
     fn from(err: AppError) -> std::io::Error {
         match err {
-            AppError::Database(e) => std::io::Error::new(std::io::ErrorKind::Other, e),
+            AppError::Database(e) => std::io::Error::other(e),
             AppError::Io(e) => e,
-            AppError::Csv(e) => std::io::Error::new(std::io::ErrorKind::Other, e),
+            AppError::Csv(e) => std::io::Error::other(e),
         }
     }
 }
@@ -53,9 +55,115 @@ pub struct RowData {
     pub week: i32,
 }
 
+impl RowData {
+    // Every tracked column with its value rendered as text, in table order
+    pub fn field_values(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("group_id", Some(self.group_id.clone())),
+            ("ta", self.ta.clone()),
+            ("attendance", self.attendance.clone()),
+            ("fa", self.fa.map(|v| v.to_string())),
+            ("fb", self.fb.map(|v| v.to_string())),
+            ("fc", self.fc.map(|v| v.to_string())),
+            ("fd", self.fd.map(|v| v.to_string())),
+            ("bonus_attempt", self.bonus_attempt.map(|v| v.to_string())),
+            (
+                "bonus_answer_quality",
+                self.bonus_answer_quality.map(|v| v.to_string()),
+            ),
+            (
+                "bonus_follow_up",
+                self.bonus_follow_up.map(|v| v.to_string()),
+            ),
+            ("exercise_submitted", self.exercise_submitted.clone()),
+            ("exercise_test_passing", self.exercise_test_passing.clone()),
+            (
+                "exercise_good_documentation",
+                self.exercise_good_documentation.clone(),
+            ),
+            (
+                "exercise_good_structure",
+                self.exercise_good_structure.clone(),
+            ),
+            ("total", self.total.map(|v| v.to_string())),
+            ("mail", Some(self.mail.clone())),
+        ]
+    }
+
+    // Weekly total as the TA table computes it: group discussion (fa/fb out of 30, fc/fd out
+    // of 20, each scored 0-5), bonus (10 each) and exercise (10/50/20/20)
+    pub fn compute_total(&self) -> u64 {
+        let score = |v: Option<u64>| v.unwrap_or(0);
+        let yes = |v: &Option<String>, points: u64| {
+            if v.as_deref() == Some("yes") {
+                points
+            } else {
+                0
+            }
+        };
+        6 * score(self.fa)
+            + 6 * score(self.fb)
+            + 4 * score(self.fc)
+            + 4 * score(self.fd)
+            + 10 * score(self.bonus_attempt)
+            + 10 * score(self.bonus_answer_quality)
+            + 10 * score(self.bonus_follow_up)
+            + yes(&self.exercise_submitted, 10)
+            + yes(&self.exercise_test_passing, 50)
+            + yes(&self.exercise_good_documentation, 20)
+            + yes(&self.exercise_good_structure, 20)
+    }
+
+    // Inverse of `field_values`, used to replay audit entries. Returns false for unknown fields.
+    pub fn set_field(&mut self, field: &str, value: Option<&str>) -> bool {
+        let text = value.map(|v| v.to_string());
+        let number = value.and_then(|v| v.parse::<u64>().ok());
+        match field {
+            "group_id" => self.group_id = text.unwrap_or_default(),
+            "ta" => self.ta = text,
+            "attendance" => self.attendance = text,
+            "fa" => self.fa = number,
+            "fb" => self.fb = number,
+            "fc" => self.fc = number,
+            "fd" => self.fd = number,
+            "bonus_attempt" => self.bonus_attempt = number,
+            "bonus_answer_quality" => self.bonus_answer_quality = number,
+            "bonus_follow_up" => self.bonus_follow_up = number,
+            "exercise_submitted" => self.exercise_submitted = text,
+            "exercise_test_passing" => self.exercise_test_passing = text,
+            "exercise_good_documentation" => self.exercise_good_documentation = text,
+            "exercise_good_structure" => self.exercise_good_structure = text,
+            "total" => self.total = number,
+            "mail" => self.mail = text.unwrap_or_default(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// One entry of the append-only `audit_log` table
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub changed_at: String,
+    pub actor: String,
+    pub cohort: String,
+    pub student: String,
+    pub week: i32,
+    // "insert", "update" or "delete"
+    pub action: String,
+    // Only set for updates; inserts and deletes carry the whole row as JSON
+    pub field: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Table {
     pub rows: Vec<RowData>,
+    // Database the rows were loaded from, if any
+    #[serde(skip)]
+    pub db_path: Option<PathBuf>,
 }
 
 // Move the business logic to a separate implementation
@@ -67,11 +175,11 @@ impl Table {
             .find(|r| r.name == row.name && r.week == row.week);
         if let Some(existing_row) = existing_row {
             if *existing_row != *row {
-                println!("Data has changed for {} in week {}", row.name, row.week);
+                log::debug!("Data has changed for {} in week {}", row.name, row.week);
             }
             *existing_row = row.clone();
         } else {
-            println!("Inserting new row for {} in week {}", row.name, row.week);
+            log::debug!("Inserting new row for {} in week {}", row.name, row.week);
             self.rows.push(row.clone());
         }
         Ok(())
@@ -117,6 +225,7 @@ pub struct CohortParticipant {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FeedbackResponse {
     pub timestamp: String,
+    pub email: String,
     pub discord_name: String,
     pub name_on_certificate: String,
     pub academic_background: String,
@@ -137,32 +246,71 @@ pub struct FeedbackResponse {
     pub fellowship_projects: String,
     pub ideal_project: String,
     pub testimonial: String,
+    // Answers to form questions that aren't mapped to a field above, keyed by sheet header
+    pub extra: BTreeMap<String, String>,
+    // Student on the cohort roster this response was matched to, and how
+    pub student_name: Option<String>,
+    pub link_method: Option<String>,
 }
 
+// `FeedbackResponse` fields a feedback form column can be mapped to, in table column order
+pub const FEEDBACK_FIELDS: &[&str] = &[
+    "timestamp",
+    "email",
+    "discord_name",
+    "name_on_certificate",
+    "academic_background",
+    "skills",
+    "session_instructions",
+    "study_material",
+    "group_discussions",
+    "lounge_discussions",
+    "deputy",
+    "teaching_assistants",
+    "bitshala_clubs",
+    "bitdev_meetups",
+    "bitspace",
+    "fellowships",
+    "expectations",
+    "improvement_ideas",
+    "bitcoin_opportunities",
+    "fellowship_projects",
+    "ideal_project",
+    "testimonial",
+];
+
 impl FeedbackResponse {
+    // Reads a row of the `responses` table written by `utils::feedback_import`
     pub fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        // Missing columns (older imports) read as empty
+        let optional = |column: &str| row.get::<_, Option<String>>(column).ok().flatten();
+        let text = |column: &str| optional(column).unwrap_or_default();
         Ok(FeedbackResponse {
-            timestamp: row.get::<_, String>("Timestamp").unwrap_or_default(),
-            discord_name: row.get::<_, String>("Discord_Name").unwrap_or_default(),
-            name_on_certificate: row.get::<_, String>("Name_on_certificate").unwrap_or_default(),
-            academic_background: row.get::<_, String>("Academic_background").unwrap_or_default(),
-            skills: row.get::<_, String>("Skills").unwrap_or_default(),
-            session_instructions: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Session_Instructions_]").unwrap_or_default(),
-            study_material: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Study_Material_(book_&_questions)_]").unwrap_or_default(),
-            group_discussions: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Group_Discussions]").unwrap_or_default(),
-            lounge_discussions: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Lounge_Discussions]").unwrap_or_default(),
-            deputy: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Deputy]").unwrap_or_default(),
-            teaching_assistants: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Teaching_Assistants]").unwrap_or_default(),
-            bitshala_clubs: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Bitshala_clubs]").unwrap_or_default(),
-            bitdev_meetups: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Bitdev_Meetups]").unwrap_or_default(),
-            bitspace: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Bitspace]").unwrap_or_default(),
-            fellowships: row.get::<_, String>("Which_component_of_cohort_did_you_find_of_help_(or_not_so_much)?_[Fellowships_]").unwrap_or_default(),
-            expectations: row.get::<_, String>("What_were_your_expectations_from_the_cohort?").unwrap_or_default(),
-            improvement_ideas: row.get::<_, String>("What_could_we_do_help_you_have_a_better_Cohort_experience._Please_give_us_your_ideas,_we_need_'em.").unwrap_or_default(),
-            bitcoin_opportunities: row.get::<_, String>("What_kind_of_opportunities_do_you_wish_to_pursue_in_Bitcoin?_").unwrap_or_default(),
-            fellowship_projects: row.get::<_, String>("Any_project_amongst_our_fellowships_(https://bitshala.org/fellowship/)_excite_you?_").unwrap_or_default(),
-            ideal_project: row.get::<_, String>("(Optional)_What_would_be_your_ideal_bitcoin_project_and_your_role_in_it?_You_may_or_may_not_chose_an_existing_bitcoin/bitshala_project,_or_even_choose_your_own_project.\nP.S._-_We_have_a_few_internships_at_Bitshala_and_fellowships_at_Bitshala_incubated_projects!").unwrap_or_default(),
-            testimonial: row.get::<_, String>("(Optional)_We'd_really_appreciate_it_if_you_can_share_a_testimonial._It_might_go_at_Bitshala_website.").unwrap_or_default(),
+            timestamp: text("timestamp"),
+            email: text("email"),
+            discord_name: text("discord_name"),
+            name_on_certificate: text("name_on_certificate"),
+            academic_background: text("academic_background"),
+            skills: text("skills"),
+            session_instructions: text("session_instructions"),
+            study_material: text("study_material"),
+            group_discussions: text("group_discussions"),
+            lounge_discussions: text("lounge_discussions"),
+            deputy: text("deputy"),
+            teaching_assistants: text("teaching_assistants"),
+            bitshala_clubs: text("bitshala_clubs"),
+            bitdev_meetups: text("bitdev_meetups"),
+            bitspace: text("bitspace"),
+            fellowships: text("fellowships"),
+            expectations: text("expectations"),
+            improvement_ideas: text("improvement_ideas"),
+            bitcoin_opportunities: text("bitcoin_opportunities"),
+            fellowship_projects: text("fellowship_projects"),
+            ideal_project: text("ideal_project"),
+            testimonial: text("testimonial"),
+            extra: serde_json::from_str(&text("extra")).unwrap_or_default(),
+            student_name: optional("student_name"),
+            link_method: optional("link_method"),
         })
     }
 }
@@ -1,4 +1,5 @@
 use backend::handlers::auth::TA;
+use backend::utils::archive::Compression;
 use backend::utils::types::RowData;
 use rand::seq::SliceRandom;
 use rand::{Rng, thread_rng};
@@ -114,3 +115,2468 @@ fn test_student_data_generation_and_sorting() {
     assert!(sorted_rows.windows(2).all(|w| w[0].total >= w[1].total));
     assert_eq!(tas.len(), 6);
 }
+
+fn temp_cohort_db(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "{}_{}_{}.db",
+        name,
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE students (
+            name TEXT NOT NULL, group_id TEXT, ta TEXT, attendance TEXT,
+            fa REAL, fb REAL, fc REAL, fd REAL,
+            bonus_attempt REAL, bonus_answer_quality REAL, bonus_follow_up REAL,
+            exercise_submitted TEXT, exercise_test_passing TEXT,
+            exercise_good_documentation TEXT, exercise_good_structure TEXT,
+            total REAL, mail TEXT, GitHub TEXT, week INTEGER
+        );",
+    )
+    .unwrap();
+    path
+}
+
+fn sample_row(name: &str, week: i32) -> RowData {
+    RowData {
+        name: name.to_string(),
+        group_id: "Group 1".to_string(),
+        ta: Some("Bala".to_string()),
+        attendance: Some("yes".to_string()),
+        fa: Some(1),
+        fb: Some(1),
+        fc: Some(1),
+        fd: Some(1),
+        bonus_attempt: Some(0),
+        bonus_answer_quality: Some(0),
+        bonus_follow_up: Some(0),
+        exercise_submitted: Some("no".to_string()),
+        exercise_test_passing: Some("no".to_string()),
+        exercise_good_documentation: Some("no".to_string()),
+        exercise_good_structure: Some("no".to_string()),
+        total: Some(4),
+        mail: format!("{}@example.com", name.to_lowercase()),
+        week,
+    }
+}
+
+#[test]
+fn test_writes_and_deletes_are_audited() {
+    use backend::database::audit::{AuditFilter, read_audit_log};
+    use backend::database::operations::{delete_from_db, read_from_db, write_to_db};
+    use backend::utils::types::Table;
+
+    let db = temp_cohort_db("audit");
+    let mut table = Table {
+        rows: vec![sample_row("Alice", 1), sample_row("Bob", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+
+    // Rewriting unchanged rows must not add entries
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    assert_eq!(
+        read_audit_log(&db, &AuditFilter::default()).unwrap().len(),
+        2
+    );
+
+    table.rows[0].attendance = Some("no".to_string());
+    table.rows[0].total = Some(0);
+    write_to_db(&db, &table, "ta:Raj").unwrap();
+
+    let updates = read_audit_log(
+        &db,
+        &AuditFilter {
+            student: Some("Alice".to_string()),
+            actor: Some("ta:Raj".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(updates.len(), 2);
+    assert!(updates.iter().all(|e| e.action == "update" && e.week == 1));
+    let attendance = updates
+        .iter()
+        .find(|e| e.field.as_deref() == Some("attendance"))
+        .unwrap();
+    assert_eq!(attendance.old_value.as_deref(), Some("yes"));
+    assert_eq!(attendance.new_value.as_deref(), Some("no"));
+
+    assert_eq!(delete_from_db(&db, "Bob", None, "ta:Raj").unwrap(), 1);
+    assert_eq!(read_from_db(&db).unwrap().rows.len(), 1);
+    let deletes = read_audit_log(
+        &db,
+        &AuditFilter {
+            student: Some("Bob".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(deletes[0].action, "delete");
+
+    // Bounds are compared as points in time, whatever their format
+    let an_hour_ago = (chrono::Utc::now() - chrono::Duration::hours(1))
+        .with_timezone(&chrono::FixedOffset::east_opt(5 * 3600).unwrap())
+        .to_rfc3339();
+    let since = |since: &str| AuditFilter {
+        since: Some(since.to_string()),
+        ..Default::default()
+    };
+    assert_eq!(read_audit_log(&db, &since(&an_hour_ago)).unwrap().len(), 5);
+    assert_eq!(read_audit_log(&db, &since("2999-01-01")).unwrap().len(), 0);
+
+    std::fs::remove_file(&db).ok();
+}
+
+#[actix_web::test]
+async fn test_audit_log_needs_a_known_cohort_and_valid_bounds() {
+    use actix_web::{App, test, web};
+    use backend::handlers::audit::get_audit_log;
+    use backend::utils::session::{Actor, SessionStore};
+    use std::sync::Mutex;
+
+    let mut store = SessionStore::new();
+    let ta = store.create(Actor::Ta("Bala".to_string()));
+    let sessions = web::Data::new(Mutex::new(store));
+    let app = test::init_service(App::new().app_data(sessions).service(get_audit_log)).await;
+
+    for (uri, status) in [
+        ("/audit/PB?since=last%20week", 400),
+        ("/audit/%2Ftmp%2Fother.db", 404),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", ta)))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+}
+
+#[test]
+fn test_students_can_be_restored_to_a_point_in_time() {
+    use backend::database::audit::{audit_timestamp, reconstruct_students_at};
+    use backend::database::operations::{
+        delete_from_db, read_from_db, restore_students, write_to_db,
+    };
+    use backend::utils::types::Table;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    let db = temp_cohort_db("restore");
+    let mut table = Table {
+        rows: vec![sample_row("Alice", 1), sample_row("Bob", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    sleep(Duration::from_millis(5));
+    let checkpoint = audit_timestamp();
+    sleep(Duration::from_millis(5));
+
+    // A mistaken bulk edit, a new row and a deletion after the checkpoint
+    for row in table.rows.iter_mut() {
+        row.total = Some(99);
+    }
+    table.rows.push(sample_row("Carol", 1));
+    write_to_db(&db, &table, "ta:Raj").unwrap();
+    delete_from_db(&db, "Alice", None, "ta:Raj").unwrap();
+
+    let preview = reconstruct_students_at(&db, &checkpoint).unwrap();
+    assert_eq!(
+        preview.rows,
+        vec![sample_row("Alice", 1), sample_row("Bob", 1)]
+    );
+
+    restore_students(&db, &preview.rows, "ta:Bala").unwrap();
+    let mut restored = read_from_db(&db).unwrap().rows;
+    restored.sort();
+    assert_eq!(restored, vec![sample_row("Alice", 1), sample_row("Bob", 1)]);
+
+    std::fs::remove_file(&db).ok();
+}
+
+#[test]
+fn test_backup_is_verified_and_old_backups_are_pruned() {
+    use backend::utils::backup::{
+        BackupConfig, apply_retention, backup_database, list_backups, verify_backup,
+    };
+
+    let db = temp_cohort_db("backup");
+    let dir = std::env::temp_dir().join(format!("backups_{}", rand::random::<u32>()));
+    let config = BackupConfig {
+        dir: dir.clone(),
+        retention_days: 35,
+        min_keep: 1,
+        compression: Compression::Gzip,
+        passphrase: None,
+    };
+
+    let backup = backup_database(&db, &config).unwrap();
+    verify_backup(&backup).unwrap();
+
+    // Two stale backups beyond the retention period, one named before millisecond timestamps;
+    // only the newest file must survive
+    let stem = db.file_stem().unwrap().to_string_lossy().to_string();
+    for stale in ["2020-01-01_000000000", "2020-02-01_000000"] {
+        std::fs::copy(&backup, dir.join(format!("{}_{}.db", stem, stale))).unwrap();
+    }
+    assert_eq!(list_backups(&db, &config).len(), 3);
+    assert_eq!(apply_retention(&db, &config).len(), 2);
+    assert_eq!(list_backups(&db, &config)[0].path, backup);
+
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::remove_file(&db).ok();
+}
+
+#[test]
+fn test_backup_restore_takes_safety_backup() {
+    use backend::database::operations::{read_from_db, write_to_db};
+    use backend::utils::backup::{BackupConfig, backup_database, list_backups, restore_backup};
+    use backend::utils::types::Table;
+
+    let db = temp_cohort_db("restore_backup");
+    let dir = std::env::temp_dir().join(format!("backups_{}", rand::random::<u32>()));
+    let config = BackupConfig {
+        dir: dir.clone(),
+        retention_days: 35,
+        min_keep: 3,
+        compression: Compression::Gzip,
+        passphrase: None,
+    };
+
+    let table = Table {
+        rows: vec![sample_row("Alice", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    let backup = backup_database(&db, &config).unwrap();
+
+    let table = Table {
+        rows: vec![sample_row("Bob", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    assert_eq!(read_from_db(&db).unwrap().rows.len(), 2);
+
+    let safety = restore_backup(&db, &backup, &config).unwrap().unwrap();
+    assert_eq!(
+        read_from_db(&db).unwrap().rows,
+        vec![sample_row("Alice", 1)]
+    );
+    assert_eq!(read_from_db(&safety).unwrap().rows.len(), 2);
+    assert_eq!(list_backups(&db, &config).len(), 2);
+
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::remove_file(&db).ok();
+}
+
+#[test]
+fn test_cron_schedule_parsing_and_next_run() {
+    use backend::utils::schedule::Schedule;
+    use chrono::{Local, TimeZone, Timelike};
+
+    let schedule: Schedule = "0 3 * * 1,6".parse().unwrap();
+    // 2025-06-04 is a Wednesday; next run is Saturday 2025-06-07 03:00
+    let wednesday = Local.with_ymd_and_hms(2025, 6, 4, 12, 0, 0).unwrap();
+    let next = schedule.next_after(wednesday).unwrap();
+    assert_eq!(next, Local.with_ymd_and_hms(2025, 6, 7, 3, 0, 0).unwrap());
+    assert_eq!(
+        schedule.next_after(next).unwrap(),
+        Local.with_ymd_and_hms(2025, 6, 9, 3, 0, 0).unwrap()
+    );
+
+    let every_quarter: Schedule = "*/15 * * * *".parse().unwrap();
+    assert_eq!(every_quarter.next_after(wednesday).unwrap().minute(), 15);
+
+    assert!("0 3 * *".parse::<Schedule>().is_err());
+    assert!("61 * * * *".parse::<Schedule>().is_err());
+}
+
+#[test]
+fn test_backup_archives_round_trip() {
+    use backend::utils::archive::{ArchiveError, pack_archive, unpack_archive};
+
+    let data = b"SQLite format 3\0 pretend database contents".repeat(100);
+    for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+        let (archive, suffix) = pack_archive(&data, compression, None).unwrap();
+        assert_eq!(suffix, compression.extension());
+        assert_eq!(unpack_archive(&archive, compression, None).unwrap(), data);
+    }
+
+    let (archive, suffix) = pack_archive(&data, Compression::Zstd, Some("hunter2")).unwrap();
+    assert_eq!(suffix, ".zst.enc");
+    assert_eq!(
+        Compression::from_file_name(&format!("x.db{}", suffix)),
+        Compression::Zstd
+    );
+    assert!(archive.len() < data.len());
+    assert_eq!(
+        unpack_archive(&archive, Compression::Zstd, Some("hunter2")).unwrap(),
+        data
+    );
+    assert!(matches!(
+        unpack_archive(&archive, Compression::Zstd, None),
+        Err(ArchiveError::PassphraseRequired)
+    ));
+    assert!(matches!(
+        unpack_archive(&archive, Compression::Zstd, Some("wrong")),
+        Err(ArchiveError::Crypto)
+    ));
+}
+
+// Minimal HTTP/1.1 server for tests. Answers `requests` connections with `handler`, which gets
+// the request line, headers and body, and returns the status, extra headers and body.
+type MockHandler = fn(&str, &[(String, String)], &[u8]) -> (u16, Vec<(String, String)>, String);
+
+fn spawn_mock_server(
+    requests: usize,
+    handler: MockHandler,
+) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut seen = Vec::new();
+        for _ in 0..requests {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+                }
+            }
+            let length = headers
+                .iter()
+                .find(|(name, _)| name == "content-length")
+                .map(|(_, v)| v.parse().unwrap())
+                .unwrap_or(0);
+            let mut body = vec![0u8; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let request_line = request_line.trim_end().to_string();
+            let (status, extra_headers, response_body) = handler(&request_line, &headers, &body);
+            let mut response = format!(
+                "HTTP/1.1 {} OK\r\nContent-Length: {}\r\nConnection: close\r\n",
+                status,
+                response_body.len()
+            );
+            for (name, value) in extra_headers {
+                response.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            response.push_str("\r\n");
+            response.push_str(&response_body);
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            seen.push(request_line);
+        }
+        seen
+    });
+    (base_url, handle)
+}
+
+#[test]
+fn test_backup_archive_is_uploaded_to_s3_compatible_target() {
+    use backend::utils::backup_targets::{BackupTarget, S3Target};
+
+    let (endpoint, server) = spawn_mock_server(1, |request_line, headers, body| {
+        let authorization = headers
+            .iter()
+            .find(|(name, _)| name == "authorization")
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default();
+        let signed = authorization.starts_with("AWS4-HMAC-SHA256 Credential=minio/")
+            && authorization.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date");
+        let ok = request_line.starts_with("PUT ") && signed && body == b"archive bytes";
+        (if ok { 200 } else { 403 }, Vec::new(), String::new())
+    });
+
+    let target = S3Target {
+        endpoint,
+        bucket: "backups".to_string(),
+        region: "us-east-1".to_string(),
+        access_key: "minio".to_string(),
+        secret_key: "minio123".to_string(),
+        prefix: "bitshala/".to_string(),
+    };
+    let location = target
+        .store("pb_cohort_2025-06-02_030000000.db.gz", b"archive bytes")
+        .unwrap();
+    assert!(location.ends_with("/backups/bitshala/pb_cohort_2025-06-02_030000000.db.gz"));
+
+    let seen = server.join().unwrap();
+    assert_eq!(
+        seen,
+        vec!["PUT /backups/bitshala/pb_cohort_2025-06-02_030000000.db.gz HTTP/1.1"]
+    );
+}
+
+#[test]
+fn test_feedback_import_keeps_previous_responses_when_download_fails() {
+    use backend::utils::cohort::{CohortConfig, find_cohort};
+    use backend::utils::feedback_import::{
+        FeedbackImportError, FeedbackMapping, import_feedback, import_feedback_csv,
+    };
+
+    let db_path = temp_cohort_db("feedback");
+    let mapping = FeedbackMapping::default();
+    let csv = "Timestamp,Discord Name\n2025-06-01,alice\n2025-06-02,bob\n";
+    let report = import_feedback_csv(&db_path, csv.as_bytes(), &mapping).unwrap();
+    assert_eq!(report.imported, 2);
+
+    let (source, server) = spawn_mock_server(1, |_, _, _| {
+        (500, Vec::new(), "sheet unavailable".to_string())
+    });
+    let cohort: CohortConfig = serde_json::from_value(serde_json::json!({
+        "name": "TEST",
+        "db": db_path.to_string_lossy(),
+        "feedback_source": format!("{}/export?format=csv", source),
+    }))
+    .unwrap();
+    assert!(import_feedback(&cohort).is_err());
+    server.join().unwrap();
+
+    // No cohort is imported from a sheet unless one is configured for it
+    let lbtcl = find_cohort("LBTCL").unwrap();
+    assert!(matches!(
+        import_feedback(&lbtcl),
+        Err(FeedbackImportError::NoSource(_))
+    ));
+
+    // An empty export must not wipe the table either
+    assert!(import_feedback_csv(&db_path, b"Timestamp,Discord Name\n", &mapping).is_err());
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let names: Vec<String> = conn
+        .prepare("SELECT discord_name FROM responses ORDER BY timestamp")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["alice", "bob"]);
+}
+
+#[test]
+fn test_feedback_import_maps_headers_and_keeps_extra_questions() {
+    use backend::database::operations::read_all_responses;
+    use backend::utils::feedback_import::{FeedbackMapping, import_feedback_csv};
+
+    let db_path = temp_cohort_db("feedback_mapping");
+    let mapping_path = db_path.with_extension("json");
+    std::fs::write(
+        &mapping_path,
+        r#"{
+            "Timestamp": "timestamp",
+            "Your Discord handle": "discord_name",
+            "Would you recommend the cohort? (testimonial)": "testimonial"
+        }"#,
+    )
+    .unwrap();
+    let mapping = FeedbackMapping::load(&mapping_path).unwrap();
+
+    let csv = "Timestamp,Your  Discord handle,Would you recommend the cohort? (testimonial),Favourite week\n\
+               2025-06-01,alice,Loved it,Week 3\n";
+    let report = import_feedback_csv(&db_path, csv.as_bytes(), &mapping).unwrap();
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.unmapped_columns, vec!["Favourite week"]);
+    assert!(report.missing_fields.contains(&"skills".to_string()));
+    assert!(!report.missing_fields.contains(&"discord_name".to_string()));
+
+    let responses = read_all_responses(&db_path, "TEST").unwrap();
+    assert_eq!(responses[0].discord_name, "alice");
+    assert_eq!(responses[0].testimonial, "Loved it");
+    assert_eq!(responses[0].extra["Favourite week"], "Week 3");
+
+    // Mappings may only target known fields
+    std::fs::write(&mapping_path, r#"{"Timestamp": "submitted_on"}"#).unwrap();
+    assert!(FeedbackMapping::load(&mapping_path).is_err());
+}
+
+#[test]
+fn test_feedback_summary_counts_ratings_and_testimonials() {
+    use backend::database::operations::{count_enrolled_students, read_all_responses, write_to_db};
+    use backend::handlers::feedback::summarize_feedback;
+    use backend::utils::feedback_import::{FeedbackMapping, import_feedback_csv};
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("feedback_summary");
+    let table = Table {
+        rows: ["Alice", "Bob", "Carol", "Dave"]
+            .iter()
+            .map(|name| sample_row(name, 0))
+            .collect(),
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "system:test").unwrap();
+
+    let csv = "Timestamp,Name on certificate,Which component of cohort did you find of help (or not so much)? [Deputy],(Optional) We'd really appreciate it if you can share a testimonial. It might go at Bitshala website.\n\
+               2025-06-01,Alice,5,Best cohort ever\n\
+               2025-06-02,Bob,3,N/A\n\
+               2025-06-03,Carol,,\n";
+    import_feedback_csv(&db_path, csv.as_bytes(), &FeedbackMapping::default()).unwrap();
+
+    let responses = read_all_responses(&db_path, "TEST").unwrap();
+    let summary = summarize_feedback(&responses, count_enrolled_students(&db_path).unwrap());
+
+    assert_eq!(summary.responses, 3);
+    assert_eq!(summary.enrolled, 4);
+    assert_eq!(summary.response_rate, Some(0.75));
+    let deputy = &summary.components["deputy"];
+    assert_eq!(deputy.answered, 2);
+    assert_eq!(deputy.distribution["5"], 1);
+    assert_eq!(deputy.average, Some(4.0));
+    assert_eq!(summary.components["bitspace"].answered, 0);
+    assert_eq!(summary.testimonials.len(), 1);
+    assert_eq!(summary.testimonials[0].name, "Alice");
+}
+
+#[test]
+fn test_feedback_responses_are_linked_to_students() {
+    use backend::database::operations::{read_all_responses, write_to_db};
+    use backend::utils::feedback_import::{FeedbackMapping, import_feedback_csv};
+    use backend::utils::feedback_links::students_without_feedback;
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("feedback_links");
+    let table = Table {
+        rows: ["Alice Smith", "Bob Jones", "Carol White", "Dave Brown"]
+            .iter()
+            .map(|name| sample_row(name, 0))
+            .collect(),
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "system:test").unwrap();
+    assert_eq!(students_without_feedback(&db_path).unwrap().len(), 4);
+
+    let csv = "Timestamp,Email Address,Discord Name,Name on certificate\n\
+               2025-06-01,ALICE SMITH@example.com,alice_s,Alice\n\
+               2025-06-02,bob@gmail.com,bobby,\"Jones, Bob\"\n\
+               2025-06-03,carol@gmail.com,cw,Carol Whyte\n\
+               2025-06-04,eve@gmail.com,eve,Eve\n";
+    let report =
+        import_feedback_csv(&db_path, csv.as_bytes(), &FeedbackMapping::default()).unwrap();
+    assert_eq!(report.linked, 3);
+
+    let links: Vec<(Option<String>, Option<String>)> = read_all_responses(&db_path, "TEST")
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.student_name, r.link_method))
+        .collect();
+    let link = |name: &str, method: &str| (Some(name.to_string()), Some(method.to_string()));
+    assert_eq!(
+        links,
+        vec![
+            link("Alice Smith", "email"),
+            link("Bob Jones", "name"),
+            link("Carol White", "name"),
+            (None, None),
+        ]
+    );
+
+    let missing: Vec<String> = students_without_feedback(&db_path)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(missing, vec!["Dave Brown"]);
+}
+
+#[test]
+fn test_certificates_follow_cohort_rules_and_verify() {
+    use backend::database::operations::write_to_db;
+    use backend::utils::certificates::{
+        CertificateRules, check_eligibility, find_certificate_in, issue_certificates,
+        render_certificate_svg,
+    };
+    use backend::utils::feedback_import::{FeedbackMapping, import_feedback_csv};
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("certificates");
+    let mut rows = Vec::new();
+    for name in ["Alice", "Bob", "Carol"] {
+        rows.push(sample_row(name, 0));
+        for week in 1..=3 {
+            let mut row = sample_row(name, week);
+            if name == "Bob" && week > 1 {
+                row.attendance = Some("no".to_string());
+            }
+            if name != "Carol" {
+                row.exercise_test_passing = Some("yes".to_string());
+            }
+            rows.push(row);
+        }
+    }
+    write_to_db(
+        &db_path,
+        &Table {
+            rows,
+            db_path: None,
+        },
+        "system:test",
+    )
+    .unwrap();
+
+    let csv = "Timestamp,Email Address,Name on certificate\n\
+               2025-06-01,alice@example.com,Alice <Liddell>\n\
+               2025-06-01,bob@example.com,Bob\n";
+    import_feedback_csv(&db_path, csv.as_bytes(), &FeedbackMapping::default()).unwrap();
+
+    let rules = CertificateRules {
+        min_attendance_weeks: 3,
+        min_total: 0,
+        min_exercises_passed: 2,
+        require_feedback: true,
+    };
+    let eligibility = check_eligibility(&db_path, &rules).unwrap();
+    let by_name = |name: &str| eligibility.iter().find(|e| e.name == name).unwrap();
+    assert!(by_name("Alice").eligible);
+    assert_eq!(by_name("Alice").name_on_certificate, "Alice <Liddell>");
+    assert_eq!(by_name("Bob").reasons, vec!["attended 1 weeks, needs 3"]);
+    assert_eq!(by_name("Carol").reasons.len(), 2);
+
+    let certificates = issue_certificates(&db_path, "TEST", &rules).unwrap();
+    assert_eq!(certificates.len(), 1);
+    let code = certificates[0].code.clone();
+    // Issuing again keeps the code
+    assert_eq!(
+        issue_certificates(&db_path, "TEST", &rules).unwrap()[0].code,
+        code
+    );
+
+    let found = find_certificate_in(&db_path, &code.to_lowercase())
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.student_name, "Alice");
+    assert!(
+        find_certificate_in(&db_path, "AAAA-BBBB-CCCC")
+            .unwrap()
+            .is_none()
+    );
+    assert!(render_certificate_svg(&found).contains("Alice &lt;Liddell&gt;"));
+}
+
+#[actix_web::test]
+async fn test_me_endpoints_only_return_the_logged_in_participant() {
+    use actix_web::{App, test, web};
+    use backend::database::operations::write_to_db;
+    use backend::handlers::me::{get_me, get_my_weekly_data};
+    use backend::handlers::students::individual::get_individual_student_data_by_mail;
+    use backend::utils::session::{Actor, SessionStore};
+    use backend::utils::types::Table;
+    use std::sync::Mutex;
+
+    let db_path = temp_cohort_db("me");
+    let rows: Vec<RowData> = ["Alice", "Bob"]
+        .iter()
+        .flat_map(|name| (0..=2).map(move |week| sample_row(name, week)))
+        .collect();
+    let table = Table {
+        rows,
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "system:test").unwrap();
+
+    let mut store = SessionStore::new();
+    let alice = store.create_for_cohort(
+        Actor::Participant("alice@example.com".to_string()),
+        &db_path.to_string_lossy(),
+    );
+    let ta = store.create(Actor::Ta("Bala".to_string()));
+    let sessions = web::Data::new(Mutex::new(store));
+    let app = test::init_service(
+        App::new()
+            .app_data(sessions.clone())
+            .app_data(web::Data::new(Mutex::new(table)))
+            .service(get_me)
+            .service(get_my_weekly_data)
+            .service(get_individual_student_data_by_mail),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/me/weekly")
+        .insert_header(("Authorization", format!("Bearer {}", alice)))
+        .to_request();
+    let rows: Vec<RowData> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.name == "Alice"));
+
+    let req = test::TestRequest::get()
+        .uri("/me")
+        .insert_header(("Authorization", alice.as_str()))
+        .to_request();
+    let me: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(me["name"], "Alice");
+    assert_eq!(me["week"], 2);
+
+    // Lookups by email are limited to the participant's own email, or a TA
+    for (uri, token, status) in [
+        (
+            "/individual_data_email/alice@example.com",
+            Some(&alice),
+            200,
+        ),
+        ("/individual_data_email/bob@example.com", Some(&alice), 401),
+        ("/individual_data_email/bob@example.com", Some(&ta), 200),
+        ("/individual_data_email/bob@example.com", None, 401),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), status, "{} with {:?}", uri, token);
+    }
+
+    // TA sessions and anonymous requests have no "me"
+    for header in [Some(ta), None] {
+        let mut req = test::TestRequest::get().uri("/me/weekly");
+        if let Some(token) = header {
+            req = req.insert_header(("Authorization", token));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 401);
+    }
+}
+
+#[actix_web::test]
+async fn test_student_writes_require_a_ta_and_are_audited_under_their_name() {
+    use actix_web::{App, test, web};
+    use backend::database::audit::{AuditFilter, read_audit_log};
+    use backend::database::operations::{read_from_db, write_to_db};
+    use backend::handlers::students::basic_crud::{add_student, remove_student};
+    use backend::utils::session::{Actor, SessionStore};
+    use backend::utils::types::Table;
+    use std::sync::Mutex;
+
+    let db_path = temp_cohort_db("ta_writes");
+    let table = Table {
+        rows: vec![sample_row("Alice", 1)],
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "system:test").unwrap();
+    let mut store = SessionStore::new();
+    let ta = store.create(Actor::Ta("Bala".to_string()));
+    let alice = store.create_for_cohort(
+        Actor::Participant("alice@example.com".to_string()),
+        &db_path.to_string_lossy(),
+    );
+    let sessions = web::Data::new(Mutex::new(store));
+    let app = test::init_service(
+        App::new()
+            .app_data(sessions.clone())
+            .service(add_student)
+            .service(remove_student),
+    )
+    .await;
+    let db = db_path.to_string_lossy().replace('/', "%2F");
+
+    // Anonymous requests and participant sessions can't write
+    for token in [None, Some(alice)] {
+        let mut req = test::TestRequest::delete().uri(&format!("/students/{}/Alice", db));
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 401);
+    }
+    assert_eq!(read_from_db(&db_path).unwrap().rows.len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/students/{}", db))
+        .insert_header(("Authorization", format!("Bearer {}", ta)))
+        .set_json(sample_row("Bob", 1))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let entries = read_audit_log(
+        &db_path,
+        &AuditFilter {
+            student: Some("Bob".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(!entries.is_empty());
+    assert!(entries.iter().all(|e| e.actor == "ta:Bala"));
+}
+
+#[actix_web::test]
+async fn test_new_weeks_are_saved_once_and_saves_only_write_the_sent_rows() {
+    use actix_web::{App, test, web};
+    use backend::database::audit::{AuditFilter, read_audit_log};
+    use backend::database::operations::{read_from_db, write_rows, write_to_db};
+    use backend::handlers::students::weekly_data::{add_weekly_data, get_weekly_data_or_common};
+    use backend::utils::session::{Actor, SessionStore};
+    use backend::utils::types::Table;
+    use std::sync::Mutex;
+
+    let db_path = temp_cohort_db("weekly_data");
+    let submitted = RowData {
+        exercise_submitted: Some("yes".to_string()),
+        ..sample_row("Alice", 1)
+    };
+    let table = Table {
+        rows: vec![sample_row("Alice", 0), sample_row("Bob", 0), submitted],
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "system:test").unwrap();
+    let state = web::Data::new(Mutex::new(read_from_db(&db_path).unwrap()));
+    let mut store = SessionStore::new();
+    let ta = store.create(Actor::Ta("Bala".to_string()));
+    let sessions = web::Data::new(Mutex::new(store));
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .app_data(sessions.clone())
+            .service(get_weekly_data_or_common)
+            .service(add_weekly_data),
+    )
+    .await;
+    let db = db_path.to_string_lossy().replace('/', "%2F");
+    let audited = |actor: &str| {
+        read_audit_log(
+            &db_path,
+            &AuditFilter {
+                actor: Some(actor.to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    };
+
+    // Bob has no week 1 yet; it's laid out and saved under the system actor
+    for _ in 0..2 {
+        let req = test::TestRequest::get()
+            .uri(&format!("/weekly_data/{}/1", db))
+            .to_request();
+        let rows: Vec<RowData> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(rows.len(), 2);
+        let alice = rows.iter().find(|r| r.name == "Alice").unwrap();
+        assert_eq!(alice.exercise_submitted.as_deref(), Some("yes"));
+    }
+    let saved = read_from_db(&db_path).unwrap().rows;
+    assert!(saved.iter().any(|r| r.name == "Bob" && r.week == 1));
+    let laid_out = audited("system:weekly-data");
+    assert!(!laid_out.is_empty());
+    assert!(laid_out.iter().all(|e| e.student == "Bob"));
+
+    // The sync marks Bob's exercise after the TAs loaded the week
+    let mut bob = saved
+        .into_iter()
+        .find(|r| r.name == "Bob" && r.week == 1)
+        .unwrap();
+    bob.exercise_submitted = Some("yes".to_string());
+    write_rows(&db_path, &[bob], "system:classroom-sync").unwrap();
+
+    // Saving Alice's row leaves Bob's alone
+    let edited = RowData {
+        fa: Some(3),
+        exercise_submitted: Some("yes".to_string()),
+        ..sample_row("Alice", 1)
+    };
+    let req = test::TestRequest::post()
+        .uri(&format!("/weekly_data/{}/1", db))
+        .insert_header(("Authorization", format!("Bearer {}", ta)))
+        .set_json(vec![edited])
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let saved = read_from_db(&db_path).unwrap().rows;
+    let bob = saved
+        .iter()
+        .find(|r| r.name == "Bob" && r.week == 1)
+        .unwrap();
+    assert_eq!(bob.exercise_submitted.as_deref(), Some("yes"));
+    assert!(audited("ta:Bala").iter().all(|e| e.student == "Alice"));
+}
+
+#[test]
+fn test_accepted_appeal_updates_score_through_audit_log() {
+    use backend::database::appeals::{
+        AppealDecision, AppealError, file_appeal, list_appeals, resolve_appeal,
+    };
+    use backend::database::audit::{AuditFilter, read_audit_log};
+    use backend::database::operations::{read_from_db, write_to_db};
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("appeals");
+    let rows = vec![sample_row("Alice", 1), sample_row("Alice", 2)];
+    write_to_db(
+        &db_path,
+        &Table {
+            rows,
+            db_path: None,
+        },
+        "system:test",
+    )
+    .unwrap();
+
+    assert!(matches!(
+        file_appeal(&db_path, "alice@example.com", 1, "total", "please"),
+        Err(AppealError::UnknownCriterion(_))
+    ));
+    let appeal = file_appeal(&db_path, "Alice@Example.com", 1, "fa", "I answered twice").unwrap();
+    assert_eq!(appeal.student, "Alice");
+    assert!(matches!(
+        file_appeal(&db_path, "alice@example.com", 1, "fa", "again"),
+        Err(AppealError::AlreadyPending)
+    ));
+    let other = file_appeal(&db_path, "alice@example.com", 2, "fb", "me too").unwrap();
+    assert_eq!(
+        list_appeals(&db_path, Some("pending"), None).unwrap().len(),
+        2
+    );
+
+    let accept = AppealDecision {
+        accept: true,
+        new_value: Some("3".to_string()),
+        comment: Some("Fair point".to_string()),
+    };
+    let resolved = resolve_appeal(&db_path, appeal.id, &accept, "ta:Bala").unwrap();
+    assert_eq!(resolved.status, "accepted");
+    assert_eq!(resolved.old_value.as_deref(), Some("1"));
+    assert_eq!(resolved.new_value.as_deref(), Some("3"));
+    assert!(matches!(
+        resolve_appeal(&db_path, appeal.id, &accept, "ta:Bala"),
+        Err(AppealError::AlreadyResolved)
+    ));
+
+    // Accepting needs a value, and yes/no criteria only take yes or no
+    let accept_without_value = AppealDecision {
+        accept: true,
+        new_value: None,
+        comment: None,
+    };
+    assert!(matches!(
+        resolve_appeal(&db_path, other.id, &accept_without_value, "ta:Bala"),
+        Err(AppealError::MissingValue)
+    ));
+    let attendance = file_appeal(
+        &db_path,
+        "alice@example.com",
+        2,
+        "attendance",
+        "I was there",
+    )
+    .unwrap();
+    let maybe = AppealDecision {
+        accept: true,
+        new_value: Some("maybe".to_string()),
+        comment: None,
+    };
+    assert!(matches!(
+        resolve_appeal(&db_path, attendance.id, &maybe, "ta:Bala"),
+        Err(AppealError::InvalidValue(..))
+    ));
+
+    let reject = AppealDecision {
+        accept: false,
+        new_value: None,
+        comment: None,
+    };
+    resolve_appeal(&db_path, other.id, &reject, "ta:Bala").unwrap();
+    resolve_appeal(&db_path, attendance.id, &reject, "ta:Bala").unwrap();
+
+    let week1 = read_from_db(&db_path)
+        .unwrap()
+        .rows
+        .into_iter()
+        .find(|r| r.week == 1)
+        .unwrap();
+    assert_eq!(week1.fa, Some(3));
+    assert_eq!(week1.total, Some(week1.compute_total()));
+
+    let entries = read_audit_log(
+        &db_path,
+        &AuditFilter {
+            actor: Some("ta:Bala".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(entries.iter().any(|e| e.field.as_deref() == Some("fa")));
+    let outcomes: Vec<String> = list_appeals(&db_path, None, Some("alice@example.com"))
+        .unwrap()
+        .into_iter()
+        .map(|a| a.status)
+        .collect();
+    assert_eq!(outcomes, vec!["accepted", "rejected", "rejected"]);
+}
+
+#[test]
+fn test_students_can_be_found_by_tags_and_notes() {
+    use backend::database::notes::{add_note, read_notes, search_students, set_tags};
+    use backend::database::operations::write_to_db;
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("notes");
+    let rows = ["Alice", "Bob", "Carol"]
+        .iter()
+        .map(|name| sample_row(name, 0))
+        .collect();
+    write_to_db(
+        &db_path,
+        &Table {
+            rows,
+            db_path: None,
+        },
+        "system:test",
+    )
+    .unwrap();
+
+    let tags = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    set_tags(
+        &db_path,
+        "Alice",
+        &tags(&["Potential fellow", "rust"]),
+        "ta:Bala",
+    )
+    .unwrap();
+    set_tags(&db_path, "Bob", &tags(&["rust", " "]), "ta:Raj").unwrap();
+    // Replacing keeps the remaining tags and drops the others
+    assert_eq!(
+        set_tags(
+            &db_path,
+            "Bob",
+            &tags(&["RUST", "needs follow-up"]),
+            "ta:Raj"
+        )
+        .unwrap(),
+        vec!["needs follow-up", "rust"]
+    );
+    add_note(
+        &db_path,
+        "Carol",
+        "ta:Setu",
+        "Struggling with Rust lifetimes",
+    )
+    .unwrap();
+    add_note(
+        &db_path,
+        "Carol",
+        "ta:Bala",
+        "Doing better after office hours",
+    )
+    .unwrap();
+
+    let notes = read_notes(&db_path, "Carol").unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].author, "ta:Bala");
+
+    let find = |list: &[&str], q: Option<&str>| {
+        search_students(&db_path, &tags(list), q)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(find(&["rust"], None), vec!["Alice", "Bob"]);
+    assert_eq!(find(&["rust", "potential fellow"], None), vec!["Alice"]);
+    assert_eq!(find(&[], Some("rust")), vec!["Alice", "Bob", "Carol"]);
+    assert_eq!(find(&[], Some("office hours")), vec!["Carol"]);
+    assert_eq!(
+        find(&["needs follow-up"], Some("ali")),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_at_risk_report_explains_scores() {
+    use backend::handlers::students::at_risk_students;
+    use std::collections::BTreeSet;
+
+    let week = |name: &str, week: i32, attended: bool, total: u64, submitted: bool| {
+        let mut row = sample_row(name, week);
+        row.attendance = Some(if attended { "yes" } else { "no" }.to_string());
+        row.total = Some(total);
+        row.exercise_submitted = Some(if submitted { "yes" } else { "no" }.to_string());
+        row
+    };
+    let rows = vec![
+        // Steady
+        sample_row("Alice", 0),
+        week("Alice", 1, true, 200, true),
+        week("Alice", 2, true, 210, true),
+        week("Alice", 3, true, 220, true),
+        week("Alice", 4, true, 230, true),
+        // Fading away
+        sample_row("Bob", 0),
+        week("Bob", 1, true, 200, true),
+        week("Bob", 2, true, 150, true),
+        week("Bob", 3, true, 100, false),
+        week("Bob", 4, false, 0, false),
+        // Gone after week 1, no rows since
+        sample_row("Carol", 0),
+        week("Carol", 1, true, 180, true),
+    ];
+
+    let with_feedback: BTreeSet<String> = ["Alice".to_string()].into();
+    let report = at_risk_students(&rows, Some(&with_feedback));
+    let names: Vec<&str> = report.iter().map(|s| s.name.as_str()).collect();
+    // Carol vanished after week 1, which outranks Bob fading away
+    assert_eq!(names, vec!["Carol", "Bob"]);
+
+    let bob = &report[1];
+    assert_eq!(bob.level, "high");
+    assert!(bob.reasons.contains(&"absent last week".to_string()));
+    assert!(
+        bob.reasons
+            .iter()
+            .any(|r| r.starts_with("totals declining"))
+    );
+    assert!(
+        bob.reasons
+            .contains(&"2 of the last 3 exercises not submitted".to_string())
+    );
+
+    let carol = &report[0];
+    assert_eq!(carol.level, "high");
+    assert!(
+        carol
+            .reasons
+            .contains(&"absent for the last 3 weeks".to_string())
+    );
+    assert!(
+        carol
+            .reasons
+            .contains(&"3 of the last 3 exercises not submitted".to_string())
+    );
+    assert!(carol.reasons.contains(&"no feedback submitted".to_string()));
+
+    // Without imported feedback nobody is flagged for it
+    let report = at_risk_students(&rows, None);
+    assert!(
+        report
+            .iter()
+            .all(|s| !s.reasons.iter().any(|r| r.contains("feedback")))
+    );
+}
+
+#[test]
+fn test_enrollment_lifecycle_and_retention_stats() {
+    use backend::database::enrollment::{
+        EnrollmentError, EnrollmentStatus, current_enrollments, enrollment_history,
+        enrollment_stats, inactive_emails, set_enrollment_status,
+    };
+    use backend::database::operations::write_to_db;
+    use backend::utils::types::Table;
+
+    let db_path = temp_cohort_db("enrollment");
+    let mut rows: Vec<RowData> = ["Alice", "Bob", "Carol", "Dave"]
+        .iter()
+        .map(|name| sample_row(name, 0))
+        .collect();
+    rows.push(sample_row("Alice", 1));
+    rows.push(sample_row("Alice", 2));
+    write_to_db(
+        &db_path,
+        &Table {
+            rows,
+            db_path: None,
+        },
+        "system:test",
+    )
+    .unwrap();
+
+    // Roster students start out enrolled
+    let enrollments = current_enrollments(&db_path).unwrap();
+    assert_eq!(enrollments.len(), 4);
+    assert_eq!(
+        enrollments["bob@example.com"].status,
+        EnrollmentStatus::Enrolled
+    );
+
+    let change = set_enrollment_status(
+        &db_path,
+        "Bob@example.com",
+        EnrollmentStatus::Dropped,
+        Some("Got a full-time job"),
+        "ta:Raj",
+    )
+    .unwrap();
+    assert_eq!(change.week, 2);
+    set_enrollment_status(
+        &db_path,
+        "carol@example.com",
+        EnrollmentStatus::Deferred,
+        None,
+        "ta:Raj",
+    )
+    .unwrap();
+    set_enrollment_status(
+        &db_path,
+        "dave@example.com",
+        EnrollmentStatus::Completed,
+        None,
+        "ta:Raj",
+    )
+    .unwrap();
+    // Completed is final
+    assert!(matches!(
+        set_enrollment_status(
+            &db_path,
+            "dave@example.com",
+            EnrollmentStatus::Applied,
+            None,
+            "ta:Raj"
+        ),
+        Err(EnrollmentError::InvalidTransition(
+            EnrollmentStatus::Completed,
+            EnrollmentStatus::Applied
+        ))
+    ));
+    // A typo doesn't create an enrollment out of thin air
+    assert!(matches!(
+        set_enrollment_status(
+            &db_path,
+            "dvae@example.com",
+            EnrollmentStatus::Dropped,
+            None,
+            "ta:Raj"
+        ),
+        Err(EnrollmentError::UnknownParticipant(_))
+    ));
+
+    let inactive = inactive_emails(&db_path).unwrap();
+    assert!(inactive.contains("bob@example.com") && inactive.contains("carol@example.com"));
+    assert_eq!(inactive.len(), 2);
+
+    let history = enrollment_history(&db_path, "bob@example.com").unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].reason.as_deref(), Some("Got a full-time job"));
+
+    let stats = enrollment_stats(&db_path).unwrap();
+    assert_eq!(stats.ever_enrolled, 3);
+    assert_eq!(stats.by_status["deferred"], 1);
+    assert_eq!(stats.dropouts_by_week.get(&2), Some(&1));
+    assert!((stats.retention_rate.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+
+    // Dropping keeps the student's weekly history
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let bob_rows: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM students WHERE name = 'Bob'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(bob_rows, 1);
+}
+
+fn sample_participant(name: &str) -> backend::utils::types::CohortParticipant {
+    backend::utils::types::CohortParticipant {
+        name: name.to_string(),
+        enrolled: false,
+        role: "pb_cohort".to_string(),
+        email: format!("{}@example.com", name.to_lowercase()),
+        describe_yourself: String::new(),
+        background: String::new(),
+        github: name.to_lowercase(),
+        skills: vec![],
+        year: String::new(),
+        books: vec![],
+        why: String::new(),
+        time: String::new(),
+        location: String::new(),
+        cohort_name: "PB".to_string(),
+    }
+}
+
+// Verifies a registration with the code that was emailed
+fn verify_participant(
+    db_path: &std::path::Path,
+    email: &str,
+) -> backend::database::verification::Verification {
+    let conn = rusqlite::Connection::open(db_path).unwrap();
+    let code: String = conn
+        .query_row(
+            "SELECT code FROM email_verifications WHERE email = ?1",
+            [email],
+            |row| row.get(0),
+        )
+        .unwrap();
+    backend::database::verification::verify_email(db_path, email, &code).unwrap()
+}
+
+#[test]
+fn test_bot_invites_are_retried_until_delivered() {
+    use backend::database::operations::register_cohort_participant;
+    use backend::database::outbox::{list_messages, retry_message};
+    use backend::utils::bot_invites::{BOT_INVITE, BotInviteConfig, deliver_bot_invites};
+    use backend::utils::email_verification::VERIFICATION_EMAIL;
+    use backend::utils::outbox_worker::RetryPolicy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    // The bot is down for the first request
+    let (base_url, server) = spawn_mock_server(2, |_, _, body| {
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["email"], "alice@example.com");
+        if CALLS.fetch_add(1, Ordering::SeqCst) == 0 {
+            (503, vec![], "down".to_string())
+        } else {
+            (200, vec![], "{}".to_string())
+        }
+    });
+
+    let db_path = temp_cohort_db("invites");
+    register_cohort_participant(&db_path, sample_participant("Alice"), None).unwrap();
+    assert!(list_messages(&db_path, BOT_INVITE).unwrap().is_empty());
+    verify_participant(&db_path, "alice@example.com");
+    let invites = list_messages(&db_path, BOT_INVITE).unwrap();
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0].status, "pending");
+
+    let config = BotInviteConfig {
+        url: format!("{}/bot/invite", base_url),
+        retry: RetryPolicy {
+            max_attempts: 5,
+            retry_delay: Duration::from_secs(3600),
+        },
+    };
+    let report = deliver_bot_invites(&db_path, &config).unwrap();
+    assert_eq!((report.delivered, report.retrying), (0, 1));
+    // Not due again until the backoff has passed
+    let report = deliver_bot_invites(&db_path, &config).unwrap();
+    assert_eq!(report.retrying + report.delivered, 0);
+
+    // Pretend the backoff has passed
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute("UPDATE outbox SET next_attempt_at = ''", [])
+        .unwrap();
+    let report = deliver_bot_invites(&db_path, &config).unwrap();
+    assert_eq!(report.delivered, 1);
+    server.join().unwrap();
+
+    let invite = &list_messages(&db_path, BOT_INVITE).unwrap()[0];
+    assert_eq!(invite.status, "delivered");
+    assert_eq!(invite.attempts, 2);
+    assert!(invite.delivered_at.is_some());
+
+    // Retrying by invite id only requeues failed bot invites, not other outbox messages
+    conn.execute("UPDATE outbox SET status = 'failed'", [])
+        .unwrap();
+    let email = &list_messages(&db_path, VERIFICATION_EMAIL).unwrap()[0];
+    assert!(
+        retry_message(&db_path, BOT_INVITE, email.id)
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(
+        list_messages(&db_path, VERIFICATION_EMAIL).unwrap()[0].status,
+        "failed"
+    );
+    let retried = retry_message(&db_path, BOT_INVITE, invite.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!((retried.status.as_str(), retried.attempts), ("pending", 0));
+}
+
+#[test]
+fn test_registration_is_validated_and_idempotent() {
+    use backend::database::operations::{RegistrationOutcome, register_cohort_participant};
+    use backend::database::outbox::list_messages;
+    use backend::database::verification::{
+        VerificationError, is_verified, resend_verification, verify_email,
+    };
+    use backend::utils::email_verification::VERIFICATION_EMAIL;
+    use backend::utils::registration::{RegistrationError, validate_registration};
+
+    let mut participant = sample_participant("Alice");
+    participant.email = " Alice@Example.com ".to_string();
+    participant.github = "https://github.com/alice-dev/".to_string();
+    let cohort = validate_registration(&mut participant).unwrap();
+    assert_eq!(cohort.db, "pb_cohort.db");
+    assert_eq!(participant.email, "alice@example.com");
+    assert_eq!(participant.github, "alice-dev");
+
+    let mut bad = sample_participant("Bob");
+    bad.email = "bob@localhost".to_string();
+    bad.github = "-bob--".to_string();
+    match validate_registration(&mut bad) {
+        Err(RegistrationError::Invalid(problems)) => assert_eq!(problems.len(), 2),
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+    let mut unknown = sample_participant("Carol");
+    unknown.cohort_name = "../../etc/passwd".to_string();
+    assert!(matches!(
+        validate_registration(&mut unknown),
+        Err(RegistrationError::UnknownCohort(_))
+    ));
+
+    let db_path = temp_cohort_db("registration");
+    assert_eq!(
+        register_cohort_participant(&db_path, participant.clone(), None)
+            .unwrap()
+            .outcome,
+        RegistrationOutcome::Created
+    );
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let created: (String, String) = conn
+        .query_row(
+            "SELECT created_at, updated_at FROM participants",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    verify_participant(&db_path, "alice@example.com");
+    // Re-registering only stages the new answers and sends a code to the registered email
+    let mut impostor = participant.clone();
+    impostor.name = "Mallory".to_string();
+    impostor.location = "Bangalore".to_string();
+    assert_eq!(
+        register_cohort_participant(&db_path, impostor, None)
+            .unwrap()
+            .outcome,
+        RegistrationOutcome::UpdatePending
+    );
+    let participant_row = || -> (i64, String, String, String, String) {
+        conn.query_row(
+            "SELECT COUNT(*), name, created_at, updated_at, location FROM participants",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .unwrap()
+    };
+    let (count, name, _, updated_at, _) = participant_row();
+    assert_eq!((count, name.as_str()), (1, "Alice"));
+    assert_eq!(updated_at, created.1);
+    assert_eq!(
+        list_messages(&db_path, VERIFICATION_EMAIL).unwrap().len(),
+        2
+    );
+    assert!(matches!(
+        verify_email(&db_path, "alice@example.com", "not-the-code"),
+        Err(VerificationError::WrongCode)
+    ));
+    assert_eq!(participant_row().1, "Alice");
+    // A resent code for the update doesn't reset the failed attempts
+    assert!(
+        resend_verification(
+            &db_path,
+            "alice@example.com",
+            "PB",
+            std::time::Duration::from_secs(3600)
+        )
+        .unwrap()
+    );
+    let failed_attempts: i64 = conn
+        .query_row(
+            "SELECT failed_attempts FROM pending_registration_updates",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(failed_attempts, 1);
+
+    // Entering the code applies the update and keeps the participant verified
+    let code: String = conn
+        .query_row(
+            "SELECT code FROM pending_registration_updates WHERE email = 'alice@example.com'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    verify_email(&db_path, "alice@example.com", &code).unwrap();
+    let (count, name, created_at, updated_at, location) = participant_row();
+    assert_eq!(count, 1);
+    assert_eq!(name, "Mallory");
+    assert_eq!(created_at, created.0);
+    assert!(updated_at > created.1);
+    assert_eq!(location, "Bangalore");
+    assert!(is_verified(&conn, "alice@example.com").unwrap());
+}
+
+#[test]
+fn test_registrations_beyond_capacity_are_waitlisted_and_promoted() {
+    use backend::database::operations::{RegistrationOutcome, register_cohort_participant};
+    use backend::database::outbox::list_messages;
+    use backend::database::waitlist::{promote_from_waitlist, read_waitlist};
+    use backend::utils::bot_invites::BOT_INVITE;
+    use backend::utils::registration::RegistrationRules;
+
+    let rules = RegistrationRules {
+        opens_at: Some("2026-01-10".to_string()),
+        closes_at: Some("2026-02-01T12:00:00Z".to_string()),
+        seats: Some(2),
+    };
+    assert!(
+        rules
+            .closed_reason("2026-01-09T23:59:59.000Z")
+            .unwrap()
+            .starts_with("Registration opens")
+    );
+    assert_eq!(rules.closed_reason("2026-01-20T00:00:00.000Z"), None);
+    assert!(rules.closed_reason("2026-02-01T12:00:00.000Z").is_some());
+
+    let db_path = temp_cohort_db("waitlist");
+    let outcomes: Vec<_> = ["Alice", "Bob", "Carol", "Dave", "Erin"]
+        .iter()
+        .map(|name| {
+            register_cohort_participant(&db_path, sample_participant(name), rules.seats).unwrap()
+        })
+        .collect();
+    assert_eq!(outcomes[1].outcome, RegistrationOutcome::Created);
+    assert_eq!(outcomes[2].outcome, RegistrationOutcome::Waitlisted);
+    assert_eq!(outcomes[4].waitlist_position, Some(3));
+    // Only seated participants get invited, once they verify their email
+    assert!(list_messages(&db_path, BOT_INVITE).unwrap().is_empty());
+    for email in ["alice@example.com", "bob@example.com", "erin@example.com"] {
+        verify_participant(&db_path, email);
+    }
+    assert_eq!(list_messages(&db_path, BOT_INVITE).unwrap().len(), 2);
+
+    // Re-registering keeps the place in line
+    let again =
+        register_cohort_participant(&db_path, sample_participant("Dave"), rules.seats).unwrap();
+    assert_eq!(
+        (again.outcome, again.waitlist_position),
+        (RegistrationOutcome::UpdatePending, Some(2))
+    );
+
+    let promoted = promote_from_waitlist(&db_path, &["ERIN@example.com".to_string()], 1).unwrap();
+    assert_eq!(promoted.len(), 1);
+    assert_eq!(promoted[0].email, "erin@example.com");
+    let promoted = promote_from_waitlist(&db_path, &[], 1).unwrap();
+    assert_eq!(promoted[0].email, "carol@example.com");
+
+    let waitlist = read_waitlist(&db_path).unwrap();
+    assert_eq!(waitlist.len(), 1);
+    assert_eq!(
+        (waitlist[0].email.as_str(), waitlist[0].position),
+        ("dave@example.com", 1)
+    );
+    let invited: Vec<String> = list_messages(&db_path, BOT_INVITE)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.email)
+        .collect();
+    // Carol hasn't verified yet
+    assert_eq!(invited.len(), 3);
+    assert!(invited.contains(&"erin@example.com".to_string()));
+    assert!(verify_participant(&db_path, "carol@example.com").invite_queued);
+}
+
+// Minimal SMTP relay for tests. Accepts `connections` connections and returns the message data
+// of each.
+fn spawn_smtp_sink(connections: usize) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = std::thread::spawn(move || {
+        let mut mails = Vec::new();
+        for _ in 0..connections {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let reply = |reader: &mut BufReader<std::net::TcpStream>, line: &str| {
+                reader
+                    .get_mut()
+                    .write_all(format!("{}\r\n", line).as_bytes())
+                    .unwrap();
+            };
+            reply(&mut reader, "220 sink ESMTP");
+            let mut data = String::new();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                if in_data {
+                    if line.trim_end() == "." {
+                        in_data = false;
+                        reply(&mut reader, "250 queued");
+                    } else {
+                        data.push_str(&line);
+                    }
+                    continue;
+                }
+                let command = line.to_uppercase();
+                if command.starts_with("DATA") {
+                    in_data = true;
+                    reply(&mut reader, "354 go ahead");
+                } else if command.starts_with("QUIT") {
+                    reply(&mut reader, "221 bye");
+                    break;
+                } else {
+                    reply(&mut reader, "250 OK");
+                }
+            }
+            mails.push(data);
+        }
+        mails
+    });
+    (port, handle)
+}
+
+#[test]
+fn test_registration_email_is_verified_before_inviting() {
+    use backend::database::operations::register_cohort_participant;
+    use backend::database::outbox::list_messages;
+    use backend::database::verification::{VerificationError, verify_email};
+    use backend::utils::bot_invites::BOT_INVITE;
+    use backend::utils::email_verification::{
+        VERIFICATION_EMAIL, VerificationConfig, deliver_verification_emails, render_verification,
+    };
+    use backend::utils::mailer::SmtpConfig;
+    use backend::utils::outbox_worker::RetryPolicy;
+    use std::time::Duration;
+
+    let db_path = temp_cohort_db("verification");
+    register_cohort_participant(&db_path, sample_participant("Alice"), None).unwrap();
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let code: String = conn
+        .query_row("SELECT code FROM email_verifications", [], |row| row.get(0))
+        .unwrap();
+
+    let (port, sink) = spawn_smtp_sink(1);
+    let smtp = SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        tls: "none".to_string(),
+        username: None,
+        password: None,
+        from: "Bitshala <noreply@bitshala.org>".to_string(),
+    };
+    let config = VerificationConfig {
+        link_base: "https://admin.bitshala.org/".to_string(),
+        code_valid_for: Duration::from_secs(3600),
+        retry: RetryPolicy {
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(60),
+        },
+    };
+    let report = deliver_verification_emails(&db_path, &smtp, &config).unwrap();
+    assert_eq!(report.delivered, 1);
+    let mail = sink.join().unwrap().remove(0);
+    assert!(mail.contains("To: alice@example.com"));
+    assert!(mail.contains(&code));
+    let message = &list_messages(&db_path, VERIFICATION_EMAIL).unwrap()[0];
+    let (_, body) = render_verification(&config, &message.payload).unwrap();
+    assert!(body.contains(&format!(
+        "https://admin.bitshala.org/verify/PB?email=alice%40example.com&code={}",
+        code
+    )));
+    assert_eq!(
+        list_messages(&db_path, VERIFICATION_EMAIL).unwrap()[0].status,
+        "delivered"
+    );
+
+    let wrong = if code == "000000" { "111111" } else { "000000" };
+    assert!(matches!(
+        verify_email(&db_path, "alice@example.com", wrong),
+        Err(VerificationError::WrongCode)
+    ));
+    assert!(list_messages(&db_path, BOT_INVITE).unwrap().is_empty());
+
+    let verification = verify_email(&db_path, "Alice@Example.com", &code).unwrap();
+    assert!(verification.invite_queued);
+    // Opening the link again doesn't queue a second invite
+    assert!(
+        !verify_email(&db_path, "alice@example.com", &code)
+            .unwrap()
+            .invite_queued
+    );
+    assert_eq!(list_messages(&db_path, BOT_INVITE).unwrap().len(), 1);
+}
+
+#[test]
+fn test_resending_a_code_keeps_the_failed_attempts() {
+    use backend::database::operations::register_cohort_participant;
+    use backend::database::verification::{VerificationError, resend_verification, verify_email};
+    use std::time::Duration;
+
+    let db_path = temp_cohort_db("verification_resend");
+    register_cohort_participant(&db_path, sample_participant("Alice"), None).unwrap();
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let code = || -> String {
+        conn.query_row("SELECT code FROM email_verifications", [], |row| row.get(0))
+            .unwrap()
+    };
+    let wrong = |code: &str| if code == "000000" { "111111" } else { "000000" };
+
+    let first = code();
+    for _ in 0..4 {
+        assert!(matches!(
+            verify_email(&db_path, "alice@example.com", wrong(&first)),
+            Err(VerificationError::WrongCode)
+        ));
+    }
+    assert!(
+        resend_verification(
+            &db_path,
+            "alice@example.com",
+            "PB",
+            Duration::from_secs(3600)
+        )
+        .unwrap()
+    );
+    let second = code();
+    assert!(matches!(
+        verify_email(&db_path, "alice@example.com", wrong(&second)),
+        Err(VerificationError::WrongCode)
+    ));
+    assert!(
+        resend_verification(
+            &db_path,
+            "alice@example.com",
+            "PB",
+            Duration::from_secs(3600)
+        )
+        .unwrap()
+    );
+    assert!(matches!(
+        verify_email(&db_path, "alice@example.com", &code()),
+        Err(VerificationError::TooManyAttempts)
+    ));
+}
+
+#[test]
+fn test_published_week_notifies_students_once() {
+    use backend::database::enrollment::{EnrollmentStatus, set_enrollment_status};
+    use backend::database::notifications::{publish_week, queue_weekly_reminders};
+    use backend::database::operations::write_to_db;
+    use backend::database::outbox::list_messages;
+    use backend::utils::mailer::SmtpConfig;
+    use backend::utils::notifications::{
+        Channel, NOTIFICATION, NotificationConfig, deliver_notifications,
+    };
+    use backend::utils::outbox_worker::RetryPolicy;
+    use backend::utils::types::Table;
+    use std::time::Duration;
+
+    let db_path = temp_cohort_db("notifications");
+    let mut bob = sample_row("Bob", 1);
+    bob.group_id = "Group 2".to_string();
+    bob.ta = Some("Setu".to_string());
+    bob.exercise_submitted = Some("yes".to_string());
+    let table = Table {
+        rows: vec![
+            sample_row("Alice", 0),
+            sample_row("Alice", 1),
+            bob,
+            sample_row("Carol", 0),
+            sample_row("Carol", 1),
+        ],
+        db_path: None,
+    };
+    write_to_db(&db_path, &table, "ta:Bala").unwrap();
+    set_enrollment_status(
+        &db_path,
+        "carol@example.com",
+        EnrollmentStatus::Dropped,
+        None,
+        "ta:Bala",
+    )
+    .unwrap();
+
+    let (port, sink) = spawn_smtp_sink(2);
+    let (webhook, discord) = spawn_mock_server(1, |request_line, _, body| {
+        assert!(request_line.starts_with("POST /webhook"));
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        let content = body["content"].as_str().unwrap();
+        assert!(content.contains("Group 1 (Bala): Alice"));
+        assert!(content.contains("Group 2 (Setu): Bob"));
+        (204, vec![], String::new())
+    });
+    let config = NotificationConfig {
+        channels: vec![Channel::Email, Channel::Discord],
+        discord_webhook_url: Some(format!("{}/webhook", webhook)),
+        smtp: SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: "none".to_string(),
+            username: None,
+            password: None,
+            from: "Bitshala <noreply@bitshala.org>".to_string(),
+        },
+        templates_dir: None,
+        retry: RetryPolicy {
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(60),
+        },
+    };
+
+    let report = publish_week(&db_path, "PB", 1, &config).unwrap();
+    // Carol dropped out
+    assert_eq!(
+        report.notified,
+        vec!["alice@example.com", "bob@example.com"]
+    );
+    let delivered = deliver_notifications(&db_path, &config).unwrap();
+    assert_eq!(delivered.delivered, 3);
+    discord.join().unwrap();
+    let mails = sink.join().unwrap();
+    assert!(mails.iter().any(|m| m.contains("To: bob@example.com")
+        && m.contains("Subject: PB week 1: you're in Group 2")));
+
+    // Publishing again only notifies students whose group changed
+    let report = publish_week(&db_path, "PB", 1, &config).unwrap();
+    assert_eq!((report.notified.len(), report.unchanged), (0, 2));
+    let mut table = table;
+    table.rows[1].group_id = "Group 3".to_string();
+    write_to_db(&db_path, &table, "ta:Bala").unwrap();
+    let email_only = NotificationConfig {
+        channels: vec![Channel::Email],
+        ..config
+    };
+    let report = publish_week(&db_path, "PB", 1, &email_only).unwrap();
+    assert_eq!(report.notified, vec!["alice@example.com"]);
+
+    let report = queue_weekly_reminders(&db_path, "PB", &email_only).unwrap();
+    assert_eq!((report.week, report.notified.len()), (1, 2));
+    let reminder = list_messages(&db_path, NOTIFICATION)
+        .unwrap()
+        .into_iter()
+        .find(|m| m.email == "alice@example.com" && m.payload.contains("weekly_reminder"))
+        .unwrap();
+    assert!(
+        reminder
+            .payload
+            .contains("You haven't submitted this week's exercise yet.")
+    );
+    let report = queue_weekly_reminders(&db_path, "PB", &email_only).unwrap();
+    assert_eq!((report.notified.len(), report.unchanged), (0, 2));
+}
+
+#[test]
+fn test_ta_digest_lists_absences_exercises_and_appeals() {
+    use backend::database::appeals::file_appeal;
+    use backend::database::operations::{register_cohort_participant, write_to_db};
+    use backend::database::outbox::list_messages;
+    use backend::utils::digest::{build_digest, queue_ta_digests};
+    use backend::utils::mailer::SmtpConfig;
+    use backend::utils::notifications::{Channel, NOTIFICATION, NotificationConfig};
+    use backend::utils::outbox_worker::RetryPolicy;
+    use backend::utils::types::Table;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    let db_path = temp_cohort_db("digest");
+    let mut rows = Vec::new();
+    for name in ["Alice", "Bob", "Carol"] {
+        let mut last_week = sample_row(name, 1);
+        let mut this_week = sample_row(name, 2);
+        if name == "Carol" {
+            last_week.exercise_submitted = Some("yes".to_string());
+            this_week.group_id = "Group 2".to_string();
+            this_week.ta = Some("Setu".to_string());
+        }
+        if name == "Alice" {
+            last_week.attendance = Some("no".to_string());
+        }
+        rows.push(last_week);
+        rows.push(this_week);
+    }
+    write_to_db(
+        &db_path,
+        &Table {
+            rows,
+            db_path: None,
+        },
+        "ta:Bala",
+    )
+    .unwrap();
+    // Bob's week 1 exercise is only in GitHub Classroom
+    register_cohort_participant(&db_path, sample_participant("Bob"), None).unwrap();
+    file_appeal(&db_path, "alice@example.com", 1, "fa", "I answered").unwrap();
+
+    let submissions = Ok(HashSet::from(["bob".to_string()]));
+    let digest = build_digest(&db_path, "PB", 2, "bala", &submissions).unwrap();
+    let names = |students: &[backend::utils::digest::DigestStudent]| {
+        students.iter().map(|s| s.name.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(digest.groups.len(), 1);
+    assert_eq!(names(&digest.groups[0].members), vec!["Alice", "Bob"]);
+    assert_eq!(names(&digest.absent_last_week), vec!["Alice"]);
+    assert_eq!(names(&digest.missing_exercise), vec!["Alice"]);
+    assert_eq!(digest.pending_appeals.len(), 1);
+    assert!(
+        digest
+            .to_text()
+            .contains("Week 1 exercise not submitted: Alice")
+    );
+
+    // Without Classroom only the table counts
+    let unavailable = Err("GITHUB_TOKEN not set".to_string());
+    let digest = build_digest(&db_path, "PB", 2, "Bala", &unavailable).unwrap();
+    assert_eq!(names(&digest.missing_exercise), vec!["Alice", "Bob"]);
+    assert!(digest.classroom_error.is_some());
+    assert!(
+        build_digest(&db_path, "PB", 2, "Raj", &submissions)
+            .unwrap()
+            .is_empty()
+    );
+
+    let config = NotificationConfig {
+        channels: vec![Channel::Email],
+        discord_webhook_url: None,
+        smtp: SmtpConfig::from_env(),
+        templates_dir: None,
+        retry: RetryPolicy {
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(60),
+        },
+    };
+    let queued = queue_ta_digests(&db_path, "PB", 2, &submissions, &config).unwrap();
+    assert_eq!(queued, vec!["Bala", "Setu"]);
+    let recipients: Vec<String> = list_messages(&db_path, NOTIFICATION)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.email)
+        .collect();
+    assert!(recipients.contains(&"balajic86@gmail.com".to_string()));
+    assert!(recipients.contains(&"setu@bitshala.org".to_string()));
+}
+
+// A Classroom grade as GitHub returns it
+fn sample_grade(github: &str, week: u32) -> serde_json::Value {
+    serde_json::json!({
+        "assignment_name": format!("Week {} exercise", week),
+        "assignment_url": "https://classroom.github.com/a/x",
+        "github_username": github,
+        "points_available": "100",
+        "points_awarded": "100",
+        "roster_identifier": github,
+        "starter_code_url": "",
+        "student_repository_name": format!("week-{}-{}", week, github),
+        "student_repository_url": format!("https://github.com/bitshala/week-{}-{}", week, github),
+        "submission_timestamp": "2026-01-01T00:00:00Z"
+    })
+}
+
+#[actix_web::test]
+async fn test_classroom_grades_are_cached_and_served_stale_when_rate_limited() {
+    use backend::utils::classroom::ClassroomClient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let (base_url, server) = spawn_mock_server(2, |request_line, headers, _| {
+        assert!(request_line.starts_with("GET /assignments/42/grades"));
+        assert!(
+            headers
+                .iter()
+                .any(|(name, value)| name == "authorization" && value == "Bearer test-token")
+        );
+        if CALLS.fetch_add(1, Ordering::SeqCst) == 0 {
+            let body = serde_json::json!([sample_grade("alice", 1)]).to_string();
+            (200, vec![], body)
+        } else {
+            let reset = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                + 3600;
+            let headers = vec![
+                ("X-RateLimit-Remaining".to_string(), "0".to_string()),
+                ("X-RateLimit-Reset".to_string(), reset.to_string()),
+            ];
+            (
+                403,
+                headers,
+                "{\"message\": \"API rate limit exceeded\"}".to_string(),
+            )
+        }
+    });
+    let cache_db = std::env::temp_dir().join(format!(
+        "classroom_cache_{}_{}.db",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let client = |ttl| {
+        ClassroomClient::new(
+            &base_url,
+            Some("test-token".to_string()),
+            cache_db.clone(),
+            ttl,
+            Duration::from_secs(60),
+        )
+        .unwrap()
+    };
+
+    let cached = client(Duration::from_secs(300));
+    let grades = cached.grades(42).await;
+    assert!(!grades.stale);
+    assert_eq!(grades.assignments[0].github_username, "alice");
+    // Served from the cache without asking GitHub
+    let again = cached.grades(42).await;
+    assert_eq!(
+        (again.stale, again.fetched_at.clone()),
+        (false, grades.fetched_at.clone())
+    );
+
+    // Once the cache expires GitHub rate-limits us; the cached grades are served as stale
+    let expired = client(Duration::ZERO);
+    let grades = expired.grades(42).await;
+    assert!(grades.stale);
+    assert_eq!(grades.assignments.len(), 1);
+    assert!(grades.error.unwrap().contains("rate limit"));
+    server.join().unwrap();
+    // No request is made until the reset
+    let grades = expired.grades(42).await;
+    assert!(grades.stale && grades.assignments.len() == 1);
+
+    let missing_token =
+        ClassroomClient::new(&base_url, None, cache_db, Duration::ZERO, Duration::ZERO).unwrap();
+    let grades = missing_token.grades(7).await;
+    assert!(grades.stale && grades.assignments.is_empty() && grades.fetched_at.is_none());
+}
+
+#[actix_web::test]
+async fn test_classroom_listings_follow_pagination() {
+    use backend::utils::classroom::ClassroomClient;
+    use std::time::Duration;
+
+    // Serves two pages of each listing, linking to the second with an absolute URL like GitHub
+    let (base_url, server) = spawn_mock_server(4, |request_line, headers, _| {
+        let host = &headers.iter().find(|(name, _)| name == "host").unwrap().1;
+        let path = request_line.split(' ').nth(1).unwrap();
+        let (listing, page) = path.split_once('?').unwrap();
+        let next = format!(
+            "<http://{}{}?per_page=100&page=2>; rel=\"next\"",
+            host, listing
+        );
+        let last = format!(
+            "<http://{}{}?per_page=100&page=2>; rel=\"last\"",
+            host, listing
+        );
+        let accepted = |id: u64, login: &str| {
+            serde_json::json!({
+                "id": id,
+                "submitted": true,
+                "passing": id != 3,
+                "commit_count": 2,
+                "grade": "10/10",
+                "students": [{ "login": login, "id": id }],
+                "repository": {
+                    "full_name": format!("bitshala/week-1-{}", login),
+                    "html_url": format!("https://github.com/bitshala/week-1-{}", login)
+                }
+            })
+        };
+        let (body, link) = match (listing, page) {
+            ("/assignments/42/grades", "per_page=100") => (
+                serde_json::json!([sample_grade("alice", 1), sample_grade("bob", 1)]),
+                format!("{}, {}", next, last),
+            ),
+            ("/assignments/42/grades", "per_page=100&page=2") => {
+                (serde_json::json!([sample_grade("carol", 1)]), String::new())
+            }
+            ("/assignments/42/accepted_assignments", "per_page=100") => (
+                serde_json::json!([accepted(1, "alice"), accepted(2, "bob")]),
+                format!("{}, {}", last, next),
+            ),
+            ("/assignments/42/accepted_assignments", "per_page=100&page=2") => {
+                (serde_json::json!([accepted(3, "carol")]), String::new())
+            }
+            other => panic!("unexpected request {:?}", other),
+        };
+        let headers = if link.is_empty() {
+            vec![]
+        } else {
+            vec![("Link".to_string(), link)]
+        };
+        (200, headers, body.to_string())
+    });
+    let cache_db = std::env::temp_dir().join(format!(
+        "classroom_pages_{}_{}.db",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let client = ClassroomClient::new(
+        &base_url,
+        Some("test-token".to_string()),
+        cache_db,
+        Duration::from_secs(300),
+        Duration::from_secs(60),
+    )
+    .unwrap();
+
+    let grades = client.grades(42).await;
+    assert!(!grades.stale);
+    let users: Vec<&str> = grades
+        .assignments
+        .iter()
+        .map(|a| a.github_username.as_str())
+        .collect();
+    assert_eq!(users, vec!["alice", "bob", "carol"]);
+
+    let accepted = client.accepted_assignments(42).await.unwrap();
+    assert_eq!(accepted.len(), 3);
+    assert_eq!(accepted[2].students[0].login, "carol");
+    assert!(!accepted[2].passing);
+    let requests = server.join().unwrap();
+    assert!(requests[1].contains("/assignments/42/grades?per_page=100&page=2"));
+}
+
+#[actix_web::test]
+async fn test_classroom_sync_updates_exercise_flags_and_records_status() {
+    use backend::database::audit::{AuditFilter, read_audit_log};
+    use backend::database::classroom_sync::read_sync_status;
+    use backend::database::operations::{read_from_db, register_cohort_participant, write_to_db};
+    use backend::utils::classroom::ClassroomClient;
+    use backend::utils::classroom_sync::sync_cohort;
+    use backend::utils::cohort::CohortConfig;
+    use backend::utils::types::Table;
+    use std::time::Duration;
+
+    let (base_url, server) = spawn_mock_server(1, |request_line, _, _| {
+        assert!(request_line.starts_with("GET /assignments/42/grades"));
+        let mut failing = sample_grade("bob", 1);
+        failing["points_awarded"] = "50".into();
+        let body = serde_json::json!([sample_grade("alice", 1), failing, sample_grade("carol", 1)]);
+        (200, vec![], body.to_string())
+    });
+    let db = temp_cohort_db("classroom_sync");
+    let table = Table {
+        rows: vec![sample_row("Alice", 1), sample_row("Bob", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    for name in ["Alice", "Bob"] {
+        register_cohort_participant(&db, sample_participant(name), None).unwrap();
+    }
+    let cohort: CohortConfig = serde_json::from_value(serde_json::json!({
+        "name": "PB",
+        "db": db.to_str().unwrap(),
+        "classroom_assignments": { "1": 42 }
+    }))
+    .unwrap();
+    let cache_db = std::env::temp_dir().join(format!(
+        "classroom_sync_cache_{}_{}.db",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let client = ClassroomClient::new(
+        &base_url,
+        Some("test-token".to_string()),
+        cache_db.clone(),
+        Duration::from_secs(300),
+        Duration::from_secs(60),
+    )
+    .unwrap();
+
+    // Carol isn't registered and Bob's tests fail
+    let report = sync_cohort(Some(&client), &cohort, "ta:Bala", true).await;
+    assert!(report.errors.is_empty());
+    assert_eq!(report.changes.len(), 3);
+    server.join().unwrap();
+    let rows = read_from_db(&db).unwrap().rows;
+    let flags = |name: &str| {
+        let row = rows.iter().find(|r| r.name == name).unwrap();
+        (
+            row.exercise_submitted.clone().unwrap(),
+            row.exercise_test_passing.clone().unwrap(),
+        )
+    };
+    assert_eq!(flags("Alice"), ("yes".to_string(), "yes".to_string()));
+    assert_eq!(flags("Bob"), ("yes".to_string(), "no".to_string()));
+    let audited = read_audit_log(
+        &db,
+        &AuditFilter {
+            field: Some("exercise_submitted".to_string()),
+            actor: Some("ta:Bala".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(audited.len(), 2);
+    let status = read_sync_status(&db).unwrap();
+    assert_eq!(
+        status.last_sync_at.as_deref(),
+        Some(report.synced_at.as_str())
+    );
+    assert_eq!(status.last_success_at, status.last_sync_at);
+    assert_eq!((status.last_error, status.updated_fields), (None, 3));
+
+    // A second sync from the cache has nothing to change
+    let report = sync_cohort(Some(&client), &cohort, "system:classroom-sync", false).await;
+    assert!(report.changes.is_empty() && report.errors.is_empty());
+
+    // A failed sync is recorded without losing the last success
+    let success = read_sync_status(&db).unwrap().last_success_at;
+    let missing_token =
+        ClassroomClient::new(&base_url, None, cache_db, Duration::ZERO, Duration::ZERO).unwrap();
+    let report = sync_cohort(Some(&missing_token), &cohort, "system:classroom-sync", true).await;
+    assert_eq!(report.errors.len(), 1);
+    let status = read_sync_status(&db).unwrap();
+    assert!(status.last_error.unwrap().starts_with("Week 1:"));
+    assert_eq!(status.last_success_at, success);
+    assert!(
+        sync_cohort(None, &cohort, "system:classroom-sync", false)
+            .await
+            .errors[0]
+            .contains("isn't configured")
+    );
+}
+
+// The background sync runs on a plain thread with a runtime of its own
+#[test]
+fn test_classroom_sync_thread_builds_its_client_inside_the_runtime() {
+    use backend::database::operations::{read_from_db, register_cohort_participant, write_to_db};
+    use backend::utils::classroom::ClassroomClient;
+    use backend::utils::classroom_sync::sync_cohort_on;
+    use backend::utils::cohort::CohortConfig;
+    use backend::utils::types::Table;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    let (base_url, server) = spawn_mock_server(1, |_, _, _| {
+        let body = serde_json::json!([sample_grade("alice", 1)]);
+        (200, vec![], body.to_string())
+    });
+    let db = temp_cohort_db("classroom_sync_thread");
+    let table = Table {
+        rows: vec![sample_row("Alice", 1)],
+        db_path: None,
+    };
+    write_to_db(&db, &table, "ta:Bala").unwrap();
+    register_cohort_participant(&db, sample_participant("Alice"), None).unwrap();
+    let cohort: CohortConfig = serde_json::from_value(serde_json::json!({
+        "name": "PB",
+        "db": db.to_str().unwrap(),
+        "classroom_assignments": { "1": 42 }
+    }))
+    .unwrap();
+    let cache_db = std::env::temp_dir().join(format!(
+        "classroom_thread_cache_{}_{}.db",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+
+    let report = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // Built on first use, like the shared client
+        let client = OnceLock::new();
+        sync_cohort_on(
+            &runtime,
+            || {
+                Some(client.get_or_init(|| {
+                    ClassroomClient::new(
+                        &base_url,
+                        Some("test-token".to_string()),
+                        cache_db,
+                        Duration::from_secs(300),
+                        Duration::from_secs(60),
+                    )
+                    .unwrap()
+                }))
+            },
+            &cohort,
+            "system:classroom-sync",
+        )
+    })
+    .join()
+    .unwrap();
+    server.join().unwrap();
+    assert!(report.errors.is_empty());
+    // Submitted and passing
+    assert_eq!(report.changes.len(), 2);
+    let rows = read_from_db(&db).unwrap().rows;
+    assert_eq!(rows[0].exercise_submitted.as_deref(), Some("yes"));
+}
+
+#[test]
+fn test_application_review_and_bulk_decisions() {
+    use backend::database::enrollment::{EnrollmentStatus, current_enrollments};
+    use backend::database::operations::{read_from_db, register_cohort_participant};
+    use backend::database::outbox::list_messages;
+    use backend::database::reviews::{
+        Decision, ReviewError, decide_applications, default_review_criteria, review_queue,
+        submit_review,
+    };
+    use backend::database::waitlist::{next_waitlist_position, read_waitlist};
+    use backend::utils::bot_invites::BOT_INVITE;
+    use backend::utils::types::CohortParticipant;
+    use std::collections::BTreeMap;
+
+    let db_path = temp_cohort_db("reviews");
+    for name in ["Alice", "Bob", "Carol"] {
+        register_cohort_participant(&db_path, sample_participant(name), None).unwrap();
+    }
+    let criteria = default_review_criteria();
+    let scores = |points: &[u32]| -> BTreeMap<String, u32> {
+        criteria
+            .iter()
+            .zip(points)
+            .map(|(c, &p)| (c.name.clone(), p))
+            .collect()
+    };
+
+    assert!(matches!(
+        submit_review(
+            &db_path,
+            "alice@example.com",
+            "ta:Bala",
+            &scores(&[5, 5, 5]),
+            None,
+            &criteria
+        ),
+        Err(ReviewError::MissingScore(_))
+    ));
+    assert!(matches!(
+        submit_review(
+            &db_path,
+            "alice@example.com",
+            "ta:Bala",
+            &scores(&[6, 5, 5, 5]),
+            None,
+            &criteria
+        ),
+        Err(ReviewError::ScoreOutOfRange(..))
+    ));
+    submit_review(
+        &db_path,
+        "alice@example.com",
+        "ta:Bala",
+        &scores(&[5, 4, 4, 5]),
+        Some("Strong motivation"),
+        &criteria,
+    )
+    .unwrap();
+    submit_review(
+        &db_path,
+        "alice@example.com",
+        "ta:Raj",
+        &scores(&[4, 4, 4, 4]),
+        None,
+        &criteria,
+    )
+    .unwrap();
+    submit_review(
+        &db_path,
+        "bob@example.com",
+        "ta:Raj",
+        &scores(&[1, 1, 0, 1]),
+        None,
+        &criteria,
+    )
+    .unwrap();
+
+    let queue = review_queue(&db_path, false).unwrap();
+    let order: Vec<&str> = queue.iter().map(|a| a.email.as_str()).collect();
+    assert_eq!(
+        order,
+        vec!["alice@example.com", "bob@example.com", "carol@example.com"]
+    );
+    assert_eq!(queue[0].average_score, Some(17.0));
+    assert_eq!(queue[0].reviews.len(), 2);
+
+    // Dave and Erin register once the seats are gone, and a second Alice after everyone
+    for name in ["Dave", "Erin"] {
+        register_cohort_participant(&db_path, sample_participant(name), Some(3)).unwrap();
+    }
+    verify_participant(&db_path, "dave@example.com");
+    let namesake = CohortParticipant {
+        email: "alice.b@example.com".to_string(),
+        ..sample_participant("Alice")
+    };
+    register_cohort_participant(&db_path, namesake, None).unwrap();
+    assert!(list_messages(&db_path, BOT_INVITE).unwrap().is_empty());
+
+    let report = decide_applications(
+        &db_path,
+        &[
+            "alice@example.com".to_string(),
+            "carol@example.com".to_string(),
+            "dave@example.com".to_string(),
+            "alice.b@example.com".to_string(),
+        ],
+        Decision::Accept,
+        None,
+        "ta:Bala",
+    )
+    .unwrap();
+    assert_eq!(
+        report.accepted,
+        vec!["alice@example.com", "carol@example.com", "dave@example.com"]
+    );
+    // The roster already has an Alice
+    assert_eq!(report.skipped, vec!["alice.b@example.com"]);
+    // Accepting from the waitlist works like a promotion
+    let waitlist = read_waitlist(&db_path).unwrap();
+    assert_eq!(
+        waitlist
+            .iter()
+            .map(|e| (e.email.as_str(), e.position))
+            .collect::<Vec<_>>(),
+        vec![("erin@example.com", 1)]
+    );
+    let invited: Vec<String> = list_messages(&db_path, BOT_INVITE)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.email)
+        .collect();
+    assert_eq!(invited, vec!["dave@example.com"]);
+
+    let report = decide_applications(
+        &db_path,
+        &[
+            "bob@example.com".to_string(),
+            "alice@example.com".to_string(),
+            "erin@example.com".to_string(),
+        ],
+        Decision::Reject,
+        Some("Not this time"),
+        "ta:Bala",
+    )
+    .unwrap();
+    assert_eq!(report.rejected, vec!["bob@example.com", "erin@example.com"]);
+    assert_eq!(report.skipped, vec!["alice@example.com"]);
+
+    // Accepted applicants are on the roster and marked accepted
+    let week_0: Vec<String> = read_from_db(&db_path)
+        .unwrap()
+        .rows
+        .into_iter()
+        .filter(|r| r.week == 0)
+        .map(|r| r.name)
+        .collect();
+    assert_eq!(week_0, vec!["Alice", "Carol", "Dave"]);
+    let enrollments = current_enrollments(&db_path).unwrap();
+    assert_eq!(
+        enrollments["alice@example.com"].status,
+        EnrollmentStatus::Accepted
+    );
+    assert_eq!(
+        enrollments["alice.b@example.com"].status,
+        EnrollmentStatus::Applied
+    );
+    // Rejected applicants are done: no seat, no place in line and no invite
+    for email in ["bob@example.com", "erin@example.com"] {
+        assert_eq!(enrollments[email].status, EnrollmentStatus::Rejected);
+    }
+    assert!(read_waitlist(&db_path).unwrap().is_empty());
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    assert_eq!(next_waitlist_position(&conn, Some(4)).unwrap(), Some(1));
+    assert_eq!(next_waitlist_position(&conn, Some(5)).unwrap(), None);
+    assert!(!verify_participant(&db_path, "erin@example.com").invite_queued);
+
+    let queue = review_queue(&db_path, false).unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].email, "alice.b@example.com");
+    assert_eq!(review_queue(&db_path, true).unwrap().len(), 6);
+}
@@ -3,6 +3,7 @@ import { useLocation, Navigate } from 'react-router-dom';
 import {
   getTokenFromLocation,
   isTaAuthenticated,
+  storeTaSession,
   storeToken,
 } from '../services/auth';
 
@@ -17,6 +18,11 @@ const ProtectedRoute = ({ element }: ProtectedRouteProps) => {
   if (token) {
     storeToken(token);
   }
+  // The Discord login sends the TA's session along with the token
+  const session = new URLSearchParams(location.search).get('session');
+  if (session) {
+    storeTaSession(session);
+  }
 
   return isTaAuthenticated(token) ? element : <Navigate to="/" replace />;
 };
@@ -42,8 +42,9 @@ export const CohortSelection = () => {
 
   useEffect(() => {
     const url = new URL(window.location.href);
-    if (url.searchParams.has('token')) {
+    if (url.searchParams.has('token') || url.searchParams.has('session')) {
       url.searchParams.delete('token');
+      url.searchParams.delete('session');
       window.history.replaceState({}, document.title, url.pathname);
     }
   }, []);
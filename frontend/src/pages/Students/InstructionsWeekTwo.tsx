@@ -58,7 +58,9 @@ export default function InstructionsWeekTwo() {
   const fetchStudentData = useCallback(async (email: string) => {
     setLoading(true);
     try {
-      const response = await fetch(`${getApiBaseUrl()}/individual_data_email/${encodeURIComponent(email)}`);
+      const response = await fetch(`${getApiBaseUrl()}/individual_data_email/${encodeURIComponent(email)}`, {
+        headers: { Authorization: `Bearer ${localStorage.getItem('participant_session') ?? ''}` },
+      });
       if (response.ok) {
         const data = await response.json();
         setStudentData(data);
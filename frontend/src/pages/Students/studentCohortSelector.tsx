@@ -54,6 +54,12 @@ export default function StudentCohortSelector() {
       localStorage.setItem('user_token', token);
       console.log('✅ Stored user token');
     }
+
+    // Session the backend bound to this participant's email, for their own data
+    const session = params.get('session');
+    if (session) {
+      localStorage.setItem('participant_session', session);
+    }
     
     if (role) {
       localStorage.setItem('user_role', role);
@@ -7,6 +7,7 @@ import { AddStudentModal } from '../components/table/AddStudentModal';
 import { TableContextMenu } from '../components/table/TableContextMenu';
 
 import { computeTotal } from '../utils/calculations';
+import { taAuthHeader } from '../services/auth';
 import type { TableRowData } from '../types/student';
 
 const AUTH_TOKEN = import.meta.env.VITE_AUTH_TOKEN;
@@ -296,7 +297,7 @@ const TableView: React.FC = () => {
 
     fetch(`${baseUrl}/weekly_data/${cohort_name}/${week}`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
+      headers: { 'Content-Type': 'application/json', ...taAuthHeader() },
       body: JSON.stringify(payload),
     })
       .then(r => {
@@ -351,7 +352,7 @@ const TableView: React.FC = () => {
 
     fetch(`${baseUrl}/students/${cohort_name}`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
+      headers: { 'Content-Type': 'application/json', ...taAuthHeader() },
       body: JSON.stringify(payload),
     })
       .then(r => {
@@ -401,7 +402,7 @@ const TableView: React.FC = () => {
 
     fetch(`${baseUrl}/del/${cohort_name}/${week}`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
+      headers: { 'Content-Type': 'application/json', ...taAuthHeader() },
       body: JSON.stringify(payload),
     })
       .then(response => {
@@ -52,12 +52,22 @@ export const storeToken = (token: string): void => {
 
 export const clearToken = (): void => {
   localStorage.removeItem('bitshala_token');
+  localStorage.removeItem('ta_session');
 };
 
 export const getStoredToken = (): string | null => {
   return localStorage.getItem('bitshala_token');
 };
 
+// Session handed out at login. Writes send it so the audit log records which TA made them.
+export const storeTaSession = (session: string): void => {
+  localStorage.setItem('ta_session', session);
+};
+
+export const taAuthHeader = (): { Authorization: string } => {
+  return { Authorization: `Bearer ${localStorage.getItem('ta_session') ?? ''}` };
+};
+
 export const redirectToDiscordAuth = (role: string): void => {
   const SCOPES = encodeURIComponent('identify guilds email guilds.join');
   let encodedRedirectUri;
@@ -91,10 +101,14 @@ export const handleDiscordCallback = (
   const role = params.get('role');
   const email = params.get('email');
   const username = params.get('username');
+  const session = params.get('session');
 
   const expectedToken = role === 'participant' ? AUTH_TOKEN_PARTICIPANT : AUTH_TOKEN_TA;
   
   if (authSource === 'discord' && token === expectedToken) {
+    if (session && role !== 'participant') {
+      storeTaSession(session);
+    }
     // Redirect TAs to select page, participants to instructions
     const redirectPath = getRedirectPathForToken(token!);
     navigate(redirectPath, { 
@@ -133,6 +147,9 @@ export const loginWithEmail = async (
     const token = data.token;
 
     if (token === AUTH_TOKEN_TA) {
+      if (data.session) {
+        storeTaSession(data.session);
+      }
       return { success: true, token };
     } else {
       return { success: false, error: 'Invalid token returned from server.' };